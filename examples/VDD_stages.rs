@@ -4,12 +4,13 @@ extern crate robust_verifiable_dp as dp;
 use dp::public_parameters::PublicParameters;
 use dp::constants;
 use dp::sign;
-use dp::replicated::{ReplicaSecret,ReplicaCommitment};
+use dp::replicated::{ReplicaSecret,ReplicaCommitment,SharingScheme};
 use std::time::Instant;
-use dp::sigma_or::{create_proof_1, create_proof_0};
+use dp::sigma_or::{create_proof_1, create_proof_0, ProofContext};
 
 use blstrs::Scalar;
 
+const SESSION_ID: u64 = 0;
 const NUM_CLIENTS: usize = 1000;
 
 fn format_duration(nanos: u128) -> String {
@@ -26,6 +27,7 @@ fn main(){
     // Create public parameters
     //生成公共参数
     let pp = PublicParameters::new( b"seed");
+    let scheme = SharingScheme::new(constants::PROVER_NUM, constants::THRESHOLD);
 
     let mut pks=Vec::new();
     let mut sig_keys =Vec::new();
@@ -44,8 +46,8 @@ fn main(){
         let x: bool = rand::random();
         xvec.push(x);
         let x_scalar = Scalar::from(x as u64);
-        let secret=ReplicaSecret::new(x_scalar.clone());
-        let coms=ReplicaCommitment::new(secret.commit(pp.get_commit_base().clone()));
+        let secret=ReplicaSecret::new(x_scalar.clone(), &scheme);
+        let coms=ReplicaCommitment::new(&scheme, secret.commit(pp.get_commit_base().clone()));
         comsvec.push(coms);
         let mut shares = Vec::new();
         for i in 0..constants::PROVER_NUM{
@@ -59,12 +61,13 @@ fn main(){
     let mut proofvec = Vec::new();   
     for i in 0..NUM_CLIENTS{
         let r_sum=secretvec[i].get_sum_r();
+        let ctx = ProofContext::new(SESSION_ID, i as u64, &pp, &comsvec[i]);
         let proof;
         if xvec[i]{
-            proof = create_proof_1(&pp.get_commit_base(), Scalar::from(xvec[i] as u64), r_sum.clone());
+            proof = create_proof_1(&pp.get_commit_base(), Scalar::from(xvec[i] as u64), r_sum.clone(), &ctx);
         }
         else{
-            proof = create_proof_0(&pp.get_commit_base(), Scalar::from(xvec[i] as u64), r_sum.clone());
+            proof = create_proof_0(&pp.get_commit_base(), Scalar::from(xvec[i] as u64), r_sum.clone(), &ctx);
         }
         proofvec.push(proof);
     }
@@ -72,7 +75,7 @@ fn main(){
     for i in 0..NUM_CLIENTS{
         let share=sharesvec[i][0].clone();
         let coms=comsvec[i].clone();
-        share.check_com(pp.get_commit_base(), coms);
+        share.check_com(&scheme, pp.get_commit_base(), coms);
         
     }
 
@@ -80,7 +83,8 @@ fn main(){
         let proof=proofvec[i].clone();
         let coms=comsvec[i].clone();
         let recon=coms.get_sum();
-        proof.verify(pp.get_commit_base(), recon);
+        let ctx = ProofContext::new(SESSION_ID, i as u64, &pp, &coms);
+        proof.verify(pp.get_commit_base(), recon, &ctx);
     }
 
     
@@ -0,0 +1,44 @@
+extern crate robust_verifiable_dp as dp;
+
+use std::env;
+use std::net::TcpListener;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use dp::async_communicator::ServerConfig;
+use dp::board_service::{serve_board, BoardContext};
+use dp::bulletin_board::MerkleBoard;
+use dp::client_auth::AuthenticatedStore;
+use dp::deployment::{self, Deployment};
+use dp::record_log::FsyncPolicy;
+use dp::tls::{server_config, PeerDirectory};
+
+// 用法: board_server <deployment.dpfile> <board.dpfile> [密钥目录]
+// 密钥目录中需要 board_sk.dpfile, pks.dpfile, verifier_pk.dpfile 和客户端注册表 clients.dpfile。
+// 只接受注册表中的客户端用身份密钥签名的提交；每次修改记入 Merkle 日志，树头用 board_sk 签名。
+// 信道用 TLS，连接方的证书必须是 prover、verifier 或注册客户端的密钥，写入按证书对应的身份授权；
+// 不带证书的连接只能读
+fn main(){
+    let args: Vec<String> = env::args().collect();
+    if args.len() < 3 {
+        eprintln!("usage: {} <deployment file> <board file> [key dir]", args[0]);
+        std::process::exit(2);
+    }
+    let deployment = Deployment::load(&args[1]).unwrap();
+    let key_dir = Path::new(args.get(3).map(String::as_str).unwrap_or("."));
+    let board_sk = deployment::load_signing_key(key_dir.join("board_sk.dpfile")).unwrap();
+    let pks = deployment::load_verifying_keys(key_dir.join("pks.dpfile")).unwrap();
+    let verifier_pk = deployment::load_verifying_key(key_dir.join("verifier_pk.dpfile")).unwrap();
+    let registry = deployment::load_client_registry(key_dir.join("clients.dpfile")).unwrap();
+    println!("{} clients registered", registry.len());
+
+    let peers = Arc::new(PeerDirectory::new(&pks, verifier_pk, registry.clone()));
+    let tls = server_config(&board_sk, peers.clone(), false).unwrap();
+    let store = MerkleBoard::open(&args[2], &deployment.scheme(), board_sk, FsyncPolicy::Always).unwrap();
+    println!("Board log holds {} entries", store.size());
+    let store = AuthenticatedStore::new(store, registry, deployment.session_id);
+    let listener = TcpListener::bind(&deployment.board_addr).unwrap();
+    println!("Bulletin board listening on {}", deployment.board_addr);
+    let ctx = BoardContext { session_id: deployment.session_id, scheme: deployment.scheme(), pp: deployment.public_parameters(), peers };
+    serve_board(listener, Arc::new(Mutex::new(store)), ctx, tls, ServerConfig::default(), |e| eprintln!("board connection failed: {}", e)).unwrap();
+}
@@ -2,15 +2,67 @@ extern crate robust_verifiable_dp as dp;
 
 
 use std::env;
+use std::time::Duration;
 
-use dp::public_parameters::PublicParameters;
+use dp::board_service::RemoteBoard;
+use dp::client::Client;
+use dp::client_session::{ClientSession, RetryPolicy, TlsTransport};
+use dp::deployment::{self, Deployment};
+use dp::protocol::Role;
+use dp::tls::client_config;
 
+const TIMEOUT: Duration = Duration::from_secs(10);
 
+// 用法: client <输入值> <客户端 id> <deployment.dpfile> <pks.dpfile> <client{id}.dpfile> <board_pk.dpfile>
+// 输入值: 直方图部署为桶编号，多比特部署为整数，否则为 0/1
 fn main(){
-    // TODO: input args
-    let x =true;
-    let id = 2024;
-    let pp=PublicParameters::new("seed".as_bytes());
-    
+    let args: Vec<String> = env::args().collect();
+    if args.len() != 7 {
+        eprintln!("usage: {} <input> <client id> <deployment file> <prover key file> <client key file> <board key file>", args[0]);
+        std::process::exit(2);
+    }
+    let x: u64 = args[1].parse().expect("input must be a non-negative integer");
+    let id: u64 = args[2].parse().expect("client id must be a number");
+    let deployment = Deployment::load(&args[3]).unwrap();
+    let pks = deployment::load_verifying_keys(&args[4]).unwrap();
+    let identity = deployment::load_signing_key(&args[5]).unwrap();
+    let board_pk = deployment::load_verifying_key(&args[6]).unwrap();
+    let pp = deployment.public_parameters();
+    let scheme = deployment.scheme();
 
-}
\ No newline at end of file
+    let client = if pp.get_buckets() > 1 {
+        Client::new_histogram(deployment.session_id, id, x as usize, &pp, &scheme, pks.clone())
+    } else if pp.get_input_bits() > 1 {
+        Client::new_range(deployment.session_id, id, x, &pp, &scheme, pks.clone())
+    } else if x <= 1 {
+        Ok(Client::new(deployment.session_id, id, x == 1, &pp, &scheme, pks.clone()))
+    } else {
+        panic!("input must be 0 or 1");
+    }.unwrap();
+
+    // 与公告板和 prover 的信道都用身份密钥认证
+    let board_tls = client_config(board_pk, Some(&identity)).unwrap();
+    let mut board = RemoteBoard::connect(&deployment.board_addr, board_tls, &scheme, deployment.session_id, Role::Client(id)).unwrap();
+    let mut transport = TlsTransport::new(&deployment.prover_addrs, &pks, &identity, deployment.session_id, TIMEOUT).unwrap();
+    let mut session = ClientSession::new(client, deployment.session_id, &scheme, &pp, &pks, board_pk, RetryPolicy::default())
+        .with_identity(identity);
+    session.commit(&mut board).unwrap();
+    println!("Posted commitments of client {}", id);
+
+    // 把份额发给每个 prover；失败的 prover 之后在公告板上公开份额
+    session.send_shares(&mut transport).unwrap();
+    for i in 0..pks.len() {
+        match session.get_error(i) {
+            None => println!("Prover {} accepted its share", i),
+            Some(e) => println!("Prover {} failed: {}", i, e),
+        }
+    }
+
+    let missing = session.await_signatures(&board).unwrap();
+    println!("{} of {} provers signed", pks.len() - missing.len(), pks.len());
+
+    session.reveal(&mut board).unwrap();
+    println!("Revealed the shares of provers {:?}", session.revealed());
+    let receipt = session.finalize(&board).unwrap();
+    println!("Contribution of client {} is counted: {}", receipt.user.id, receipt.verify(&scheme, &pks, &pp));
+}
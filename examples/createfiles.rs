@@ -1,76 +1,66 @@
-use std::io::Write;
-use rand::Rng;
-
-use blstrs::{G1Projective,Scalar};
-use robust_verifiable_dp::sig;
-use robust_verifiable_dp::util;
-use robust_verifiable_dp::public_parameters::PublicParameters;
-use robust_verifiable_dp::prover::Prover;
-
-const N_B: usize = 10;
-// NUM_PROVERS >= 2*THRESHOLD + 1
-const NUM_PROVERS: usize = 7;
-const THRESHOLD: usize = 3;
-const TYPES: usize = 20;
+extern crate robust_verifiable_dp as dp;
 
+use std::env;
+use std::fs;
+use std::path::PathBuf;
 
+use dp::client_auth::ClientRegistry;
+use dp::constants;
+use dp::deployment::{self, Deployment};
+use dp::privacy::PrivacyGuarantee;
+use dp::public_parameters::PublicParameters;
+use dp::sign;
 
+const SEED: &[u8] = b"seed";
+const BOARD_PORT: u16 = 7000;
+const NUM_CLIENTS: u64 = 16;
 
+// 生成部署配置和密钥文件：deployment.dpfile, pks.dpfile, sk{i}.dpfile,
+// 公告板的树头签名密钥 board_sk.dpfile / board_pk.dpfile, verifier 的密钥 verifier_sk.dpfile / verifier_pk.dpfile,
+// 客户端身份密钥 client{id}.dpfile 和公告板的客户端注册表 clients.dpfile
+// 用法: createfiles [输出目录]
 fn main(){
-    let pp = PublicParameters::new(
-        N_B, NUM_PROVERS, THRESHOLD, b"seed"
-    );
+    let dir = PathBuf::from(env::args().nth(1).unwrap_or_else(|| ".".to_string()));
+    fs::create_dir_all(&dir).unwrap();
 
+    let target = PrivacyGuarantee::new(1.0, 1e-6);
+    let noise_bits = PublicParameters::new(SEED).with_privacy(target).get_noise_bits();
+    let deployment = Deployment {
+        seed: SEED.to_vec(),
+        session_id: 0,
+        prover_num: constants::PROVER_NUM,
+        threshold: constants::THRESHOLD,
+        input_bits: 1,
+        buckets: 1,
+        noise_bits,
+        board_addr: format!("127.0.0.1:{}", BOARD_PORT),
+        prover_addrs: (0..constants::PROVER_NUM).map(|i| format!("127.0.0.1:{}", BOARD_PORT + 1 + i as u16)).collect(),
+    };
+    deployment.save(dir.join("deployment.dpfile")).unwrap();
 
     // 生成密钥文件
-    let keys=sig::generate_ed_sig_keys(NUM_PROVERS);
     let mut pks = Vec::new();
-    let mut sks = Vec::new();
-    for i in 0..NUM_PROVERS {
-        pks.push(keys[i].public_key.clone());
-        sks.push(keys[i].private_key.clone());
-    }
-    //将pks整体使用bcs库序列化到文件中
-    let pk_bytes = bcs::to_bytes(&pks).unwrap();
-    let mut pk_file = std::fs::File::create("pks.dpfile").unwrap();
-    pk_file.write_all(&pk_bytes).unwrap();
-    //将sks的每个元素使用bcs库序列化到文件中，并标号
-    for i in 0..NUM_PROVERS {
-        let sk_bytes = bcs::to_bytes(&sks[i].clone()).unwrap();
-        let mut sk_file = std::fs::File::create(format!("sk{}.dpfile",i)).unwrap();
-        sk_file.write_all(&sk_bytes).unwrap();
+    for i in 0..constants::PROVER_NUM {
+        let (sk, pk) = sign::gen_keys();
+        deployment::save_signing_key(dir.join(format!("sk{}.dpfile", i)), &sk).unwrap();
+        pks.push(pk);
     }
-    let mut rng = rand::thread_rng();
+    deployment::save_verifying_keys(dir.join("pks.dpfile"), &pks).unwrap();
 
-    let mut comsvecvecvec=Vec::new();
-    for i in 0..NUM_PROVERS {
-        let mut boolvecvec=Vec::new();
-        let mut Scalarvecvec=Vec::new();
-        let mut comsvecvec=Vec::new();
-        for j in 0..TYPES {
-            let boolvec:Vec<bool>=(0..(N_B)).map(|_| rng.gen_bool(0.5)).collect();
-            boolvecvec.push(boolvec.clone());
-            let Scalarvec = util::random_scalars(N_B, &mut rng);
-            Scalarvecvec.push(Scalarvec.clone());
-            let prover = Prover::new(i,boolvec,Scalarvec,&pp,keys[i].private_key.clone());
-            comsvecvec.push(prover.get_coms_v_k().clone());
-        }
-        //将boolvecvec整体使用bcs库序列化到文件中
-        let boolvecvec_bytes = bcs::to_bytes(&boolvecvec).unwrap();
-        let mut boolvecvec_file = std::fs::File::create(format!("boolvecvec{}.dpfile",i)).unwrap();
-        boolvecvec_file.write_all(&boolvecvec_bytes).unwrap();
-        //将Scalarvecvec整体使用bcs库序列化到文件中
-        let Scalarvecvec_bytes = bcs::to_bytes(&Scalarvecvec).unwrap();
-        let mut Scalarvecvec_file = std::fs::File::create(format!("Scalarvecvec{}.dpfile",i)).unwrap();
-        Scalarvecvec_file.write_all(&Scalarvecvec_bytes).unwrap();
-        //将comsvecvec传入comsvecvecvec
-        comsvecvecvec.push(comsvecvec.clone());
-    }
-    //将comsvecvecvec整体使用bcs库序列化到文件中
-    let comsvecvecvec_bytes = bcs::to_bytes(&comsvecvecvec).unwrap();
-    let mut comsvecvecvec_file = std::fs::File::create("comsvecvecvec.dpfile").unwrap();
-    comsvecvecvec_file.write_all(&comsvecvecvec_bytes).unwrap();
-    
-    
+    let (board_sk, board_pk) = sign::gen_keys();
+    deployment::save_signing_key(dir.join("board_sk.dpfile"), &board_sk).unwrap();
+    deployment::save_verifying_key(dir.join("board_pk.dpfile"), &board_pk).unwrap();
+
+    let (verifier_sk, verifier_pk) = sign::gen_keys();
+    deployment::save_signing_key(dir.join("verifier_sk.dpfile"), &verifier_sk).unwrap();
+    deployment::save_verifying_key(dir.join("verifier_pk.dpfile"), &verifier_pk).unwrap();
 
-}
\ No newline at end of file
+    let mut registry = ClientRegistry::new();
+    for id in 0..NUM_CLIENTS {
+        let (sk, pk) = sign::gen_keys();
+        deployment::save_signing_key(dir.join(format!("client{}.dpfile", id)), &sk).unwrap();
+        registry.register(id, pk).unwrap();
+    }
+    deployment::save_client_registry(dir.join("clients.dpfile"), &registry).unwrap();
+    println!("Wrote a deployment of {} provers and {} clients with {} noise bits to {}", constants::PROVER_NUM, NUM_CLIENTS, noise_bits, dir.display());
+}
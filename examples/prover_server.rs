@@ -1,8 +1,73 @@
+extern crate robust_verifiable_dp as dp;
+
 use std::env;
+use std::path::Path;
+use std::sync::Arc;
 
-fn main(){
+use tokio::net::TcpListener;
 
+use dp::async_communicator::{serve_async, ServerConfig};
+use dp::board_service::RemoteBoard;
+use dp::deployment::{self, Deployment};
+use dp::prover::Prover;
+use dp::protocol::Role;
+use dp::prover_service::{serve_connection_async, ProverService};
+use dp::record_log::FsyncPolicy;
+use dp::share_store::FileShareStore;
+use dp::tls::{self, client_config, server_config, PeerDirectory};
 
+// 用法: prover_server <deployment.dpfile> <prover 编号> [密钥目录]
+// 份额保存在 shares{i}.dpfile，收到 verifier 的 Shutdown 请求后退出；聚合前用 board_pk.dpfile 审计公告板日志。
+// 信道用 TLS，连接方必须用 prover、verifier（verifier_pk.dpfile）或注册客户端（clients.dpfile）的密钥认证
+#[tokio::main]
+async fn main(){
+    let args: Vec<String> = env::args().collect();
+    if args.len() < 3 {
+        eprintln!("usage: {} <deployment file> <prover index> [key dir]", args[0]);
+        std::process::exit(2);
+    }
+    let deployment = Deployment::load(&args[1]).unwrap();
+    let index: usize = args[2].parse().expect("prover index must be a number");
+    let key_dir = Path::new(args.get(3).map(String::as_str).unwrap_or("."));
+    let sk = deployment::load_signing_key(key_dir.join(format!("sk{}.dpfile", index))).unwrap();
+    let pks = deployment::load_verifying_keys(key_dir.join("pks.dpfile")).unwrap();
+    let board_pk = deployment::load_verifying_key(key_dir.join("board_pk.dpfile")).unwrap();
+    let verifier_pk = deployment::load_verifying_key(key_dir.join("verifier_pk.dpfile")).unwrap();
+    let registry = deployment::load_client_registry(key_dir.join("clients.dpfile")).unwrap();
+    let peers = Arc::new(PeerDirectory::new(&pks, verifier_pk, registry));
+    let tls_config = server_config(&sk, peers.clone(), true).unwrap();
+    let board_tls = client_config(board_pk, Some(&sk)).unwrap();
 
+    let pp = deployment.public_parameters();
+    let scheme = deployment.scheme();
+    // 份额库在整个进程生命周期内存在
+    let share_store = Box::leak(Box::new(FileShareStore::open(format!("shares{}.dpfile", index), FsyncPolicy::Always).unwrap()));
+    let prover = Prover::new(index, deployment.session_id, &pp, &scheme, sk, &pks, share_store).unwrap();
+    let board = RemoteBoard::connect(&deployment.board_addr, board_tls, &scheme, deployment.session_id, Role::Prover(index)).unwrap();
+    let service = Arc::new(ProverService::new(prover, board, board_pk));
 
-}
\ No newline at end of file
+    let listener = TcpListener::bind(&deployment.prover_addrs[index]).await.unwrap();
+    println!("Prover {} listening on {}", index, deployment.prover_addrs[index]);
+    let config = ServerConfig::default();
+    serve_async(listener, config, move |comm| {
+        let (service, tls_config, peers) = (service.clone(), tls_config.clone(), peers.clone());
+        async move {
+            let res = async {
+                let mut comm = tls::accept(comm.into_inner(), tls_config, config.timeout).await?;
+                let peer = tls::client_key(&comm).and_then(|pk| peers.role_of(&pk));
+                serve_connection_async(service, peer, &mut comm).await
+            }.await;
+            match res {
+                Ok(true) => Ok(()),
+                Ok(false) => {
+                    println!("Prover {} shutting down", index);
+                    std::process::exit(0);
+                }
+                Err(e) => {
+                    eprintln!("connection failed: {}", e);
+                    Err(e)
+                }
+            }
+        }
+    }).await.unwrap();
+}
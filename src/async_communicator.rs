@@ -0,0 +1,201 @@
+// Communicator 的异步版本（tokio），帧格式与 SyncTcpCommunicator 相同
+// 每次读写都有超时，服务端用信号量限制同时处理的连接数
+use serde::{Serialize, Deserialize};
+use std::future::Future;
+use std::io;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream, ToSocketAddrs};
+use tokio::sync::Semaphore;
+use crate::communicator::{decode_payload, encode_frame, frame_length};
+use crate::protocol::{self, Envelope, Message, Role};
+
+#[allow(async_fn_in_trait)]
+pub trait AsyncCommunicator {
+    async fn send<T: Serialize>(&mut self, message: &T) -> io::Result<()>;
+    async fn receive<T: for<'de> Deserialize<'de>>(&mut self) -> io::Result<T>;
+    /// The undecoded payload of the next frame.
+    async fn receive_frame(&mut self) -> io::Result<Vec<u8>>;
+
+    async fn send_ack(&mut self) -> io::Result<()>;
+    async fn receive_ack(&mut self) -> io::Result<()>;
+
+    /// Sends `message` in an envelope of the current protocol version.
+    async fn send_message(&mut self, session_id: u64, sender: Role, message: Message) -> io::Result<()> {
+        self.send(&Envelope::new(session_id, sender, message)).await
+    }
+
+    /// Receives an envelope, rejecting other protocol versions and sessions.
+    async fn receive_message(&mut self, session_id: u64) -> io::Result<(Role, Message)> {
+        protocol::decode(&self.receive_frame().await?)?.open(session_id)
+    }
+}
+
+/// Length-prefixed BCS framing over any async byte stream.
+pub struct AsyncStreamCommunicator<S> {
+    stream: S,
+    timeout: Option<Duration>,
+}
+
+pub type TokioTcpCommunicator = AsyncStreamCommunicator<TcpStream>;
+
+async fn with_timeout<F: Future<Output = io::Result<T>>, T>(timeout: Option<Duration>, f: F) -> io::Result<T> {
+    match timeout {
+        Some(t) => tokio::time::timeout(t, f).await
+            .map_err(|_| io::Error::new(io::ErrorKind::TimedOut, "Operation timed out"))?,
+        None => f.await,
+    }
+}
+
+impl<S> AsyncStreamCommunicator<S> {
+    pub fn new(stream: S) -> Self {
+        Self { stream, timeout: None }
+    }
+
+    /// Timeout of each send or receive; `None` waits forever.
+    pub fn with_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    pub fn get_ref(&self) -> &S {
+        &self.stream
+    }
+
+    pub fn into_inner(self) -> S {
+        self.stream
+    }
+}
+
+impl TokioTcpCommunicator {
+    pub async fn connect<A: ToSocketAddrs>(addr: A, timeout: Option<Duration>) -> io::Result<Self> {
+        let stream = with_timeout(timeout, TcpStream::connect(addr)).await?;
+        Ok(Self::new(stream).with_timeout(timeout))
+    }
+}
+
+impl<S: AsyncRead + AsyncWrite + Unpin> AsyncCommunicator for AsyncStreamCommunicator<S> {
+    async fn send<T: Serialize>(&mut self, message: &T) -> io::Result<()> {
+        let frame = encode_frame(message)?;
+        with_timeout(self.timeout, async {
+            self.stream.write_all(&frame).await?;
+            self.stream.flush().await
+        }).await
+    }
+
+    async fn receive<T: for<'de> Deserialize<'de>>(&mut self) -> io::Result<T> {
+        decode_payload(&self.receive_frame().await?)
+    }
+
+    async fn receive_frame(&mut self) -> io::Result<Vec<u8>> {
+        with_timeout(self.timeout, async {
+            let mut length_bytes = [0; 4];
+            self.stream.read_exact(&mut length_bytes).await?;
+            let mut buffer = vec![0; frame_length(length_bytes)?];
+            self.stream.read_exact(&mut buffer).await?;
+            Ok(buffer)
+        }).await
+    }
+
+    async fn send_ack(&mut self) -> io::Result<()> {
+        with_timeout(self.timeout, async {
+            self.stream.write_all(b"ACK").await?;
+            self.stream.flush().await
+        }).await
+    }
+
+    async fn receive_ack(&mut self) -> io::Result<()> {
+        let mut ack_buf = [0; 3];
+        with_timeout(self.timeout, self.stream.read_exact(&mut ack_buf)).await?;
+        if &ack_buf == b"ACK" {
+            Ok(())
+        } else {
+            Err(io::Error::new(io::ErrorKind::InvalidData, "Invalid ACK received"))
+        }
+    }
+}
+
+/// Limits of an async server.
+#[derive(Clone, Copy, Debug)]
+pub struct ServerConfig {
+    /// Connections handled at the same time; further ones wait in the accept queue.
+    pub max_connections: usize,
+    /// Timeout of every send and receive on a connection.
+    pub timeout: Option<Duration>,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self { max_connections: 1024, timeout: Some(Duration::from_secs(30)) }
+    }
+}
+
+/// Accepts connections on `listener` and runs `handler` on each in its own task, at most
+/// `config.max_connections` at a time. Runs until accepting fails. The error of a single
+/// connection is dropped; a handler that wants it reported reports it itself.
+pub async fn serve_async<F, Fut>(listener: TcpListener, config: ServerConfig, handler: F) -> io::Result<()>
+where
+    F: Fn(TokioTcpCommunicator) -> Fut,
+    Fut: Future<Output = io::Result<()>> + Send + 'static,
+{
+    let permits = Arc::new(Semaphore::new(config.max_connections.max(1)));
+    loop {
+        let permit = permits.clone().acquire_owned().await
+            .map_err(|e| io::Error::other(e.to_string()))?;
+        let (stream, _) = listener.accept().await?;
+        let comm = TokioTcpCommunicator::new(stream).with_timeout(config.timeout);
+        let task = handler(comm);
+        tokio::spawn(async move {
+            let _ = task.await;
+            drop(permit);
+        });
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+    use tokio::net::TcpListener;
+    use super::*;
+
+    #[tokio::test]
+    async fn test_async_communicator() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let active = Arc::new(AtomicUsize::new(0));
+        let peak = Arc::new(AtomicUsize::new(0));
+        let (active_, peak_) = (active.clone(), peak.clone());
+        let config = ServerConfig { max_connections: 2, timeout: Some(Duration::from_millis(500)) };
+        tokio::spawn(serve_async(listener, config, move |mut comm| {
+            let (active, peak) = (active_.clone(), peak_.clone());
+            async move {
+                let now = active.fetch_add(1, Ordering::SeqCst) + 1;
+                peak.fetch_max(now, Ordering::SeqCst);
+                let x: Vec<u64> = comm.receive().await?;
+                tokio::time::sleep(Duration::from_millis(50)).await;
+                comm.send(&x.iter().sum::<u64>()).await?;
+                active.fetch_sub(1, Ordering::SeqCst);
+                Ok(())
+            }
+        }));
+
+        let clients: Vec<_> = (0..6u64).map(|i| tokio::spawn(async move {
+            let mut comm = TokioTcpCommunicator::connect(addr, Some(Duration::from_secs(5))).await.unwrap();
+            comm.send(&vec![i, 1, 2]).await.unwrap();
+            comm.receive::<u64>().await.unwrap()
+        })).collect();
+        for (i, client) in clients.into_iter().enumerate() {
+            assert_eq!(client.await.unwrap(), i as u64 + 3);
+        }
+        assert!(peak.load(Ordering::SeqCst) <= 2);
+
+        // 对端不说话时读超时
+        let mut comm = TokioTcpCommunicator::connect(addr, Some(Duration::from_millis(100))).await.unwrap();
+        let err = comm.receive::<u64>().await.unwrap_err();
+        assert!(matches!(err.kind(), io::ErrorKind::TimedOut | io::ErrorKind::UnexpectedEof));
+    }
+}
@@ -0,0 +1,159 @@
+// 外部随机信标：替代协议内掷硬币来获得 add_noise_from_rand_bits 所需的公共随机比特
+// 使用时必须在所有 prover 公布 coms_v_k 之后才能确定所用的轮次
+use serde::{Serialize, Deserialize};
+use sha3::{Digest, Sha3_256};
+use std::fs;
+use std::io;
+use std::path::Path;
+use crate::public_randomness::expand_seed;
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BeaconOutput {
+    pub round: u64,
+    pub value: [u8; 32],
+}
+
+pub trait RandomnessBeacon {
+    /// Fetches the output of round `round`.
+    fn fetch(&self, round: u64) -> io::Result<BeaconOutput>;
+
+    /// Checks that `output` is the genuine output of round `round`.
+    fn verify(&self, round: u64, output: &BeaconOutput) -> bool;
+}
+
+/// Turns a beacon output into `rows` vectors of `cols` public random bits.
+pub fn expand_beacon_output(output: &BeaconOutput, rows: usize, cols: usize) -> Vec<Vec<bool>> {
+    let mut hasher = Sha3_256::new();
+    hasher.update(crate::DST_ROBUST_DP_BEACON_HASH_CHAIN);
+    hasher.update(output.round.to_le_bytes());
+    hasher.update(output.value);
+    expand_seed(&hasher.finalize().into(), rows, cols)
+}
+
+fn chain_hash(value: &[u8; 32]) -> [u8; 32] {
+    let mut hasher = Sha3_256::new();
+    hasher.update(crate::DST_ROBUST_DP_BEACON_HASH_CHAIN);
+    hasher.update(value);
+    hasher.finalize().into()
+}
+
+/// A local stand-in for an external beacon built on a reversed hash chain.
+///
+/// The owner computes `x_k = H^k(seed)` for `k = 0..=length` and publishes the anchor
+/// `x_length`. Round `r` (`1..=length`) outputs `x_{length-r}`, so each round is unpredictable
+/// from earlier ones and anyone holding the anchor can verify it by hashing `r` times.
+pub struct HashChainBeacon {
+    anchor: [u8; 32],
+    length: u64,
+    chain: Option<Vec<[u8; 32]>>, // 只有信标持有者拥有完整的链
+}
+
+impl HashChainBeacon {
+    pub fn generate(seed: &[u8], length: u64) -> Self {
+        let mut hasher = Sha3_256::new();
+        hasher.update(crate::DST_ROBUST_DP_BEACON_HASH_CHAIN);
+        hasher.update(seed);
+        let mut chain: Vec<[u8; 32]> = vec![hasher.finalize().into()];
+        for k in 0..length as usize {
+            chain.push(chain_hash(&chain[k]));
+        }
+        Self {
+            anchor: chain[length as usize],
+            length,
+            chain: Some(chain),
+        }
+    }
+
+    /// A verify-only beacon that knows just the published anchor.
+    pub fn from_anchor(anchor: [u8; 32], length: u64) -> Self {
+        Self {
+            anchor,
+            length,
+            chain: None,
+        }
+    }
+
+    pub fn get_anchor(&self) -> [u8; 32] {
+        self.anchor
+    }
+
+    pub fn get_length(&self) -> u64 {
+        self.length
+    }
+
+    /// Writes the full chain to `path` so the beacon survives restarts.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let chain = self.chain.as_ref().ok_or_else(|| io::Error::new(io::ErrorKind::Unsupported, "Beacon only holds the anchor"))?;
+        let bytes = bcs::to_bytes(chain).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        fs::write(path, bytes)
+    }
+
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let bytes = fs::read(path)?;
+        let chain: Vec<[u8; 32]> = bcs::from_bytes(&bytes).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        if chain.is_empty() || chain.windows(2).any(|w| chain_hash(&w[0]) != w[1]) {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "Corrupted hash chain"));
+        }
+        Ok(Self {
+            anchor: chain[chain.len() - 1],
+            length: (chain.len() - 1) as u64,
+            chain: Some(chain),
+        })
+    }
+}
+
+impl RandomnessBeacon for HashChainBeacon {
+    fn fetch(&self, round: u64) -> io::Result<BeaconOutput> {
+        let chain = self.chain.as_ref().ok_or_else(|| io::Error::new(io::ErrorKind::Unsupported, "Beacon only holds the anchor"))?;
+        if round == 0 || round > self.length {
+            return Err(io::Error::new(io::ErrorKind::NotFound, "Beacon round out of range"));
+        }
+        Ok(BeaconOutput {
+            round,
+            value: chain[(self.length - round) as usize],
+        })
+    }
+
+    fn verify(&self, round: u64, output: &BeaconOutput) -> bool {
+        if output.round != round || round == 0 || round > self.length {
+            return false;
+        }
+        let mut value = output.value;
+        for _ in 0..round {
+            value = chain_hash(&value);
+        }
+        value == self.anchor
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::{expand_beacon_output, HashChainBeacon, RandomnessBeacon};
+
+    #[test]
+    fn test_hash_chain_beacon() {
+        let beacon = HashChainBeacon::generate(b"beacon seed", 16);
+        let observer = HashChainBeacon::from_anchor(beacon.get_anchor(), beacon.get_length());
+        assert!(observer.fetch(1).is_err());
+        assert!(beacon.fetch(0).is_err() && beacon.fetch(17).is_err());
+
+        let out = beacon.fetch(5).unwrap();
+        assert!(observer.verify(5, &out));
+        assert!(!observer.verify(6, &out));
+        let mut forged = out.clone();
+        forged.value[0] ^= 1;
+        assert!(!observer.verify(5, &forged));
+
+        let path = std::env::temp_dir().join(format!("dp_beacon_{}.chain", std::process::id()));
+        beacon.save(&path).unwrap();
+        let reopened = HashChainBeacon::open(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(reopened.get_anchor(), beacon.get_anchor());
+        assert_eq!(reopened.fetch(5).unwrap(), out);
+
+        let bits = expand_beacon_output(&out, 3, 100);
+        assert_eq!(bits, expand_beacon_output(&reopened.fetch(5).unwrap(), 3, 100));
+        assert_ne!(bits, expand_beacon_output(&beacon.fetch(6).unwrap(), 3, 100));
+    }
+}
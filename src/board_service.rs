@@ -0,0 +1,369 @@
+// 通过 TLS 提供公告板：服务端持有带 Merkle 日志的 UserStore，客户端 RemoteBoard 本身也实现
+// UserStore 和 BoardLog，所以 Prover / Client / Verifier 不需要知道公告板在另一个进程里，
+// 并且可以用 BoardMonitor 审计它
+use ed25519_dalek::VerifyingKey;
+use rustls::ClientConfig;
+use std::io;
+use std::net::{SocketAddr, TcpListener, ToSocketAddrs};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+use crate::async_communicator::ServerConfig;
+use crate::bulletin_board::{BoardEntry, BoardLog, Hash, ProvenEntry, SignedTreeHead};
+use crate::communicator::Communicator;
+use crate::error::{Error, Result};
+use crate::protocol::{Message, Role};
+use crate::public_parameters::PublicParameters;
+use crate::range_proof::InputProof;
+use crate::replicated::{ReplicaCommitment, ReplicaShare, SharingScheme};
+use crate::sign::{verify_sig, MySignature};
+use crate::tls::{accept_sync, connect_sync, sync_client_key, PeerDirectory, SyncTlsClientCommunicator};
+use crate::user_store::{User, UserStore};
+
+// RemoteBoard 每次请求等待回复的上限
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+// 连接空闲超过这么久就重连，免得撞上服务端的读超时（默认 ServerConfig 为 30 秒）
+const RECONNECT_AFTER: Duration = Duration::from_secs(20);
+
+/// What the board checks every post against.
+#[derive(Clone)]
+pub struct BoardContext {
+    pub session_id: u64,
+    pub scheme: SharingScheme,
+    pub pp: PublicParameters,
+    pub peers: Arc<PeerDirectory>,
+}
+
+// 只认 TLS 信道认证出的身份，信封里的 sender 由对端自己填写，不能作为依据
+fn check_peer(peer: Option<Role>, expected: Role, message: &str) -> Result<()> {
+    if peer != Some(expected) {
+        return Err(Error::Forbidden { message: message.to_string(), peer });
+    }
+    Ok(())
+}
+
+fn try_handle<S: UserStore + BoardLog>(store: &mut S, ctx: &BoardContext, peer: Option<Role>, request: Message) -> Result<Message> {
+    Ok(match request {
+        Message::SubmitCommitment { id, commitment, proof, signature } => {
+            check_peer(peer, Role::Client(id), "SubmitCommitment")?;
+            // 承诺长度不对或输入证明不成立的提交不进日志
+            if commitment.len() != ctx.scheme.split_len() * ctx.pp.get_buckets() {
+                return Err(Error::MalformedCommitment);
+            }
+            User::new(&ctx.scheme, id, commitment.clone(), proof.clone()).check_proof(&ctx.scheme, ctx.session_id, &ctx.pp)?;
+            match signature {
+                Some(sig) => store.new_signed_user(id, commitment, proof, &sig)?,
+                None => store.new_user(id, commitment, proof)?,
+            }
+            Message::Ack
+        }
+        Message::ShareSignature { id, prover, sig } => {
+            check_peer(peer, Role::Prover(prover), "ShareSignature")?;
+            let pk = ctx.peers.get_prover_keys().get(prover).ok_or(Error::UnknownProver(prover))?;
+            let user = store.get_user(id).ok_or(Error::UnknownUser(id))?;
+            if !verify_sig(&user.commitment, pk, &sig.clone().into()) {
+                return Err(Error::BadSignature(prover));
+            }
+            store.sig_to_user(id, sig, prover)?;
+            Message::Ack
+        }
+        Message::RevealShare { id, prover, share } => {
+            check_peer(peer, Role::Client(id), "RevealShare")?;
+            if prover >= ctx.scheme.prover_num() {
+                return Err(Error::UnknownProver(prover));
+            }
+            if share.get_ind() != prover {
+                return Err(Error::WrongProverIndex { expected: prover, got: share.get_ind() });
+            }
+            let user = store.get_user(id).ok_or(Error::UnknownUser(id))?;
+            if !share.check_com(&ctx.scheme, ctx.pp.get_commit_base(), user.commitment) {
+                return Err(Error::BadShareOpening);
+            }
+            store.upload_share(id, share, prover)?;
+            Message::Ack
+        }
+        Message::RequestUser(id) => Message::User(store.get_user(id)),
+        Message::RequestCommitment(id) => Message::Commitment(store.get_user_commitment_proof(id)),
+        Message::RequestAllUsers => Message::Users(store.iter_all_users()?.collect()),
+        Message::RequestTreeHead => Message::TreeHead(store.signed_head()?),
+        Message::RequestConsistency { old_size, new_size } => Message::Consistency(store.prove_consistency(old_size, new_size)?),
+        Message::RequestEntries { from, to } => Message::Entries(store.log_entries(from, to)?),
+        Message::RequestUserEntry { id, size } => {
+            let (index, entry, proof) = store.prove_user(id, size)?;
+            Message::UserEntry { index, entry, proof }
+        }
+        Message::RequestUserPosts { id, size } => Message::UserPosts(store.prove_posts(id, size)?),
+        other => return Err(Error::Protocol(format!("board does not serve {}", other.name()))),
+    })
+}
+
+/// Answers the requests of one connection from `peer`, the role its TLS key stands for, until
+/// the peer hangs up.
+pub fn serve_board_connection<S: UserStore + BoardLog, C: Communicator>(store: &Mutex<S>, ctx: &BoardContext, peer: Option<Role>, comm: &mut C) -> io::Result<()> {
+    loop {
+        let request = match comm.receive_message(ctx.session_id) {
+            Ok((_, request)) => request,
+            // 对端挂断或空闲到读超时都是正常结束
+            Err(e) if matches!(e.kind(), io::ErrorKind::UnexpectedEof | io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut) => return Ok(()),
+            Err(e) => return Err(e),
+        };
+        let reply = match store.lock() {
+            Ok(mut store) => try_handle(&mut *store, ctx, peer, request).unwrap_or_else(Message::Reject),
+            Err(e) => Message::Reject(e.into()),
+        };
+        comm.send_message(ctx.session_id, Role::Board, reply)?;
+    }
+}
+
+// 同时处理的连接数；Drop 时归还名额
+struct Permit(Arc<(Mutex<usize>, Condvar)>);
+
+impl Permit {
+    fn acquire(slots: &Arc<(Mutex<usize>, Condvar)>, max: usize) -> io::Result<Self> {
+        let (active, freed) = &**slots;
+        let poisoned = |_| io::Error::other("lock poisoned");
+        let mut active = freed.wait_while(active.lock().map_err(poisoned)?, |n| *n >= max).map_err(poisoned)?;
+        *active += 1;
+        Ok(Permit(slots.clone()))
+    }
+}
+
+impl Drop for Permit {
+    fn drop(&mut self) {
+        let (active, freed) = &*self.0;
+        if let Ok(mut active) = active.lock() {
+            *active -= 1;
+        }
+        freed.notify_one();
+    }
+}
+
+/// Serves the board over TLS on `listener`, one thread per connection and at most
+/// `config.max_connections` at a time, until accepting fails. `config.timeout` bounds the
+/// handshake and every read, so an idle connection is closed; `RemoteBoard` reconnects. The
+/// error that ends a connection is passed to `on_error`.
+pub fn serve_board<S, E>(listener: TcpListener, store: Arc<Mutex<S>>, ctx: BoardContext, tls: Arc<rustls::ServerConfig>, config: ServerConfig, on_error: E) -> io::Result<()>
+where
+    S: UserStore + BoardLog + Send + 'static,
+    E: Fn(io::Error) + Send + Sync + 'static,
+{
+    let ctx = Arc::new(ctx);
+    let slots = Arc::new((Mutex::new(0usize), Condvar::new()));
+    let on_error = Arc::new(on_error);
+    loop {
+        let permit = Permit::acquire(&slots, config.max_connections.max(1))?;
+        let (stream, _) = listener.accept()?;
+        let (store, ctx, tls, on_error) = (store.clone(), ctx.clone(), tls.clone(), on_error.clone());
+        thread::spawn(move || {
+            let res = accept_sync(stream, tls, config.timeout).and_then(|mut comm| {
+                let peer = sync_client_key(&comm).and_then(|pk| ctx.peers.role_of(&pk));
+                serve_board_connection(&store, &ctx, peer, &mut comm)
+            });
+            if let Err(e) = res {
+                on_error(e);
+            }
+            drop(permit);
+        });
+    }
+}
+
+/// A bulletin board served by another process.
+pub struct RemoteBoard {
+    scheme: SharingScheme,
+    session_id: u64,
+    role: Role, // 本端在消息信封里的身份
+    addr: SocketAddr,
+    tls: Arc<ClientConfig>,
+    comm: Mutex<(SyncTlsClientCommunicator, Instant)>, // 连接和它最后一次使用的时间
+}
+
+impl RemoteBoard {
+    /// Connects to the board over TLS; `tls` pins the board's key and carries this party's
+    /// identity.
+    pub fn connect<A: ToSocketAddrs>(addr: A, tls: Arc<ClientConfig>, scheme: &SharingScheme, session_id: u64, role: Role) -> Result<Self> {
+        let addr = addr.to_socket_addrs().map_err(Error::connection)?.next()
+            .ok_or_else(|| Error::Connection("board address does not resolve".to_string()))?;
+        let comm = connect_sync(&addr, tls.clone(), TIMEOUT).map_err(Error::connection)?;
+        Ok(Self { scheme: scheme.clone(), session_id, role, addr, tls, comm: Mutex::new((comm, Instant::now())) })
+    }
+
+    fn call(&self, request: Message) -> Result<Message> {
+        let mut guard = self.comm.lock()?;
+        let (comm, last_used) = &mut *guard;
+        if last_used.elapsed() > RECONNECT_AFTER {
+            *comm = connect_sync(&self.addr, self.tls.clone(), TIMEOUT).map_err(Error::connection)?;
+        }
+        *last_used = Instant::now();
+        comm.send_message(self.session_id, self.role, request).map_err(Error::connection)?;
+        match comm.receive_message(self.session_id).map_err(Error::connection)? {
+            (_, Message::Reject(e)) => Err(e),
+            (_, reply) => Ok(reply),
+        }
+    }
+
+    fn append(&self, request: Message) -> Result<()> {
+        match self.call(request)? {
+            Message::Ack => Ok(()),
+            other => Err(other.unexpected()),
+        }
+    }
+}
+
+impl UserStore for RemoteBoard {
+    fn new_user(&mut self, id: u64, commitment: ReplicaCommitment, proof: InputProof) -> Result<()> {
+        self.append(Message::SubmitCommitment { id, commitment, proof, signature: None })
+    }
+
+    fn new_signed_user(&mut self, id: u64, commitment: ReplicaCommitment, proof: InputProof, sig: &MySignature) -> Result<()> {
+        self.append(Message::SubmitCommitment { id, commitment, proof, signature: Some(sig.clone()) })
+    }
+
+    fn get_user(&self, id: u64) -> Option<User> {
+        // 网络上来的记录可能与 prover 数不符，不符的当作不存在
+        match self.call(Message::RequestUser(id)) {
+            Ok(Message::User(user)) => user.filter(|user| user.id == id && user.check_slots(&self.scheme).is_ok()),
+            _ => None,
+        }
+    }
+
+    fn get_user_commitment_proof(&self, id: u64) -> Option<(ReplicaCommitment, InputProof)> {
+        match self.call(Message::RequestCommitment(id)) {
+            Ok(Message::Commitment(res)) => res,
+            _ => None,
+        }
+    }
+
+    fn sig_to_user(&mut self, id: u64, sig: MySignature, proverid: usize) -> Result<()> {
+        self.append(Message::ShareSignature { id, prover: proverid, sig })
+    }
+
+    fn upload_share(&mut self, id: u64, share: ReplicaShare, proverid: usize) -> Result<()> {
+        self.append(Message::RevealShare { id, prover: proverid, share })
+    }
+
+    fn iter_all_users(&self) -> Result<Box<dyn Iterator<Item = User>>> {
+        match self.call(Message::RequestAllUsers)? {
+            Message::Users(users) => {
+                for user in &users {
+                    user.check_slots(&self.scheme)?;
+                }
+                Ok(Box::new(users.into_iter()))
+            }
+            other => Err(other.unexpected()),
+        }
+    }
+
+    fn check_all_users(&self, session_id: u64, pks: &[VerifyingKey], pp: &PublicParameters) -> Vec<u64> {
+        let mut valid_user_ids = Vec::new();
+
+        if let Ok(users_iter) = self.iter_all_users() {
+            for user in users_iter {
+                if user.check_whole(&self.scheme, session_id, pks, pp) {
+                    valid_user_ids.push(user.id);
+                }
+            }
+        }
+
+        valid_user_ids
+    }
+}
+
+impl BoardLog for RemoteBoard {
+    fn signed_head(&self) -> Result<SignedTreeHead> {
+        match self.call(Message::RequestTreeHead)? {
+            Message::TreeHead(head) => Ok(head),
+            other => Err(other.unexpected()),
+        }
+    }
+
+    fn prove_consistency(&self, old_size: u64, new_size: u64) -> Result<Vec<Hash>> {
+        match self.call(Message::RequestConsistency { old_size, new_size })? {
+            Message::Consistency(proof) => Ok(proof),
+            other => Err(other.unexpected()),
+        }
+    }
+
+    fn log_entries(&self, from: u64, to: u64) -> Result<Vec<BoardEntry>> {
+        match self.call(Message::RequestEntries { from, to })? {
+            Message::Entries(entries) => Ok(entries),
+            other => Err(other.unexpected()),
+        }
+    }
+
+    fn prove_user(&self, id: u64, size: u64) -> Result<(u64, BoardEntry, Vec<Hash>)> {
+        match self.call(Message::RequestUserEntry { id, size })? {
+            Message::UserEntry { index, entry, proof } => Ok((index, entry, proof)),
+            other => Err(other.unexpected()),
+        }
+    }
+
+    fn prove_posts(&self, id: u64, size: u64) -> Result<Vec<ProvenEntry>> {
+        match self.call(Message::RequestUserPosts { id, size })? {
+            Message::UserPosts(posts) => Ok(posts),
+            other => Err(other.unexpected()),
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use blstrs::Scalar;
+    use crate::bulletin_board::MerkleBoard;
+    use crate::client::Client;
+    use crate::client_auth::ClientRegistry;
+    use crate::sign::{self, sign_verified_deal};
+    use crate::user_store::MemoryUserStore;
+    use super::*;
+
+    #[test]
+    fn test_board_service() {
+        let scheme = SharingScheme::new(3, 1);
+        let pp = PublicParameters::new(b"seed");
+        let keys: Vec<_> = (0..3).map(|_| sign::gen_keys()).collect();
+        let pks: Vec<_> = keys.iter().map(|k| k.1).collect();
+        let peers = Arc::new(PeerDirectory::new(&pks, sign::gen_keys().1, ClientRegistry::new()));
+        let ctx = BoardContext { session_id: 0, scheme: scheme.clone(), pp: pp.clone(), peers };
+        let mut board = MerkleBoard::new(&scheme, sign::gen_keys().0);
+
+        let client = Client::new(0, 7, true, &pp, &scheme, pks.clone());
+        let mut scratch = MemoryUserStore::new(&scheme);
+        client.send_proof_coms(&mut scratch).unwrap();
+        let (commitment, proof) = scratch.get_user_commitment_proof(7).unwrap();
+        let submit = Message::SubmitCommitment { id: 7, commitment: commitment.clone(), proof, signature: None };
+        // 别的客户端和匿名连接都不能替客户端 7 提交
+        assert!(matches!(try_handle(&mut board, &ctx, Some(Role::Client(8)), submit.clone()), Err(Error::Forbidden { .. })));
+        assert!(matches!(try_handle(&mut board, &ctx, None, submit.clone()), Err(Error::Forbidden { .. })));
+        assert!(matches!(try_handle(&mut board, &ctx, Some(Role::Client(7)), submit), Ok(Message::Ack)));
+        assert!(matches!(try_handle(&mut board, &ctx, None, Message::RequestUser(7)), Ok(Message::User(Some(_)))));
+
+        // 签名只能由对应的 prover 发布，且必须能验证；重复发布同一签名无害，但不能覆盖
+        let sig: MySignature = sign_verified_deal(&keys[0].0, &commitment).into();
+        let post = |prover, sig| Message::ShareSignature { id: 7, prover, sig };
+        assert!(matches!(try_handle(&mut board, &ctx, Some(Role::Prover(1)), post(0, sig.clone())), Err(Error::Forbidden { .. })));
+        assert!(matches!(try_handle(&mut board, &ctx, Some(Role::Prover(0)), post(0, MySignature::default())), Err(Error::BadSignature(0))));
+        assert!(matches!(try_handle(&mut board, &ctx, Some(Role::Prover(0)), post(0, sig.clone())), Ok(Message::Ack)));
+        assert!(matches!(try_handle(&mut board, &ctx, Some(Role::Prover(0)), post(0, sig)), Ok(Message::Ack)));
+        assert!(matches!(board.sig_to_user(7, MySignature::default(), 0), Err(Error::AlreadyPosted { id: 7, prover: 0 })));
+
+        // 公开的份额必须出自客户端本人，编号对应，且能打开承诺
+        let reveal = |prover, share| Message::RevealShare { id: 7, prover, share };
+        let share = client.send_share(1).1;
+        assert!(matches!(try_handle(&mut board, &ctx, Some(Role::Client(8)), reveal(1, share.clone())), Err(Error::Forbidden { .. })));
+        assert!(matches!(try_handle(&mut board, &ctx, Some(Role::Client(7)), reveal(2, share.clone())), Err(Error::WrongProverIndex { expected: 2, got: 1 })));
+        let tampered = share.add_noise(vec![Scalar::from(1u64); scheme.share_len()], vec![Scalar::from(0u64); scheme.share_len()]);
+        assert!(matches!(try_handle(&mut board, &ctx, Some(Role::Client(7)), reveal(1, tampered)), Err(Error::BadShareOpening)));
+        assert!(matches!(try_handle(&mut board, &ctx, Some(Role::Client(7)), reveal(1, share)), Ok(Message::Ack)));
+        let user = board.get_user(7).unwrap();
+        assert!(user.signatures[0].is_some() && user.share[1].is_some());
+
+        // 承诺长度不对或证明不属于提交者的都拒绝，不进日志
+        let (commitment, proof) = (user.commitment, user.sigma_proof);
+        let doubled = ReplicaCommitment::concat(vec![commitment.clone(), commitment.clone()]);
+        let submit = |id, commitment| Message::SubmitCommitment { id, commitment, proof: proof.clone(), signature: None };
+        assert!(matches!(try_handle(&mut board, &ctx, Some(Role::Client(9)), submit(9, doubled)), Err(Error::MalformedCommitment)));
+        assert!(try_handle(&mut board, &ctx, Some(Role::Client(9)), submit(9, commitment)).is_err());
+        assert!(board.get_user(9).is_none());
+    }
+}
@@ -0,0 +1,646 @@
+// 只追加、哈希链接的公告板
+// 每次 new_user / sig_to_user / upload_share 都作为一条记录加入 Merkle 日志（RFC 6962 的树结构），
+// 运营者对树头 (size, root) 签名；prover 和 verifier 用一致性证明检查新树头是旧树头的扩展，
+// 于是运营者向不同读者展示不同内容（分叉）会被发现
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+use serde::{Serialize, Deserialize};
+use sha3::{Digest, Sha3_256};
+use std::path::Path;
+use crate::error::{Error, Result};
+use crate::public_parameters::PublicParameters;
+use crate::range_proof::InputProof;
+use crate::record_log::{FsyncPolicy, RecordLog};
+use crate::replicated::{ReplicaCommitment, ReplicaShare, SharingScheme};
+use crate::sign::MySignature;
+use crate::user_store::{MemoryUserStore, User, UserStore};
+
+pub type Hash = [u8; 32];
+
+/// An entry of the log with its index and inclusion proof.
+pub type ProvenEntry = (u64, BoardEntry, Vec<Hash>);
+
+/// Entries a `BoardMonitor` asks for per request when auditing the log.
+pub const AUDIT_PAGE: u64 = 1024;
+
+// 一次返回的记录总大小上限，留出余量使应答不超过 MAX_MESSAGE_SIZE
+const PAGE_BYTES: usize = crate::communicator::MAX_MESSAGE_SIZE / 2;
+
+/// One change to the board.
+#[allow(clippy::large_enum_variant)]
+#[derive(Clone, Serialize, Deserialize)]
+pub enum BoardEntry {
+    NewUser { id: u64, commitment: ReplicaCommitment, proof: InputProof, signature: Option<MySignature> },
+    Signature { id: u64, sig: MySignature, prover: usize },
+    Share { id: u64, share: ReplicaShare, prover: usize },
+}
+
+impl BoardEntry {
+    pub fn leaf_hash(&self) -> Hash {
+        leaf_hash(&bcs::to_bytes(self).unwrap())
+    }
+
+    /// The user the entry changes.
+    pub fn user_id(&self) -> u64 {
+        match self {
+            BoardEntry::NewUser { id, .. } | BoardEntry::Signature { id, .. } | BoardEntry::Share { id, .. } => *id,
+        }
+    }
+
+    /// Applies the entry to `store`.
+    pub fn apply<S: UserStore>(&self, store: &mut S) -> Result<()> {
+        match self.clone() {
+            BoardEntry::NewUser { id, commitment, proof, signature: None } => store.new_user(id, commitment, proof),
+            BoardEntry::NewUser { id, commitment, proof, signature: Some(sig) } => store.new_signed_user(id, commitment, proof, &sig),
+            BoardEntry::Signature { id, sig, prover } => store.sig_to_user(id, sig, prover),
+            BoardEntry::Share { id, share, prover } => store.upload_share(id, share, prover),
+        }
+    }
+}
+
+fn leaf_hash(data: &[u8]) -> Hash {
+    let mut hasher = Sha3_256::new();
+    hasher.update([0u8]);
+    hasher.update(data);
+    hasher.finalize().into()
+}
+
+fn node_hash(left: &Hash, right: &Hash) -> Hash {
+    let mut hasher = Sha3_256::new();
+    hasher.update([1u8]);
+    hasher.update(left);
+    hasher.update(right);
+    hasher.finalize().into()
+}
+
+// 小于 n 的最大的 2 的幂（n >= 2）
+fn split(n: usize) -> usize {
+    1 << (usize::BITS - 1 - (n - 1).leading_zeros())
+}
+
+/// Merkle tree hash of `leaves`.
+pub fn merkle_root(leaves: &[Hash]) -> Hash {
+    match leaves.len() {
+        0 => Sha3_256::digest([]).into(),
+        1 => leaves[0],
+        n => {
+            let k = split(n);
+            node_hash(&merkle_root(&leaves[..k]), &merkle_root(&leaves[k..]))
+        }
+    }
+}
+
+// 审计路径和一致性证明只用到子树根；root(lo, n) 给出从第 lo 片叶子起 n 片叶子的子树根
+fn audit_path(m: usize, lo: usize, n: usize, root: &dyn Fn(usize, usize) -> Hash) -> Vec<Hash> {
+    if n <= 1 {
+        return Vec::new();
+    }
+    let k = split(n);
+    let (mut path, sibling) = if m < k {
+        (audit_path(m, lo, k, root), root(lo + k, n - k))
+    } else {
+        (audit_path(m - k, lo + k, n - k, root), root(lo, k))
+    };
+    path.push(sibling);
+    path
+}
+
+fn subproof(m: usize, lo: usize, n: usize, complete: bool, root: &dyn Fn(usize, usize) -> Hash) -> Vec<Hash> {
+    if m == n {
+        return if complete { Vec::new() } else { vec![root(lo, n)] };
+    }
+    let k = split(n);
+    let (mut proof, sibling) = if m <= k {
+        (subproof(m, lo, k, complete, root), root(lo + k, n - k))
+    } else {
+        (subproof(m - k, lo + k, n - k, false, root), root(lo, k))
+    };
+    proof.push(sibling);
+    proof
+}
+
+/// Audit path of leaf `m` in the tree over `leaves`.
+pub fn inclusion_proof(m: usize, leaves: &[Hash]) -> Vec<Hash> {
+    audit_path(m, 0, leaves.len(), &|lo, n| merkle_root(&leaves[lo..lo + n]))
+}
+
+/// Proof that the tree over `leaves[..m]` is a prefix of the tree over `leaves`.
+pub fn consistency_proof(m: usize, leaves: &[Hash]) -> Vec<Hash> {
+    if m == 0 || m > leaves.len() {
+        return Vec::new();
+    }
+    subproof(m, 0, leaves.len(), true, &|lo, n| merkle_root(&leaves[lo..lo + n]))
+}
+
+// 按层缓存完整子树的哈希：levels[h][i] 是第 i 组 2^h 片叶子的子树根。
+// RFC 6962 的切分下左子树总是对齐的完整子树，任一前缀树的根和证明只需 O(log n) 个缓存值
+#[derive(Default)]
+struct MerkleTree {
+    levels: Vec<Vec<Hash>>,
+}
+
+impl MerkleTree {
+    fn len(&self) -> usize {
+        self.levels.first().map_or(0, Vec::len)
+    }
+
+    fn push(&mut self, leaf: Hash) {
+        let mut hash = leaf;
+        for h in 0.. {
+            if self.levels.len() == h {
+                self.levels.push(Vec::new());
+            }
+            let level = &mut self.levels[h];
+            level.push(hash);
+            if level.len() % 2 == 1 {
+                break;
+            }
+            hash = node_hash(&level[level.len() - 2], &level[level.len() - 1]);
+        }
+    }
+
+    fn root(&self, lo: usize, n: usize) -> Hash {
+        match n {
+            0 => Sha3_256::digest([]).into(),
+            _ if n.is_power_of_two() && lo.is_multiple_of(n) => self.levels[n.trailing_zeros() as usize][lo / n],
+            _ => {
+                let k = split(n);
+                node_hash(&self.root(lo, k), &self.root(lo + k, n - k))
+            }
+        }
+    }
+
+    // 大小为 n 的前缀树中叶子 m 的审计路径
+    fn inclusion_proof(&self, m: usize, n: usize) -> Vec<Hash> {
+        audit_path(m, 0, n, &|lo, k| self.root(lo, k))
+    }
+
+    // 大小为 m 的前缀树到大小为 n 的前缀树的一致性证明
+    fn consistency_proof(&self, m: usize, n: usize) -> Vec<Hash> {
+        if m == 0 || m > n {
+            return Vec::new();
+        }
+        subproof(m, 0, n, true, &|lo, k| self.root(lo, k))
+    }
+}
+
+/// Checks that `leaf` is leaf `m` of the tree of size `n` with root `root`.
+pub fn verify_inclusion(leaf: &Hash, m: usize, n: usize, root: &Hash, proof: &[Hash]) -> bool {
+    fn walk(leaf: &Hash, m: usize, n: usize, proof: &[Hash]) -> Option<Hash> {
+        if n == 1 {
+            return proof.is_empty().then_some(*leaf);
+        }
+        let (sibling, rest) = proof.split_last()?;
+        let k = split(n);
+        if m < k {
+            Some(node_hash(&walk(leaf, m, k, rest)?, sibling))
+        } else {
+            Some(node_hash(sibling, &walk(leaf, m - k, n - k, rest)?))
+        }
+    }
+    m < n && walk(leaf, m, n, proof).as_ref() == Some(root)
+}
+
+/// Checks that the tree of size `m` with root `old_root` is a prefix of the tree of size `n`
+/// with root `new_root`.
+pub fn verify_consistency(m: usize, n: usize, old_root: &Hash, new_root: &Hash, proof: &[Hash]) -> bool {
+    // 由证明同时重算旧树和新树（子树）的根
+    fn walk(m: usize, n: usize, complete: bool, old_root: &Hash, proof: &[Hash]) -> Option<(Hash, Hash)> {
+        if m == n {
+            return match (complete, proof) {
+                (true, []) => Some((*old_root, *old_root)),
+                (false, [root]) => Some((*root, *root)),
+                _ => None,
+            };
+        }
+        let (sibling, rest) = proof.split_last()?;
+        let k = split(n);
+        if m <= k {
+            let (old, new) = walk(m, k, complete, old_root, rest)?;
+            Some((old, node_hash(&new, sibling)))
+        } else {
+            let (old, new) = walk(m - k, n - k, false, old_root, rest)?;
+            Some((node_hash(sibling, &old), node_hash(sibling, &new)))
+        }
+    }
+    if m == 0 || m > n {
+        return m == 0 && proof.is_empty();
+    }
+    walk(m, n, true, old_root, proof) == Some((*old_root, *new_root))
+}
+
+/// A tree head signed by the board operator.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SignedTreeHead {
+    pub size: u64,
+    pub root: Hash,
+    pub signature: Vec<u8>,
+}
+
+fn tree_head_message(size: u64, root: &Hash) -> Vec<u8> {
+    let mut msg = crate::DST_ROBUST_DP_TREE_HEAD.to_vec();
+    msg.extend(size.to_le_bytes());
+    msg.extend(root);
+    msg
+}
+
+impl SignedTreeHead {
+    pub fn verify(&self, board_pk: &VerifyingKey) -> bool {
+        let Ok(signature) = Signature::from_slice(&self.signature) else {
+            return false;
+        };
+        board_pk.verify(&tree_head_message(self.size, &self.root), &signature).is_ok()
+    }
+}
+
+/// The log of a board and the proofs a `BoardMonitor` needs to audit it.
+pub trait BoardLog {
+    fn signed_head(&self) -> Result<SignedTreeHead>;
+
+    /// Proof that the tree of size `old_size` is a prefix of the tree of size `new_size`.
+    fn prove_consistency(&self, old_size: u64, new_size: u64) -> Result<Vec<Hash>>;
+
+    /// Entries `from..to` of the log, or a non-empty prefix of them if they do not fit in one
+    /// reply.
+    fn log_entries(&self, from: u64, to: u64) -> Result<Vec<BoardEntry>>;
+
+    /// The index of the `NewUser` entry of `id` and its inclusion proof in the tree of size
+    /// `size`.
+    fn prove_user(&self, id: u64, size: u64) -> Result<(u64, BoardEntry, Vec<Hash>)>;
+
+    /// The `Signature` and `Share` entries of user `id` in the tree of size `size`, each with
+    /// its index and inclusion proof.
+    fn prove_posts(&self, id: u64, size: u64) -> Result<Vec<ProvenEntry>>;
+}
+
+/// A `UserStore` whose every change is logged in a Merkle tree with signed tree heads.
+pub struct MerkleBoard {
+    state: MemoryUserStore,
+    entries: Vec<BoardEntry>,
+    tree: MerkleTree,
+    sig_key: SigningKey,
+    log: Option<RecordLog>, // 持久化时每条记录一个 BoardEntry
+}
+
+impl MerkleBoard {
+    pub fn new(scheme: &SharingScheme, sig_key: SigningKey) -> Self {
+        Self {
+            state: MemoryUserStore::new(scheme),
+            entries: Vec::new(),
+            tree: MerkleTree::default(),
+            sig_key,
+            log: None,
+        }
+    }
+
+    /// Opens a board persisted at `path`, replaying the entries already there.
+    pub fn open<P: AsRef<Path>>(path: P, scheme: &SharingScheme, sig_key: SigningKey, policy: FsyncPolicy) -> Result<Self> {
+        let (log, records) = RecordLog::open(path, policy)?;
+        let mut board = Self::new(scheme, sig_key);
+        for (_, payload) in records {
+            let entry: BoardEntry = bcs::from_bytes(&payload).map_err(|e| Error::Store(e.to_string()))?;
+            board.append(entry)?;
+        }
+        board.log = Some(log);
+        Ok(board)
+    }
+
+    pub fn size(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Entries `from..to` of the log.
+    pub fn entries(&self, from: usize, to: usize) -> Vec<BoardEntry> {
+        let to = to.min(self.entries.len());
+        self.entries[from.min(to)..to].to_vec()
+    }
+
+    pub fn tree_head(&self) -> SignedTreeHead {
+        let size = self.tree.len();
+        let root = self.tree.root(0, size);
+        let size = size as u64;
+        SignedTreeHead {
+            size,
+            root,
+            signature: self.sig_key.sign(&tree_head_message(size, &root)).to_bytes().to_vec(),
+        }
+    }
+
+    /// Inclusion proof of entry `index` in the current tree.
+    pub fn inclusion_proof(&self, index: usize) -> Vec<Hash> {
+        self.tree.inclusion_proof(index, self.tree.len())
+    }
+
+    /// Consistency proof from the tree of size `old_size` to the current tree.
+    pub fn consistency_proof(&self, old_size: usize) -> Vec<Hash> {
+        self.tree.consistency_proof(old_size, self.tree.len())
+    }
+
+    fn tree_size(&self, size: u64) -> Result<usize> {
+        if size > self.tree.len() as u64 {
+            return Err(Error::UnknownTreeSize(size));
+        }
+        Ok(size as usize)
+    }
+
+    // 先在状态上检查记录能否作用，再写日志；写盘失败就把该用户恢复原样，
+    // 于是状态、树和日志始终一致，审计者重放日志得到的正是这里的状态
+    fn append(&mut self, entry: BoardEntry) -> Result<()> {
+        let before = self.state.get_user(entry.user_id());
+        entry.apply(&mut self.state)?;
+        if let Some(log) = &mut self.log {
+            let logged = bcs::to_bytes(&entry).map_err(|e| Error::Store(e.to_string()))
+                .and_then(|bytes| Ok(log.append(&bytes)?));
+            if let Err(e) = logged {
+                self.state.restore(entry.user_id(), before)?;
+                return Err(e);
+            }
+        }
+        self.tree.push(entry.leaf_hash());
+        self.entries.push(entry);
+        Ok(())
+    }
+}
+
+impl BoardLog for MerkleBoard {
+    fn signed_head(&self) -> Result<SignedTreeHead> {
+        Ok(self.tree_head())
+    }
+
+    fn prove_consistency(&self, old_size: u64, new_size: u64) -> Result<Vec<Hash>> {
+        let n = self.tree_size(new_size)?;
+        Ok(self.tree.consistency_proof(old_size.min(new_size) as usize, n))
+    }
+
+    fn log_entries(&self, from: u64, to: u64) -> Result<Vec<BoardEntry>> {
+        let to = self.tree_size(to)?;
+        let mut bytes = 0;
+        let page = self.entries[(from as usize).min(to)..to].iter()
+            .take_while(|e| {
+                let first = bytes == 0;
+                bytes += bcs::serialized_size(e).unwrap_or(PAGE_BYTES);
+                first || bytes <= PAGE_BYTES
+            })
+            .cloned()
+            .collect();
+        Ok(page)
+    }
+
+    fn prove_user(&self, id: u64, size: u64) -> Result<(u64, BoardEntry, Vec<Hash>)> {
+        let n = self.tree_size(size)?;
+        let index = self.entries[..n].iter()
+            .position(|e| matches!(e, BoardEntry::NewUser { id: user, .. } if *user == id))
+            .ok_or(Error::UnknownUser(id))?;
+        Ok((index as u64, self.entries[index].clone(), self.tree.inclusion_proof(index, n)))
+    }
+
+    fn prove_posts(&self, id: u64, size: u64) -> Result<Vec<ProvenEntry>> {
+        let n = self.tree_size(size)?;
+        Ok(self.entries[..n].iter().enumerate()
+            .filter(|(_, e)| !matches!(e, BoardEntry::NewUser { .. }) && e.user_id() == id)
+            .map(|(index, e)| (index as u64, e.clone(), self.tree.inclusion_proof(index, n)))
+            .collect())
+    }
+}
+
+impl UserStore for MerkleBoard {
+    fn new_user(&mut self, id: u64, commitment: ReplicaCommitment, proof: InputProof) -> Result<()> {
+        self.append(BoardEntry::NewUser { id, commitment, proof, signature: None })
+    }
+
+    fn new_signed_user(&mut self, id: u64, commitment: ReplicaCommitment, proof: InputProof, sig: &MySignature) -> Result<()> {
+        self.append(BoardEntry::NewUser { id, commitment, proof, signature: Some(sig.clone()) })
+    }
+
+    fn get_user(&self, id: u64) -> Option<User> {
+        self.state.get_user(id)
+    }
+
+    fn get_user_commitment_proof(&self, id: u64) -> Option<(ReplicaCommitment, InputProof)> {
+        self.state.get_user_commitment_proof(id)
+    }
+
+    // 重复提交同样的签名或份额不再记入日志
+    fn sig_to_user(&mut self, id: u64, sig: MySignature, prover: usize) -> Result<()> {
+        if self.state.get_user(id).is_some_and(|user| user.signatures.get(prover) == Some(&Some(sig.clone()))) {
+            return Ok(());
+        }
+        self.append(BoardEntry::Signature { id, sig, prover })
+    }
+
+    fn upload_share(&mut self, id: u64, share: ReplicaShare, prover: usize) -> Result<()> {
+        if self.state.get_user(id).is_some_and(|user| user.share.get(prover) == Some(&Some(share.clone()))) {
+            return Ok(());
+        }
+        self.append(BoardEntry::Share { id, share, prover })
+    }
+
+    fn iter_all_users(&self) -> Result<Box<dyn Iterator<Item = User>>> {
+        self.state.iter_all_users()
+    }
+
+    fn check_all_users(&self, session_id: u64, pks: &[VerifyingKey], pp: &PublicParameters) -> Vec<u64> {
+        self.state.check_all_users(session_id, pks, pp)
+    }
+}
+
+/// What a prover or the verifier keeps to detect a board operator that equivocates: the latest
+/// tree head it accepted.
+pub struct BoardMonitor {
+    board_pk: VerifyingKey,
+    head: Option<SignedTreeHead>,
+}
+
+impl BoardMonitor {
+    pub fn new(board_pk: VerifyingKey) -> Self {
+        Self { board_pk, head: None }
+    }
+
+    pub fn get_head(&self) -> Option<&SignedTreeHead> {
+        self.head.as_ref()
+    }
+
+    /// Accepts `head` if it is signed by the board and `proof` shows it extends the last
+    /// accepted head.
+    pub fn update(&mut self, head: SignedTreeHead, proof: &[Hash]) -> Result<()> {
+        if !head.verify(&self.board_pk) {
+            return Err(Error::BadTreeHead);
+        }
+        if let Some(old) = &self.head {
+            if !verify_consistency(old.size as usize, head.size as usize, &old.root, &head.root, proof) {
+                return Err(Error::BoardEquivocation { old_size: old.size, new_size: head.size });
+            }
+        }
+        self.head = Some(head);
+        Ok(())
+    }
+
+    /// Fetches the board's current head and accepts it if it extends the last accepted one.
+    pub fn sync<L: BoardLog>(&mut self, log: &L) -> Result<()> {
+        let head = log.signed_head()?;
+        let proof = match &self.head {
+            Some(old) if old.size > 0 && old.size <= head.size => log.prove_consistency(old.size, head.size)?,
+            _ => Vec::new(),
+        };
+        self.update(head, &proof)
+    }
+
+    /// Checks that the log under the last accepted head holds the submission of user `id`, and
+    /// returns that entry.
+    pub fn check_user<L: BoardLog>(&self, log: &L, id: u64) -> Result<BoardEntry> {
+        let size = self.head.as_ref().ok_or(Error::BadTreeHead)?.size;
+        let (index, entry, proof) = log.prove_user(id, size)?;
+        let logged = matches!(&entry, BoardEntry::NewUser { id: user, .. } if *user == id);
+        if !logged || !self.check_inclusion(index as usize, &entry, &proof) {
+            return Err(Error::NotLogged(id));
+        }
+        Ok(entry)
+    }
+
+    /// Rebuilds the board from the log under the last accepted head, fetching it in pages of
+    /// at most `AUDIT_PAGE` entries.
+    pub fn audit<L: BoardLog>(&self, scheme: &SharingScheme, log: &L) -> Result<MemoryUserStore> {
+        let size = self.head.as_ref().ok_or(Error::BadTreeHead)?.size;
+        let mut entries = Vec::new();
+        while (entries.len() as u64) < size {
+            let from = entries.len() as u64;
+            let page = log.log_entries(from, (from + AUDIT_PAGE).min(size))?;
+            // 空页或超出请求范围的页都说明日志与树头不符
+            if page.is_empty() || page.len() as u64 > AUDIT_PAGE {
+                return Err(Error::BoardEquivocation { old_size: size, new_size: from + page.len() as u64 });
+            }
+            entries.extend(page);
+        }
+        self.replay(scheme, &entries)
+    }
+
+    /// Checks that `entry` is entry `index` under the last accepted head.
+    pub fn check_inclusion(&self, index: usize, entry: &BoardEntry, proof: &[Hash]) -> bool {
+        self.head.as_ref().is_some_and(|head| verify_inclusion(&entry.leaf_hash(), index, head.size as usize, &head.root, proof))
+    }
+
+    /// Rebuilds the board from all of its entries, checking they are exactly the log under
+    /// the last accepted head.
+    pub fn replay(&self, scheme: &SharingScheme, entries: &[BoardEntry]) -> Result<MemoryUserStore> {
+        let head = self.head.as_ref().ok_or(Error::BadTreeHead)?;
+        let leaves: Vec<Hash> = entries.iter().map(|e| e.leaf_hash()).collect();
+        if leaves.len() as u64 != head.size || merkle_root(&leaves) != head.root {
+            return Err(Error::BoardEquivocation { old_size: head.size, new_size: leaves.len() as u64 });
+        }
+        let mut store = MemoryUserStore::new(scheme);
+        for entry in entries {
+            entry.apply(&mut store)?;
+        }
+        Ok(store)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::client::Client;
+    use crate::error::Error;
+    use crate::public_parameters::PublicParameters;
+    use crate::replicated::SharingScheme;
+    use crate::sign;
+    use crate::user_store::UserStore;
+    use super::*;
+
+    #[test]
+    fn test_merkle_proofs() {
+        let leaves: Vec<Hash> = (0..13u8).map(|i| leaf_hash(&[i])).collect();
+        let mut tree = MerkleTree::default();
+        for n in 1..=leaves.len() {
+            tree.push(leaves[n - 1]);
+            let root = merkle_root(&leaves[..n]);
+            // 缓存的子树哈希给出同样的根和证明
+            assert_eq!(tree.root(0, n), root);
+            for m in 0..=n {
+                assert_eq!(tree.inclusion_proof(m.min(n - 1), n), inclusion_proof(m.min(n - 1), &leaves[..n]));
+                assert_eq!(tree.consistency_proof(m, n), consistency_proof(m, &leaves[..n]));
+            }
+            for m in 0..n {
+                assert!(verify_inclusion(&leaves[m], m, n, &root, &inclusion_proof(m, &leaves[..n])));
+                assert!(!verify_inclusion(&leaves[(m + 1) % 13], m, n, &root, &inclusion_proof(m, &leaves[..n])));
+            }
+            for m in 1..=n {
+                let proof = consistency_proof(m, &leaves[..n]);
+                assert!(verify_consistency(m, n, &merkle_root(&leaves[..m]), &root, &proof));
+                if m < n {
+                    assert!(!verify_consistency(m, n, &merkle_root(&leaves[1..m + 1]), &root, &proof));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_board_equivocation() {
+        let scheme = SharingScheme::new(3, 1);
+        let pp = PublicParameters::new(b"seed");
+        let (board_key, board_pk) = sign::gen_keys();
+        let mut board = MerkleBoard::new(&scheme, board_key.clone());
+        let mut monitor = BoardMonitor::new(board_pk);
+
+        for id in 0..3u64 {
+            Client::new(0, id, true, &pp, &scheme, Vec::new()).send_proof_coms(&mut board).unwrap();
+        }
+        monitor.update(board.tree_head(), &[]).unwrap();
+        assert!(board.sig_to_user(7, Default::default(), 0).is_err());
+        assert_eq!(board.size(), 3);
+
+        let client = Client::new(0, 3, false, &pp, &scheme, Vec::new());
+        client.send_proof_coms(&mut board).unwrap();
+        board.upload_share(3, client.send_share(1).1, 1).unwrap();
+        monitor.update(board.tree_head(), &board.consistency_proof(3)).unwrap();
+        assert!(monitor.check_inclusion(4, &board.entries(4, 5)[0], &board.inclusion_proof(4)));
+        assert_eq!(monitor.replay(&scheme, &board.entries(0, 5)).unwrap().iter_all_users().unwrap().count(), 4);
+
+        // 运营者给另一个读者看的分叉：去掉了 id 1 的记录
+        let mut fork = MerkleBoard::new(&scheme, board_key);
+        for entry in board.entries(0, 5).iter().enumerate().filter(|(i, _)| *i != 1).map(|(_, e)| e) {
+            fork.append(entry.clone()).unwrap();
+        }
+        let mut forked = BoardMonitor::new(board_pk);
+        forked.update(board.tree_head(), &[]).unwrap();
+        let err = forked.update(fork.tree_head(), &fork.consistency_proof(5));
+        assert_eq!(err, Err(Error::BoardEquivocation { old_size: 5, new_size: 4 }));
+        assert!(monitor.replay(&scheme, &fork.entries(0, 4)).is_err());
+
+        let mut bad = board.tree_head();
+        bad.root[0] ^= 1;
+        assert_eq!(monitor.update(bad, &[]), Err(Error::BadTreeHead));
+    }
+
+    #[test]
+    fn test_persistent_board() {
+        let scheme = SharingScheme::new(3, 1);
+        let pp = PublicParameters::new(b"seed");
+        let (board_key, board_pk) = sign::gen_keys();
+        let path = std::env::temp_dir().join(format!("rvdp_merkle_board_{}.log", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+
+        let mut board = MerkleBoard::open(&path, &scheme, board_key.clone(), FsyncPolicy::Always).unwrap();
+        let mut monitor = BoardMonitor::new(board_pk);
+        for id in 0..2u64 {
+            Client::new(0, id, true, &pp, &scheme, Vec::new()).send_proof_coms(&mut board).unwrap();
+        }
+        monitor.sync(&board).unwrap();
+        drop(board);
+
+        // 重启后日志原样恢复，新树头仍是旧树头的扩展
+        let mut board = MerkleBoard::open(&path, &scheme, board_key, FsyncPolicy::Always).unwrap();
+        assert_eq!(board.size(), 2);
+        Client::new(0, 2, false, &pp, &scheme, Vec::new()).send_proof_coms(&mut board).unwrap();
+        monitor.sync(&board).unwrap();
+        assert!(matches!(monitor.check_user(&board, 2).unwrap(), BoardEntry::NewUser { id: 2, .. }));
+        assert!(matches!(monitor.check_user(&board, 5), Err(Error::UnknownUser(5))));
+        assert_eq!(monitor.audit(&scheme, &board).unwrap().iter_all_users().unwrap().count(), 3);
+        assert_eq!(board.prove_consistency(1, 4).unwrap_err(), Error::UnknownTreeSize(4));
+
+        // 写日志失败的记录既不进入状态也不进入树
+        board.log = Some(RecordLog::open_read_only(&path).unwrap().0);
+        assert!(Client::new(0, 3, false, &pp, &scheme, Vec::new()).send_proof_coms(&mut board).is_err());
+        assert!(board.get_user(3).is_none());
+        assert_eq!(board.tree_head(), monitor.get_head().unwrap().clone());
+        std::fs::remove_file(&path).unwrap();
+    }
+}
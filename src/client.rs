@@ -1,40 +1,103 @@
 use blstrs::Scalar;
-use ed25519_dalek::VerifyingKey;
+use ed25519_dalek::{SigningKey, VerifyingKey};
+use crate::client_auth::sign_submission;
 use crate::public_parameters::PublicParameters;
-use crate::sigma_or::{ProofStruct, create_proof_0, create_proof_1};
-use crate::replicated::{ReplicaSecret, ReplicaCommitment, ReplicaShare};
-use crate::constants;
+use crate::sigma_or::{ProofContext, create_proof_0, create_proof_1};
+use crate::range_proof::{InputProof, create_range_proof};
+use crate::one_hot::create_one_hot_proof;
+use crate::fixed_point::FixedPoint;
+use crate::replicated::{ReplicaSecret, ReplicaCommitment, ReplicaShare, SharingScheme};
 use crate::user_store::UserStore;
+use crate::error::{Error, Result};
 
 pub struct Client{
     id: u64,
-    secret: ReplicaSecret,
+    secrets: Vec<ReplicaSecret>, // 每个桶一份秘密，非直方图查询只有一份
     coms: ReplicaCommitment,
-    sigma_proof: ProofStruct,
-    pks: [VerifyingKey;constants::PROVER_NUM],
+    sigma_proof: InputProof,
+    pks: Vec<VerifyingKey>,
 }
 
 impl Client{
-    pub fn new(id: u64, x: bool,pp:&PublicParameters,pks: [VerifyingKey;constants::PROVER_NUM]) -> Self {
+    pub fn new(session_id: u64, id: u64, x: bool,pp:&PublicParameters,scheme:&SharingScheme,pks: Vec<VerifyingKey>) -> Self {
         let x_scalar = Scalar::from(x as u64);
-        let secret=ReplicaSecret::new(x_scalar.clone());
+        let secret=ReplicaSecret::new(x_scalar, scheme);
         let r_sum=secret.get_sum_r();
-        let coms=secret.commit(pp.get_commit_base().clone());
-        let proof;
-        if x{
-            proof = create_proof_1(&pp.get_commit_base(), x_scalar.clone(), r_sum.clone());
+        let coms=ReplicaCommitment::new(scheme, secret.commit(pp.get_commit_base().clone()));
+        let ctx = ProofContext::new(session_id, id, pp, &coms);
+        let proof = if x {
+            create_proof_1(pp.get_commit_base(), x_scalar, r_sum, &ctx)
+        } else {
+            create_proof_0(pp.get_commit_base(), x_scalar, r_sum, &ctx)
+        };
+
+        Self {
+            id,
+            secrets: vec![secret],
+            coms,
+            sigma_proof: proof.into(),
+            pks,
         }
-        else{
-            proof = create_proof_0(&pp.get_commit_base(), x_scalar.clone(), r_sum.clone());
+    }
+
+    /// A client contributing an integer `x` in `[0, 2^pp.get_input_bits())`.
+    pub fn new_range(session_id: u64, id: u64, x: u64, pp:&PublicParameters, scheme:&SharingScheme, pks: Vec<VerifyingKey>) -> Result<Self> {
+        let bits = pp.get_input_bits();
+        if bits < 64 && x >> bits != 0 {
+            return Err(Error::InputOutOfRange { value: x, bits });
         }
+        let secret=ReplicaSecret::new(Scalar::from(x), scheme);
+        let coms=ReplicaCommitment::new(scheme, secret.commit(pp.get_commit_base().clone()));
+        let ctx = ProofContext::new(session_id, id, pp, &coms);
+        let proof = create_range_proof(pp.get_commit_base(), x, secret.get_sum_r(), bits, &ctx);
 
-        Self {
+        Ok(Self {
             id,
-            secret,
-            coms: ReplicaCommitment::new(coms),
-            sigma_proof: proof,
+            secrets: vec![secret],
+            coms,
+            sigma_proof: proof.into(),
             pks,
+        })
+    }
+
+    /// A client contributing a real `x`, clipped and encoded with `enc`; `pp` should come from
+    /// `enc.public_parameters`.
+    pub fn new_fixed_point(session_id: u64, id: u64, x: f64, enc: &FixedPoint, pp:&PublicParameters, scheme:&SharingScheme, pks: Vec<VerifyingKey>) -> Result<Self> {
+        Self::new_range(session_id, id, enc.encode(x), pp, scheme, pks)
+    }
+
+    /// A client voting for `bucket` in a histogram over `pp.get_buckets()` buckets.
+    pub fn new_histogram(session_id: u64, id: u64, bucket: usize, pp:&PublicParameters, scheme:&SharingScheme, pks: Vec<VerifyingKey>) -> Result<Self> {
+        let buckets = pp.get_buckets();
+        if bucket >= buckets {
+            return Err(Error::UnknownBucket { bucket, buckets });
         }
+        let secrets: Vec<ReplicaSecret> = (0..buckets)
+            .map(|b| ReplicaSecret::new(Scalar::from((b == bucket) as u64), scheme))
+            .collect();
+        let coms=ReplicaCommitment::concat(secrets.iter()
+            .map(|secret| ReplicaCommitment::new(scheme, secret.commit(pp.get_commit_base().clone())))
+            .collect());
+        let ctx = ProofContext::new(session_id, id, pp, &coms);
+        let r_sums: Vec<Scalar> = secrets.iter().map(|secret| secret.get_sum_r()).collect();
+        let proof = create_one_hot_proof(pp.get_commit_base(), bucket, &r_sums, &ctx);
+
+        Ok(Self {
+            id,
+            secrets,
+            coms,
+            sigma_proof: proof.into(),
+            pks,
+        })
+    }
+
+    // 各个桶的份额按桶顺序拼接
+    fn get_share(&self, proverind:usize) -> ReplicaShare {
+        ReplicaShare::concat(self.secrets.iter().map(|secret| secret.get_share(proverind)).collect())
+    }
+
+    pub fn get_id(&self) -> u64 {
+        self.id
     }
 
     pub fn get_coms(&self) -> ReplicaCommitment {
@@ -42,30 +105,30 @@ impl Client{
     }
 
 
-    pub fn send_proof_coms<'a, D :UserStore>(&self, broad: &'a mut D) -> bool {
+    pub fn send_proof_coms<D :UserStore>(&self, broad: &mut D) -> Result<()> {
         broad.new_user(self.id, self.coms.clone(), self.sigma_proof.clone())
     }
 
+    /// Posts the commitments and proof signed with the client's identity key.
+    pub fn send_signed_proof_coms<D :UserStore>(&self, broad: &mut D, session_id: u64, identity: &SigningKey) -> Result<()> {
+        let sig = sign_submission(identity, session_id, self.id, &self.coms, &self.sigma_proof);
+        broad.new_signed_user(self.id, self.coms.clone(), self.sigma_proof.clone(), &sig)
+    }
+
     pub fn send_share(&self, proverind:usize) -> (u64, ReplicaShare) {
-        let share=self.secret.get_share(proverind);
+        let share=self.get_share(proverind);
         (self.id, share)
     }
 
-    pub fn reveal_share<'a, D:UserStore>(&self, broad: &'a mut D) -> bool {
-        match broad.get_user(self.id) {
-            Some(user) => {
-                let signed=user.check_signature(&self.pks.to_vec());
-                for i in 0..constants::PROVER_NUM {
-                    if !signed.contains(&i) {
-                        broad.upload_share(self.id, self.secret.get_share(i), i);
-                    }
-                }
-                return true;
-            }
-            None => {
-                return false;
+    pub fn reveal_share<D:UserStore>(&self, broad: &mut D) -> Result<()> {
+        let user = broad.get_user(self.id).ok_or(Error::UnknownUser(self.id))?;
+        let signed=user.check_signature(&self.pks);
+        for i in 0..self.pks.len() {
+            if !signed.contains(&i) {
+                broad.upload_share(self.id, self.get_share(i), i)?;
             }
         }
+        Ok(())
     }
 }
 
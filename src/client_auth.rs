@@ -0,0 +1,230 @@
+// 客户端身份：注册表把每个客户端 id 绑定到一把 ed25519 公钥，客户端提交承诺时用对应私钥签名。
+// 一把公钥只能注册一个 id，公告板又拒绝重复的 id，所以每个身份至多贡献一次
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+use sha3::{Digest, Sha3_256};
+use std::collections::HashMap;
+use crate::bulletin_board::{BoardEntry, BoardLog, Hash, ProvenEntry, SignedTreeHead};
+use crate::error::{Error, Result};
+use crate::public_parameters::PublicParameters;
+use crate::range_proof::InputProof;
+use crate::replicated::{ReplicaCommitment, ReplicaShare};
+use crate::sign::MySignature;
+use crate::user_store::{User, UserStore};
+
+fn submission_message(session_id: u64, id: u64, commitment: &ReplicaCommitment, proof: &InputProof) -> Vec<u8> {
+    let mut hasher = Sha3_256::new();
+    hasher.update(bcs::to_bytes(commitment).unwrap());
+    hasher.update(bcs::to_bytes(proof).unwrap());
+    let mut msg = crate::DST_ROBUST_DP_CLIENT_SUBMISSION.to_vec();
+    msg.extend(session_id.to_le_bytes());
+    msg.extend(id.to_le_bytes());
+    msg.extend(hasher.finalize());
+    msg
+}
+
+/// Signs client `id`'s commitments and input proof with its identity key.
+pub fn sign_submission(identity: &SigningKey, session_id: u64, id: u64, commitment: &ReplicaCommitment, proof: &InputProof) -> MySignature {
+    identity.sign(&submission_message(session_id, id, commitment, proof)).into()
+}
+
+pub fn verify_submission(pk: &VerifyingKey, session_id: u64, id: u64, commitment: &ReplicaCommitment, proof: &InputProof, sig: &MySignature) -> bool {
+    let sig: Signature = sig.clone().into();
+    pk.verify(&submission_message(session_id, id, commitment, proof), &sig).is_ok()
+}
+
+/// The clients allowed to contribute and their identity keys.
+#[derive(Clone, Debug, Default)]
+pub struct ClientRegistry {
+    keys: HashMap<u64, VerifyingKey>,
+    owners: HashMap<[u8; 32], u64>, // 公钥 → 已注册的 id
+}
+
+impl ClientRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers `pk` as the identity of client `id`. Neither the id nor the key may be
+    /// registered already.
+    pub fn register(&mut self, id: u64, pk: VerifyingKey) -> Result<()> {
+        if self.keys.contains_key(&id) {
+            return Err(Error::AlreadyRegistered(id));
+        }
+        if let Some(&owner) = self.owners.get(pk.as_bytes()) {
+            return Err(Error::DuplicateClientKey(owner));
+        }
+        self.owners.insert(pk.to_bytes(), id);
+        self.keys.insert(id, pk);
+        Ok(())
+    }
+
+    pub fn get_key(&self, id: u64) -> Option<VerifyingKey> {
+        self.keys.get(&id).copied()
+    }
+
+    /// The client `pk` is registered to.
+    pub fn get_id(&self, pk: &VerifyingKey) -> Option<u64> {
+        self.owners.get(pk.as_bytes()).copied()
+    }
+
+    /// The registered clients in id order.
+    pub fn clients(&self) -> Vec<(u64, VerifyingKey)> {
+        let mut clients: Vec<_> = self.keys.iter().map(|(&id, &pk)| (id, pk)).collect();
+        clients.sort_unstable_by_key(|&(id, _)| id);
+        clients
+    }
+
+    /// Checks that the board record of `user` carries its registered client's signature.
+    pub fn check_user(&self, session_id: u64, user: &User) -> Result<()> {
+        let pk = self.get_key(user.id).ok_or(Error::UnregisteredClient(user.id))?;
+        let sig = user.submission_sig.as_ref().ok_or(Error::UnsignedSubmission(user.id))?;
+        if !verify_submission(&pk, session_id, user.id, &user.commitment, &user.sigma_proof, sig) {
+            return Err(Error::BadSubmissionSignature(user.id));
+        }
+        Ok(())
+    }
+
+    pub fn len(&self) -> usize {
+        self.keys.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+}
+
+/// A `UserStore` that only accepts submissions signed by a registered client, at most one per
+/// client.
+pub struct AuthenticatedStore<S: UserStore> {
+    inner: S,
+    registry: ClientRegistry,
+    session_id: u64,
+}
+
+impl<S: UserStore> AuthenticatedStore<S> {
+    pub fn new(inner: S, registry: ClientRegistry, session_id: u64) -> Self {
+        Self { inner, registry, session_id }
+    }
+
+    pub fn get_registry(&self) -> &ClientRegistry {
+        &self.registry
+    }
+
+    pub fn into_inner(self) -> S {
+        self.inner
+    }
+}
+
+impl<S: UserStore> UserStore for AuthenticatedStore<S> {
+    fn new_user(&mut self, id: u64, _commitment: ReplicaCommitment, _sigma_proof: InputProof) -> Result<()> {
+        Err(Error::UnsignedSubmission(id))
+    }
+
+    fn new_signed_user(&mut self, id: u64, commitment: ReplicaCommitment, sigma_proof: InputProof, sig: &MySignature) -> Result<()> {
+        let pk = self.registry.get_key(id).ok_or(Error::UnregisteredClient(id))?;
+        if !verify_submission(&pk, self.session_id, id, &commitment, &sigma_proof, sig) {
+            return Err(Error::BadSubmissionSignature(id));
+        }
+        if self.inner.get_user(id).is_some() {
+            return Err(Error::DuplicateUser(id));
+        }
+        self.inner.new_signed_user(id, commitment, sigma_proof, sig)
+    }
+
+    fn get_user(&self, id: u64) -> Option<User> {
+        self.inner.get_user(id)
+    }
+
+    fn get_user_commitment_proof(&self, id: u64) -> Option<(ReplicaCommitment, InputProof)> {
+        self.inner.get_user_commitment_proof(id)
+    }
+
+    fn sig_to_user(&mut self, id: u64, sig: MySignature, proverid: usize) -> Result<()> {
+        self.inner.sig_to_user(id, sig, proverid)
+    }
+
+    fn upload_share(&mut self, id: u64, share: ReplicaShare, proverid: usize) -> Result<()> {
+        self.inner.upload_share(id, share, proverid)
+    }
+
+    fn iter_all_users(&self) -> Result<Box<dyn Iterator<Item = User>>> {
+        self.inner.iter_all_users()
+    }
+
+    fn check_all_users(&self, session_id: u64, pks: &[VerifyingKey], pp: &PublicParameters) -> Vec<u64> {
+        self.inner.check_all_users(session_id, pks, pp)
+    }
+}
+
+impl<S: UserStore + BoardLog> BoardLog for AuthenticatedStore<S> {
+    fn signed_head(&self) -> Result<SignedTreeHead> {
+        self.inner.signed_head()
+    }
+
+    fn prove_consistency(&self, old_size: u64, new_size: u64) -> Result<Vec<Hash>> {
+        self.inner.prove_consistency(old_size, new_size)
+    }
+
+    fn log_entries(&self, from: u64, to: u64) -> Result<Vec<BoardEntry>> {
+        self.inner.log_entries(from, to)
+    }
+
+    fn prove_user(&self, id: u64, size: u64) -> Result<(u64, BoardEntry, Vec<Hash>)> {
+        self.inner.prove_user(id, size)
+    }
+
+    fn prove_posts(&self, id: u64, size: u64) -> Result<Vec<ProvenEntry>> {
+        self.inner.prove_posts(id, size)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::bulletin_board::{BoardMonitor, MerkleBoard};
+    use crate::client::Client;
+    use crate::sign;
+    use crate::test_utils::{setup, Setup};
+    use super::*;
+
+    #[test]
+    fn test_authenticated_store() {
+        let Setup { scheme, pp, pks, .. } = setup(3, 1);
+        let (alice_sk, alice_pk) = sign::gen_keys();
+        let (mallory_sk, mallory_pk) = sign::gen_keys();
+
+        let mut registry = ClientRegistry::new();
+        registry.register(1, alice_pk).unwrap();
+        registry.register(2, mallory_pk).unwrap();
+        // 一把公钥不能换个 id 再注册一次
+        assert_eq!(registry.register(3, alice_pk), Err(Error::DuplicateClientKey(1)));
+        assert_eq!(registry.register(2, sign::gen_keys().1), Err(Error::AlreadyRegistered(2)));
+        let (board_key, board_pk) = sign::gen_keys();
+        let mut broad = AuthenticatedStore::new(MerkleBoard::new(&scheme, board_key), registry, 0);
+
+        let alice = Client::new(0, 1, true, &pp, &scheme, pks.clone());
+        assert_eq!(alice.send_proof_coms(&mut broad), Err(Error::UnsignedSubmission(1)));
+        // mallory 不能冒充 alice，也不能用未注册的 id 提交
+        assert_eq!(alice.send_signed_proof_coms(&mut broad, 0, &mallory_sk), Err(Error::BadSubmissionSignature(1)));
+        assert_eq!(alice.send_signed_proof_coms(&mut broad, 1, &alice_sk), Err(Error::BadSubmissionSignature(1)));
+        let stranger = Client::new(0, 5, true, &pp, &scheme, pks.clone());
+        assert_eq!(stranger.send_signed_proof_coms(&mut broad, 0, &mallory_sk), Err(Error::UnregisteredClient(5)));
+
+        alice.send_signed_proof_coms(&mut broad, 0, &alice_sk).unwrap();
+        // 同一身份的第二次贡献不会覆盖第一次
+        let again = Client::new(0, 1, false, &pp, &scheme, pks.clone());
+        assert_eq!(again.send_signed_proof_coms(&mut broad, 0, &alice_sk), Err(Error::DuplicateUser(1)));
+        assert!(broad.get_user(1).unwrap().commitment == alice.get_coms());
+
+        // 签名记在公告板的日志里，审计者重放日志后仍能检查客户端身份
+        let registry = broad.get_registry().clone();
+        let board = broad.into_inner();
+        let mut monitor = BoardMonitor::new(board_pk);
+        monitor.update(board.tree_head(), &[]).unwrap();
+        let mut user = monitor.replay(&scheme, &board.entries(0, board.size())).unwrap().get_user(1).unwrap();
+        assert_eq!(registry.check_user(0, &user), Ok(()));
+        assert_eq!(registry.check_user(1, &user), Err(Error::BadSubmissionSignature(1)));
+        user.submission_sig = None;
+        assert_eq!(registry.check_user(0, &user), Err(Error::UnsignedSubmission(1)));
+    }
+}
@@ -0,0 +1,365 @@
+// 客户端一次提交的状态机：提交承诺 → 发送份额（带重试）→ 等待签名出现在公告板上 →
+// 为未签名的 prover 公开份额 → 从公告板取回自己的记录作为回执。
+// 收据只有覆盖的正是本客户端的承诺时才算数，公告板上的承诺与本地不符则中止。
+// 客户端每次读公告板都同步树头，提交要有包含证明，回执取自重放的日志，而不是运营者返回的记录
+use ed25519_dalek::{SigningKey, VerifyingKey};
+use rustls::ClientConfig;
+use serde::{Serialize, Deserialize};
+use std::io;
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+use crate::bulletin_board::{BoardEntry, BoardLog, BoardMonitor};
+use crate::client::Client;
+use crate::error::{Error, Result};
+use crate::prover_service::submit_share;
+use crate::public_parameters::PublicParameters;
+use crate::replicated::{ReplicaShare, SharingScheme};
+use crate::receipt::{ExclusionClaim, InclusionReceipt, ValidUserSet};
+use crate::tls::client_config;
+use crate::user_store::{User, UserStore};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum Phase {
+    Created,
+    Committed,
+    SharesSent,
+    AwaitingSignatures,
+    Revealed,
+    Finalized,
+}
+
+/// How hard the client tries to reach the provers.
+#[derive(Clone, Copy, Debug)]
+pub struct RetryPolicy {
+    /// Attempts per prover when sending its share.
+    pub attempts: usize,
+    /// Wait before the first retry; doubled after every failed attempt.
+    pub backoff: Duration,
+    /// How long to wait for signatures on the board before revealing shares.
+    pub signature_deadline: Duration,
+    pub poll_interval: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            attempts: 3,
+            backoff: Duration::from_millis(500),
+            signature_deadline: Duration::from_secs(30),
+            poll_interval: Duration::from_millis(500),
+        }
+    }
+}
+
+/// Delivers a client's share to a prover and returns the prover's receipt.
+pub trait ShareTransport {
+    fn send_share(&mut self, prover: usize, client_share: (u64, ReplicaShare)) -> Result<InclusionReceipt>;
+}
+
+/// Provers reached over TLS, one address per prover. Each connection pins the prover's key and
+/// authenticates the client with its identity key.
+pub struct TlsTransport {
+    addrs: Vec<String>,
+    configs: Vec<Arc<ClientConfig>>,
+    session_id: u64,
+    timeout: Duration,
+}
+
+impl TlsTransport {
+    pub fn new(addrs: &[String], pks: &[VerifyingKey], identity: &SigningKey, session_id: u64, timeout: Duration) -> io::Result<Self> {
+        let configs = pks.iter().map(|pk| client_config(*pk, Some(identity))).collect::<io::Result<_>>()?;
+        Ok(Self { addrs: addrs.to_vec(), configs, session_id, timeout })
+    }
+}
+
+impl ShareTransport for TlsTransport {
+    fn send_share(&mut self, prover: usize, client_share: (u64, ReplicaShare)) -> Result<InclusionReceipt> {
+        let (addr, tls) = self.addrs.get(prover).zip(self.configs.get(prover)).ok_or(Error::UnknownProver(prover))?;
+        submit_share(addr.as_str(), tls.clone(), self.session_id, client_share, self.timeout)
+    }
+}
+
+/// The board's record of a contribution that passes `check_all_users`, with the provers'
+/// receipts.
+#[derive(Clone)]
+pub struct ContributionReceipt {
+    pub session_id: u64,
+    pub user: User,
+    pub receipts: Vec<InclusionReceipt>,
+}
+
+impl ContributionReceipt {
+    /// Whether the record proves that every prover counts the contribution.
+    pub fn verify(&self, scheme: &SharingScheme, pks: &[VerifyingKey], pp: &PublicParameters) -> bool {
+        self.user.check_whole(scheme, self.session_id, pks, pp)
+    }
+
+    /// Evidence to hold against a published user set that leaves this contribution out, with
+    /// the board's proofs that the submission and the provers' signatures and revealed shares
+    /// are in the log the set was counted from.
+    pub fn exclusion_claim<L: BoardLog>(&self, log: &L, published: &ValidUserSet) -> Result<ExclusionClaim> {
+        let (index, entry, proof) = log.prove_user(self.user.id, published.board_size)?;
+        let posts = log.prove_posts(self.user.id, published.board_size)?;
+        Ok(ExclusionClaim { user: self.user.clone(), index, entry, proof, posts, receipts: self.receipts.clone() })
+    }
+}
+
+pub struct ClientSession {
+    client: Client,
+    session_id: u64,
+    scheme: SharingScheme,
+    pp: PublicParameters,
+    pks: Vec<VerifyingKey>,
+    policy: RetryPolicy,
+    identity: Option<SigningKey>, // 公告板验证客户端身份时用来签名提交
+    monitor: BoardMonitor,
+    phase: Phase,
+    receipts: Vec<Option<InclusionReceipt>>, // 已验证的、覆盖本客户端承诺的收据
+    errors: Vec<Option<Error>>, // 每个 prover 最后一次失败的原因
+    revealed: Vec<usize>,
+}
+
+impl ClientSession {
+    /// `board_pk` is the key the board signs its tree heads with.
+    pub fn new(client: Client, session_id: u64, scheme: &SharingScheme, pp: &PublicParameters, pks: &[VerifyingKey], board_pk: VerifyingKey, policy: RetryPolicy) -> Self {
+        Self {
+            client,
+            session_id,
+            scheme: scheme.clone(),
+            pp: pp.clone(),
+            pks: pks.to_vec(),
+            policy,
+            identity: None,
+            monitor: BoardMonitor::new(board_pk),
+            phase: Phase::Created,
+            receipts: vec![None; pks.len()],
+            errors: vec![None; pks.len()],
+            revealed: Vec::new(),
+        }
+    }
+
+    /// Signs the submission with the client's registered identity key.
+    pub fn with_identity(mut self, identity: SigningKey) -> Self {
+        self.identity = Some(identity);
+        self
+    }
+
+    pub fn get_phase(&self) -> Phase {
+        self.phase
+    }
+
+    /// Provers whose valid receipt the client holds.
+    pub fn receipted(&self) -> Vec<usize> {
+        (0..self.pks.len()).filter(|&i| self.receipts[i].is_some()).collect()
+    }
+
+    /// Provers whose shares were revealed on the board.
+    pub fn revealed(&self) -> &[usize] {
+        &self.revealed
+    }
+
+    /// Why sending the share to `prover` last failed.
+    pub fn get_error(&self, prover: usize) -> Option<&Error> {
+        self.errors.get(prover)?.as_ref()
+    }
+
+    fn expect(&self, phase: Phase) -> Result<()> {
+        if self.phase != phase {
+            return Err(Error::WrongPhase { expected: phase, got: self.phase });
+        }
+        Ok(())
+    }
+
+    fn is_valid(&self, prover: usize, receipt: &InclusionReceipt) -> bool {
+        receipt.prover == prover && receipt.verify(&self.pks[prover], self.session_id, self.client.get_id(), &self.client.get_coms())
+    }
+
+    // 公告板上本客户端的记录，承诺必须与本地一致
+    fn board_user<B: UserStore + BoardLog>(&mut self, board: &B) -> Result<User> {
+        self.monitor.sync(board)?;
+        let id = self.client.get_id();
+        let user = board.get_user(id).ok_or(Error::UnknownUser(id))?;
+        if user.commitment != self.client.get_coms() {
+            return Err(Error::CommitmentMismatch(id));
+        }
+        Ok(user)
+    }
+
+    /// Posts the commitments and checks that the board's log includes them.
+    pub fn commit<B: UserStore + BoardLog>(&mut self, board: &mut B) -> Result<()> {
+        self.expect(Phase::Created)?;
+        match &self.identity {
+            Some(identity) => self.client.send_signed_proof_coms(board, self.session_id, identity)?,
+            None => self.client.send_proof_coms(board)?,
+        }
+        self.monitor.sync(board)?;
+        let id = self.client.get_id();
+        match self.monitor.check_user(board, id)? {
+            BoardEntry::NewUser { commitment, .. } if commitment == self.client.get_coms() => {}
+            _ => return Err(Error::CommitmentMismatch(id)),
+        }
+        self.phase = Phase::Committed;
+        Ok(())
+    }
+
+    /// Sends every prover its share, retrying with exponential backoff. Receipts that do not
+    /// cover the client's commitments count as failures.
+    pub fn send_shares<T: ShareTransport>(&mut self, transport: &mut T) -> Result<()> {
+        self.expect(Phase::Committed)?;
+        for i in 0..self.pks.len() {
+            let mut backoff = self.policy.backoff;
+            for attempt in 0..self.policy.attempts {
+                if attempt > 0 {
+                    thread::sleep(backoff);
+                    backoff *= 2;
+                }
+                match transport.send_share(i, self.client.send_share(i)) {
+                    Ok(receipt) if self.is_valid(i, &receipt) => {
+                        self.receipts[i] = Some(receipt);
+                        self.errors[i] = None;
+                        break;
+                    }
+                    Ok(_) => self.errors[i] = Some(Error::BadSignature(i)),
+                    Err(e) => self.errors[i] = Some(e),
+                }
+            }
+        }
+        self.phase = Phase::SharesSent;
+        Ok(())
+    }
+
+    /// Polls the board until every prover's signature is there or the deadline passes. Returns
+    /// the provers still missing.
+    pub fn await_signatures<B: UserStore + BoardLog>(&mut self, board: &B) -> Result<Vec<usize>> {
+        if self.phase == Phase::SharesSent {
+            self.phase = Phase::AwaitingSignatures;
+        }
+        self.expect(Phase::AwaitingSignatures)?;
+        let start = Instant::now();
+        loop {
+            let on_board = self.board_user(board)?.check_signature(&self.pks);
+            let missing: Vec<usize> = (0..self.pks.len()).filter(|i| !on_board.contains(i)).collect();
+            if missing.is_empty() || start.elapsed() >= self.policy.signature_deadline {
+                return Ok(missing);
+            }
+            thread::sleep(self.policy.poll_interval);
+        }
+    }
+
+    /// Reveals on the board the shares of provers whose signature is not on the board.
+    pub fn reveal<B: UserStore + BoardLog>(&mut self, board: &mut B) -> Result<()> {
+        self.expect(Phase::AwaitingSignatures)?;
+        let on_board = self.board_user(board)?.check_signature(&self.pks);
+        for i in 0..self.pks.len() {
+            if !on_board.contains(&i) {
+                board.upload_share(self.client.get_id(), self.client.send_share(i).1, i)?;
+                self.revealed.push(i);
+            }
+        }
+        self.phase = Phase::Revealed;
+        Ok(())
+    }
+
+    /// Replays the board's log and checks that the client's record in it passes
+    /// `check_all_users`.
+    pub fn finalize<B: UserStore + BoardLog>(&mut self, board: &B) -> Result<ContributionReceipt> {
+        self.expect(Phase::Revealed)?;
+        self.monitor.sync(board)?;
+        let id = self.client.get_id();
+        let user = self.monitor.audit(&self.scheme, board)?.get_user(id).ok_or(Error::NotLogged(id))?;
+        if user.commitment != self.client.get_coms() {
+            return Err(Error::CommitmentMismatch(id));
+        }
+        user.validate(&self.scheme, self.session_id, &self.pks, &self.pp)?;
+        self.phase = Phase::Finalized;
+        let receipts = self.receipts.iter().flatten().cloned().collect();
+        Ok(ContributionReceipt { session_id: self.session_id, user, receipts })
+    }
+
+    /// Runs every phase against a board and provers that do not share state.
+    pub fn run<B: UserStore + BoardLog, T: ShareTransport>(&mut self, board: &mut B, transport: &mut T) -> Result<ContributionReceipt> {
+        self.commit(board)?;
+        self.send_shares(transport)?;
+        self.await_signatures(board)?;
+        self.reveal(board)?;
+        self.finalize(board)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use crate::bulletin_board::MerkleBoard;
+    use crate::client::Client;
+    use crate::prover::Prover;
+    use crate::replicated::ReplicaShare;
+    use crate::share_store::MemoryShareStore;
+    use crate::receipt::InclusionReceipt;
+    use crate::sign;
+    use crate::test_utils::{setup, Setup};
+    use crate::user_store::UserStore;
+    use super::*;
+
+    // prover 0 第一次失败，prover 1 返回错误的收据，prover 2 不可达
+    struct FlakyProvers<'a, 'b> {
+        provers: Vec<Prover<'a, MemoryShareStore>>,
+        board: &'b mut MerkleBoard,
+        calls: Vec<usize>,
+    }
+
+    impl ShareTransport for FlakyProvers<'_, '_> {
+        fn send_share(&mut self, prover: usize, client_share: (u64, ReplicaShare)) -> Result<InclusionReceipt> {
+            self.calls[prover] += 1;
+            match prover {
+                0 if self.calls[0] == 1 => Err(Error::Connection("connection reset".to_string())),
+                1 => {
+                    let coms = self.board.get_user(client_share.0).unwrap().commitment;
+                    Ok(InclusionReceipt::issue(&sign::gen_keys().0, 0, client_share.0, &coms, 1))
+                }
+                2 => Err(Error::Connection("connection refused".to_string())),
+                _ => self.provers[prover].handle_client(client_share, self.board),
+            }
+        }
+    }
+
+    #[test]
+    fn test_client_session() {
+        let Setup { scheme, pp, pks, provers, .. } = setup(3, 1);
+        let (board_key, board_pk) = sign::gen_keys();
+        let mut board = MerkleBoard::new(&scheme, board_key.clone());
+
+        let policy = RetryPolicy {
+            attempts: 2,
+            backoff: Duration::from_millis(1),
+            signature_deadline: Duration::from_millis(20),
+            poll_interval: Duration::from_millis(5),
+        };
+        let client = Client::new(0, 9, true, &pp, &scheme, pks.clone());
+        let mut session = ClientSession::new(client, 0, &scheme, &pp, &pks, board_pk, policy);
+        assert!(session.reveal(&mut board).is_err());
+        session.commit(&mut board).unwrap();
+
+        let mut transport = FlakyProvers { provers, board: &mut board, calls: vec![0; 3] };
+        session.send_shares(&mut transport).unwrap();
+        assert_eq!(transport.calls, vec![2, 2, 2]);
+        assert_eq!(session.receipted(), vec![0]);
+        assert_eq!(session.get_error(1), Some(&Error::BadSignature(1)));
+
+        assert_eq!(session.await_signatures(&board).unwrap(), vec![1, 2]);
+        session.reveal(&mut board).unwrap();
+        assert_eq!(session.revealed(), &[1, 2]);
+        // 运营者把公告板回滚到客户端刚提交时的样子，客户端同步树头时发现
+        let mut rolled_back = MerkleBoard::new(&scheme, board_key);
+        for entry in board.entries(0, 1) {
+            entry.apply(&mut rolled_back).unwrap();
+        }
+        assert!(matches!(session.finalize(&rolled_back), Err(Error::BoardEquivocation { .. })));
+        let receipt = session.finalize(&board).unwrap();
+        assert_eq!(session.get_phase(), Phase::Finalized);
+        assert!(receipt.verify(&scheme, &pks, &pp));
+        assert_eq!(receipt.receipts.len(), 1);
+        assert_eq!(board.check_all_users(0, &pks, &pp), vec![9]);
+    }
+}
@@ -1,55 +1,130 @@
 use serde::{Serialize, Deserialize};
 use std::io::{self, Result, Write, Read};
-use std::net::{TcpStream, SocketAddr};
+use std::net::{TcpListener, TcpStream, SocketAddr, ToSocketAddrs};
 use std::time::Duration;
-use bcs;
+use crate::protocol::{self, Envelope, Message, Role};
 
 // 定义 Communicator trait
 pub trait Communicator {
     fn send<T: Serialize>(&mut self, message: &T) -> Result<()>;
     fn receive<T: for<'de> Deserialize<'de>>(&mut self) -> Result<T>;
+    /// The undecoded payload of the next frame.
+    fn receive_frame(&mut self) -> Result<Vec<u8>>;
 
     fn send_ack(&mut self) -> Result<()>;
     fn receive_ack(&mut self) -> Result<()>;
+
+    /// Sends `message` in an envelope of the current protocol version.
+    fn send_message(&mut self, session_id: u64, sender: Role, message: Message) -> Result<()> {
+        self.send(&Envelope::new(session_id, sender, message))
+    }
+
+    /// Receives an envelope, rejecting other protocol versions and sessions.
+    fn receive_message(&mut self, session_id: u64) -> Result<(Role, Message)> {
+        protocol::decode(&self.receive_frame()?)?.open(session_id)
+    }
+}
+
+/// Length-prefixed BCS framing over any blocking byte stream.
+pub struct SyncStreamCommunicator<S> {
+    stream: S,
 }
 
 // Tcp 通信实现
-pub struct SyncTcpCommunicator {
-    stream: TcpStream,
+pub type SyncTcpCommunicator = SyncStreamCommunicator<TcpStream>;
+
+impl<S> SyncStreamCommunicator<S> {
+    pub fn new(stream: S) -> Self {
+        Self { stream }
+    }
+
+    pub fn get_ref(&self) -> &S {
+        &self.stream
+    }
+
+    pub fn into_inner(self) -> S {
+        self.stream
+    }
 }
 
-impl Communicator for SyncTcpCommunicator {
-    fn send<T: Serialize>(&mut self, message: &T) -> io::Result<()> {
-        let serialized = bcs::to_bytes(message).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
-        let length = serialized.len();
-        if length > MAX_MESSAGE_SIZE {
-            return Err(io::Error::new(io::ErrorKind::InvalidInput, "Message size exceeds maximum allowed limit"));
+impl SyncTcpCommunicator {
+    pub fn connect<A: ToSocketAddrs>(addr: A) -> Result<Self> {
+        Ok(Self::new(TcpStream::connect(addr)?))
+    }
+
+    pub fn connect_timeout(addr: &SocketAddr, timeout: Duration) -> Result<Self> {
+        Ok(Self::new(TcpStream::connect_timeout(addr, timeout)?))
+    }
+
+    /// Read and write timeout; `None` blocks forever.
+    pub fn set_timeout(&self, timeout: Option<Duration>) -> Result<()> {
+        self.stream.set_read_timeout(timeout)?;
+        self.stream.set_write_timeout(timeout)
+    }
+
+    pub fn peer_addr(&self) -> Result<SocketAddr> {
+        self.stream.peer_addr()
+    }
+}
+
+/// Accepts connections on `listener` one at a time and hands each to `handler`, until the
+/// handler returns `false`. An error of a single connection is dropped and does not stop the
+/// loop; a handler that wants it reported reports it itself.
+pub fn serve<F: FnMut(SyncTcpCommunicator) -> Result<bool>>(listener: &TcpListener, mut handler: F) -> Result<()> {
+    for stream in listener.incoming() {
+        let comm = SyncTcpCommunicator::new(stream?);
+        if let Ok(false) = handler(comm) {
+            break;
         }
+    }
+    Ok(())
+}
 
-        let length_bytes = (length as u32).to_be_bytes();
-        self.stream.write_all(&length_bytes)?;
-        self.stream.write_all(&serialized)?;
-        Ok(())
+// 帧格式：[长度 u32 BE][BCS 编码]，同步和异步实现共用
+pub(crate) fn encode_frame<T: Serialize>(message: &T) -> io::Result<Vec<u8>> {
+    let serialized = bcs::to_bytes(message).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    let length = serialized.len();
+    if length > MAX_MESSAGE_SIZE {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "Message size exceeds maximum allowed limit"));
+    }
+    let mut frame = (length as u32).to_be_bytes().to_vec();
+    frame.extend(serialized);
+    Ok(frame)
+}
+
+pub(crate) fn frame_length(length_bytes: [u8; 4]) -> io::Result<usize> {
+    let length = u32::from_be_bytes(length_bytes) as usize;
+    if length > MAX_MESSAGE_SIZE {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "Received message size exceeds maximum allowed limit"));
+    }
+    Ok(length)
+}
+
+pub(crate) fn decode_payload<T: for<'de> Deserialize<'de>>(payload: &[u8]) -> io::Result<T> {
+    bcs::from_bytes(payload).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+impl<S: Read + Write> Communicator for SyncStreamCommunicator<S> {
+    fn send<T: Serialize>(&mut self, message: &T) -> io::Result<()> {
+        self.stream.write_all(&encode_frame(message)?)?;
+        self.stream.flush()
     }
 
     fn receive<T: for<'de> Deserialize<'de>>(&mut self) -> io::Result<T> {
+        decode_payload(&self.receive_frame()?)
+    }
+
+    fn receive_frame(&mut self) -> io::Result<Vec<u8>> {
         let mut length_bytes = [0; 4];
         self.stream.read_exact(&mut length_bytes)?;
-        let length = u32::from_be_bytes(length_bytes) as usize;
-
-        if length > MAX_MESSAGE_SIZE {
-            return Err(io::Error::new(io::ErrorKind::InvalidData, "Received message size exceeds maximum allowed limit"));
-        }
-
-        let mut buffer = vec![0; length];
+        let mut buffer = vec![0; frame_length(length_bytes)?];
         self.stream.read_exact(&mut buffer)?;
-        let deserialized = bcs::from_bytes(&buffer).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
-        Ok(deserialized)
+        Ok(buffer)
     }
 
     fn send_ack(&mut self) -> io::Result<()> {
         self.stream.write_all(b"ACK")?;
-        Ok(())
+        self.stream.flush()
     }
 
     fn receive_ack(&mut self) -> io::Result<()> {
@@ -66,6 +141,6 @@ impl Communicator for SyncTcpCommunicator {
 }
 
 
-const MAX_MESSAGE_SIZE: usize = 16 * 1024 * 1024;
+pub const MAX_MESSAGE_SIZE: usize = 16 * 1024 * 1024;
 
 
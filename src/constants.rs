@@ -1,23 +1,8 @@
-// pub const BITS_NUM:usize = 100000;
-// pub const PROVER_NUM:usize = 5;
-// pub const THRESHOLD:usize = 2;
-// pub const SHARE_LEN:usize = 6;
-// pub const SPLIT_LEN:usize = 10;
-// pub const IND_ARR: [[usize; 6];5] = [
-//     [0, 1, 2, 3, 4, 5],//1,2,3,4,5,6
-//     [0, 1, 2, 6, 7, 8],//1,2,3,7,8,9
-//     [0, 3, 4, 6, 7, 9],//1,4,5,7,8,10
-//     [1, 3, 5, 6, 8, 9],//2,4,6,7,9,10
-//     [2, 4, 5, 7, 8, 9],//3,5,6,8,9,10
-// ];
-
 pub const BITS_NUM:usize = 100000;
+
+// 默认部署规模，其余参数由 replicated::SharingScheme 生成
 pub const PROVER_NUM:usize = 3;
 pub const THRESHOLD:usize = 1;
-pub const SHARE_LEN:usize = 2;
-pub const SPLIT_LEN:usize = 3;
-pub const IND_ARR: [[usize; 2];3] = [
-    [0, 1],//1,2
-    [0, 2],//1,3
-    [1, 2],//2,3
-];
\ No newline at end of file
+
+// prover 只对至少这么多有效用户的和加噪声并公开
+pub const MIN_USERS:usize = 10;
@@ -0,0 +1,103 @@
+// 部署配置与密钥文件：prover 守护进程、公告板和客户端从同一份配置生成公共参数
+use ed25519_dalek::{SigningKey, VerifyingKey};
+use serde::{Serialize, Deserialize};
+use std::fs;
+use std::path::Path;
+use crate::client_auth::ClientRegistry;
+use crate::error::{Error, Result};
+use crate::public_parameters::PublicParameters;
+use crate::replicated::SharingScheme;
+
+/// Everything the parties of one deployment must agree on.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Deployment {
+    pub seed: Vec<u8>,
+    pub session_id: u64,
+    pub prover_num: usize,
+    pub threshold: usize,
+    pub input_bits: usize,
+    pub buckets: usize,
+    pub noise_bits: usize,
+    pub board_addr: String,
+    pub prover_addrs: Vec<String>,
+}
+
+impl Deployment {
+    pub fn public_parameters(&self) -> PublicParameters {
+        let pp = if self.buckets > 1 {
+            PublicParameters::new_histogram(&self.seed, self.buckets)
+        } else {
+            PublicParameters::new_with_input_bits(&self.seed, self.input_bits)
+        };
+        pp.with_noise_bits(self.noise_bits)
+    }
+
+    pub fn scheme(&self) -> SharingScheme {
+        SharingScheme::new(self.prover_num, self.threshold)
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        write_file(path, self)
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let deployment: Self = read_file(path)?;
+        if deployment.prover_addrs.len() != deployment.prover_num {
+            return Err(Error::Store("one address per prover expected".to_string()));
+        }
+        Ok(deployment)
+    }
+}
+
+fn write_file<P: AsRef<Path>, T: Serialize>(path: P, value: &T) -> Result<()> {
+    let bytes = bcs::to_bytes(value).map_err(|e| Error::Store(e.to_string()))?;
+    Ok(fs::write(path, bytes)?)
+}
+
+fn read_file<P: AsRef<Path>, T: for<'de> Deserialize<'de>>(path: P) -> Result<T> {
+    bcs::from_bytes(&fs::read(path)?).map_err(|e| Error::Store(e.to_string()))
+}
+
+pub fn save_signing_key<P: AsRef<Path>>(path: P, key: &SigningKey) -> Result<()> {
+    write_file(path, &key.to_bytes())
+}
+
+pub fn load_signing_key<P: AsRef<Path>>(path: P) -> Result<SigningKey> {
+    Ok(SigningKey::from_bytes(&read_file(path)?))
+}
+
+pub fn save_verifying_key<P: AsRef<Path>>(path: P, pk: &VerifyingKey) -> Result<()> {
+    write_file(path, &pk.to_bytes())
+}
+
+pub fn load_verifying_key<P: AsRef<Path>>(path: P) -> Result<VerifyingKey> {
+    VerifyingKey::from_bytes(&read_file(path)?).map_err(|e| Error::Store(e.to_string()))
+}
+
+/// Writes the verifying keys of all provers, in prover order.
+pub fn save_verifying_keys<P: AsRef<Path>>(path: P, pks: &[VerifyingKey]) -> Result<()> {
+    let bytes: Vec<[u8; 32]> = pks.iter().map(|pk| pk.to_bytes()).collect();
+    write_file(path, &bytes)
+}
+
+pub fn load_verifying_keys<P: AsRef<Path>>(path: P) -> Result<Vec<VerifyingKey>> {
+    let bytes: Vec<[u8; 32]> = read_file(path)?;
+    bytes.iter()
+        .map(|b| VerifyingKey::from_bytes(b).map_err(|e| Error::Store(e.to_string())))
+        .collect()
+}
+
+/// Writes the registered clients and their identity keys, in id order.
+pub fn save_client_registry<P: AsRef<Path>>(path: P, registry: &ClientRegistry) -> Result<()> {
+    let bytes: Vec<(u64, [u8; 32])> = registry.clients().iter().map(|(id, pk)| (*id, pk.to_bytes())).collect();
+    write_file(path, &bytes)
+}
+
+pub fn load_client_registry<P: AsRef<Path>>(path: P) -> Result<ClientRegistry> {
+    let bytes: Vec<(u64, [u8; 32])> = read_file(path)?;
+    let mut registry = ClientRegistry::new();
+    for (id, pk) in bytes {
+        registry.register(id, VerifyingKey::from_bytes(&pk).map_err(|e| Error::Store(e.to_string()))?)?;
+    }
+    Ok(registry)
+}
@@ -0,0 +1,220 @@
+use serde::{Serialize, Deserialize};
+use std::fmt;
+use crate::sigma_or::SigmaOrError;
+use crate::public_randomness::CoinTossError;
+use crate::range_proof::RangeProofError;
+use crate::one_hot::OneHotError;
+use crate::replicated::BlameReport;
+use crate::client_session::Phase;
+use crate::protocol::Role;
+
+/// Why a client, prover or store operation was rejected. Sent back to the peer inside a
+/// `Reject`, so both ends see the same error.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Error {
+    /// No user with this id is on the bulletin board.
+    UnknownUser(u64),
+    /// A user with this id already submitted commitments.
+    DuplicateUser(u64),
+    /// The client id is not in the client registry.
+    UnregisteredClient(u64),
+    /// The board only accepts submissions signed with the client's identity key.
+    UnsignedSubmission(u64),
+    /// The submission is not signed with the client's registered identity key.
+    BadSubmissionSignature(u64),
+    /// The client id already has an identity key.
+    AlreadyRegistered(u64),
+    /// The identity key is already registered to this client id.
+    DuplicateClientKey(u64),
+    /// The prover index is outside `0..prover_num`.
+    UnknownProver(usize),
+    /// The bit proof of a client does not verify.
+    BadProof(SigmaOrError),
+    /// The range proof of a client does not verify.
+    BadRangeProof(RangeProofError),
+    /// The one-hot proof of a histogram client does not verify.
+    BadOneHotProof(OneHotError),
+    /// The kind of input proof does not match the deployment (histogram or not).
+    WrongProofKind,
+    /// A client input does not fit in the configured number of bits.
+    InputOutOfRange { value: u64, bits: usize },
+    /// A histogram client voted for a bucket outside `0..buckets`.
+    UnknownBucket { bucket: usize, buckets: usize },
+    /// A share does not open the commitments it claims to.
+    BadShareOpening,
+    /// The signature of this prover does not cover the commitments.
+    BadSignature(usize),
+    /// This prover's signature or share of the user is already on the board.
+    AlreadyPosted { id: u64, prover: usize },
+    /// The authenticated peer (`None` if it presented no key) may not send this message.
+    Forbidden { message: String, peer: Option<Role> },
+    /// A share or signature was addressed to the wrong prover.
+    WrongProverIndex { expected: usize, got: usize },
+    /// The commitment vector does not have one entry per split.
+    MalformedCommitment,
+    /// The record of this user does not have one signature and one share slot per prover.
+    BadRecord(u64),
+    /// These provers neither signed nor have a valid revealed share.
+    MissingProvers(Vec<usize>),
+    /// Neither the share store nor the bulletin board has this user's share.
+    MissingShare(u64),
+    /// The prover's noise commitments were not proven to be bits.
+    RejectedProver(usize),
+    /// The user is not valid on the audited bulletin board.
+    InvalidUser(u64),
+    /// A noisy sum was asked for fewer distinct users than the prover releases.
+    TooFewUsers { got: usize, min: usize },
+    /// The prover already released its noisy share of this session.
+    NoisyShareReleased,
+    /// The share store holds other noise for this session; noise is never drawn twice.
+    NoiseExists(u64),
+    /// The share store holds no noise for this session.
+    MissingNoise(u64),
+    /// The public random bits are not `rows` vectors of `cols` bits.
+    BadPublicRandomness { rows: usize, cols: usize },
+    /// The commit-then-reveal coin toss for the public random bits failed.
+    CoinToss(CoinTossError),
+    /// The same prover sent more than one share.
+    DuplicateShare(usize),
+    /// The valid shares do not cover these splits; `dropped` says why each other share was
+    /// rejected.
+    Unrecoverable { missing_splits: Vec<usize>, dropped: Vec<(usize, Error)> },
+    /// Checked reconstruction found copies that do not open their commitments.
+    Blame(BlameReport),
+    /// A tree head is not signed by the bulletin board.
+    BadTreeHead,
+    /// The bulletin board showed a tree head that does not extend the one seen before.
+    BoardEquivocation { old_size: u64, new_size: u64 },
+    /// The bulletin board has no tree of this size yet.
+    UnknownTreeSize(u64),
+    /// The submission of this user is not in the board's log under the accepted tree head.
+    NotLogged(u64),
+    /// Running this query would exceed the deployment's privacy budget.
+    PrivacyBudgetExceeded,
+    /// A client session step was run out of order.
+    WrongPhase { expected: Phase, got: Phase },
+    /// The commitments on the bulletin board are not the ones this client made.
+    CommitmentMismatch(u64),
+    /// A peer sent a message this party does not serve, or a reply of the wrong kind.
+    Protocol(String),
+    /// Resolving, connecting to or talking with a peer failed.
+    Connection(String),
+    /// The backing store failed (poisoned lock, I/O, encoding).
+    Store(String),
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::UnknownUser(id) => write!(f, "unknown user {}", id),
+            Error::DuplicateUser(id) => write!(f, "user {} already submitted", id),
+            Error::UnregisteredClient(id) => write!(f, "client {} is not registered", id),
+            Error::UnsignedSubmission(id) => write!(f, "submission of client {} is not signed", id),
+            Error::BadSubmissionSignature(id) => write!(f, "submission of client {} is not signed by its identity key", id),
+            Error::AlreadyRegistered(id) => write!(f, "client {} is already registered", id),
+            Error::DuplicateClientKey(id) => write!(f, "identity key is already registered to client {}", id),
+            Error::UnknownProver(ind) => write!(f, "unknown prover {}", ind),
+            Error::BadProof(e) => write!(f, "bad bit proof: {}", e),
+            Error::BadRangeProof(e) => write!(f, "bad range proof: {}", e),
+            Error::BadOneHotProof(e) => write!(f, "bad one-hot proof: {}", e),
+            Error::WrongProofKind => write!(f, "input proof does not match the query type"),
+            Error::InputOutOfRange { value, bits } => write!(f, "input {} does not fit in {} bits", value, bits),
+            Error::UnknownBucket { bucket, buckets } => write!(f, "bucket {} is not in 0..{}", bucket, buckets),
+            Error::BadShareOpening => write!(f, "share does not open the commitments"),
+            Error::BadSignature(ind) => write!(f, "bad signature from prover {}", ind),
+            Error::AlreadyPosted { id, prover } => write!(f, "prover {} already has a signature or share of user {} on the board", prover, id),
+            Error::Forbidden { message, peer: Some(peer) } => write!(f, "{} may not send {}", peer, message),
+            Error::Forbidden { message, peer: None } => write!(f, "unauthenticated peers may not send {}", message),
+            Error::WrongProverIndex { expected, got } => write!(f, "expected prover {}, got {}", expected, got),
+            Error::MalformedCommitment => write!(f, "commitment has the wrong number of splits"),
+            Error::BadRecord(id) => write!(f, "record of user {} does not match the number of provers", id),
+            Error::MissingProvers(inds) => write!(f, "provers {:?} neither signed nor received a revealed share", inds),
+            Error::MissingShare(id) => write!(f, "no share of user {}", id),
+            Error::RejectedProver(ind) => write!(f, "prover {} was rejected", ind),
+            Error::InvalidUser(id) => write!(f, "user {} is not valid on the bulletin board", id),
+            Error::TooFewUsers { got, min } => write!(f, "noisy sum of {} users, at least {} required", got, min),
+            Error::NoisyShareReleased => write!(f, "noisy share of this session was already released"),
+            Error::NoiseExists(session_id) => write!(f, "session {} already has noise", session_id),
+            Error::MissingNoise(session_id) => write!(f, "no noise recorded for session {}", session_id),
+            Error::BadPublicRandomness { rows, cols } => write!(f, "public random bits must be {} rows of {} bits", rows, cols),
+            Error::CoinToss(e) => write!(f, "coin toss failed: {}", e),
+            Error::DuplicateShare(ind) => write!(f, "prover {} sent more than one share", ind),
+            Error::Unrecoverable { missing_splits, dropped } => {
+                write!(f, "splits {:?} are not covered by any valid share", missing_splits)?;
+                for (ind, e) in dropped {
+                    write!(f, "; prover {}: {}", ind, e)?;
+                }
+                Ok(())
+            }
+            Error::Blame(report) => write!(f, "{}", report),
+            Error::BadTreeHead => write!(f, "tree head is not signed by the bulletin board"),
+            Error::BoardEquivocation { old_size, new_size } => write!(f, "bulletin board of size {} is not an extension of size {}", new_size, old_size),
+            Error::UnknownTreeSize(size) => write!(f, "bulletin board has no tree of size {}", size),
+            Error::NotLogged(id) => write!(f, "submission of user {} is not in the board's log", id),
+            Error::PrivacyBudgetExceeded => write!(f, "privacy budget exceeded"),
+            Error::WrongPhase { expected, got } => write!(f, "client session is {:?}, expected {:?}", got, expected),
+            Error::CommitmentMismatch(id) => write!(f, "bulletin board holds other commitments for user {}", id),
+            Error::Protocol(msg) => write!(f, "protocol error: {}", msg),
+            Error::Connection(msg) => write!(f, "connection failed: {}", msg),
+            Error::Store(msg) => write!(f, "store failure: {}", msg),
+        }
+    }
+}
+
+impl Error {
+    /// A failed exchange with a peer; `From<io::Error>` is for the local store.
+    pub fn connection(e: std::io::Error) -> Self {
+        Error::Connection(e.to_string())
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::BadProof(e) => Some(e),
+            Error::BadRangeProof(e) => Some(e),
+            Error::BadOneHotProof(e) => Some(e),
+            Error::CoinToss(e) => Some(e),
+            Error::Blame(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<SigmaOrError> for Error {
+    fn from(e: SigmaOrError) -> Self {
+        Error::BadProof(e)
+    }
+}
+
+impl From<OneHotError> for Error {
+    fn from(e: OneHotError) -> Self {
+        Error::BadOneHotProof(e)
+    }
+}
+
+impl From<RangeProofError> for Error {
+    fn from(e: RangeProofError) -> Self {
+        Error::BadRangeProof(e)
+    }
+}
+
+impl From<CoinTossError> for Error {
+    fn from(e: CoinTossError) -> Self {
+        Error::CoinToss(e)
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(e: std::io::Error) -> Self {
+        Error::Store(e.to_string())
+    }
+}
+
+impl<T> From<std::sync::PoisonError<T>> for Error {
+    fn from(_: std::sync::PoisonError<T>) -> Self {
+        Error::Store("lock poisoned".to_string())
+    }
+}
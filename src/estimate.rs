@@ -0,0 +1,98 @@
+// 结果后处理：去掉噪声均值并给出方差与置信区间
+// 重构时每个 split 取某个 prover 的一份拷贝，每份拷贝带 noise_bits 个 Bernoulli(1/2) 硬币，
+// 所以每个桶的结果偏移 split_len · noise_bits / 2
+use blstrs::Scalar;
+use crate::fixed_point::scalar_to_signed;
+use crate::public_parameters::PublicParameters;
+use crate::replicated::SharingScheme;
+
+/// A debiased query result `value` carrying the noise of `coins` fair coins, each weighing
+/// `weight`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Estimate {
+    pub value: f64,
+    pub coins: usize,
+    pub weight: f64,
+}
+
+impl Estimate {
+    pub fn variance(&self) -> f64 {
+        self.coins as f64 * self.weight * self.weight / 4.0
+    }
+
+    pub fn std_dev(&self) -> f64 {
+        self.variance().sqrt()
+    }
+
+    /// An interval containing the true result with probability at least `confidence`, by
+    /// Hoeffding's inequality on the noise coins.
+    pub fn confidence_interval(&self, confidence: f64) -> (f64, f64) {
+        if !(confidence > 0.0 && confidence < 1.0) {
+            panic!("Confidence must be in (0, 1)");
+        }
+        let half_width = self.weight * (self.coins as f64 * (2.0 / (1.0 - confidence)).ln() / 2.0).sqrt();
+        (self.value - half_width, self.value + half_width)
+    }
+
+    /// The estimate of `factor * result + shift`.
+    pub fn affine(&self, factor: f64, shift: f64) -> Estimate {
+        Estimate {
+            value: factor * self.value + shift,
+            coins: self.coins,
+            weight: factor.abs() * self.weight,
+        }
+    }
+}
+
+/// Expected noise in one reconstructed bucket, doubled so that it is an integer.
+fn twice_noise_mean(scheme: &SharingScheme, pp: &PublicParameters) -> i128 {
+    scheme.split_len() as i128 * pp.get_noise_bits() as i128
+}
+
+/// Subtracts the expected noise from a reconstructed result; `None` if the result is not a
+/// small signed integer.
+pub fn debias(scheme: &SharingScheme, pp: &PublicParameters, total: Scalar) -> Option<Estimate> {
+    let twice = 2 * scalar_to_signed(total)? - twice_noise_mean(scheme, pp);
+    Some(Estimate {
+        value: twice as f64 / 2.0,
+        coins: scheme.split_len() * pp.get_noise_bits(),
+        weight: 1.0,
+    })
+}
+
+/// Debiases every bucket of a histogram.
+pub fn debias_buckets(scheme: &SharingScheme, pp: &PublicParameters, totals: &[Scalar]) -> Option<Vec<Estimate>> {
+    totals.iter().map(|&total| debias(scheme, pp, total)).collect()
+}
+
+
+#[cfg(test)]
+mod tests {
+    use blstrs::Scalar;
+    use crate::fixed_point::{FixedPoint, signed_to_scalar};
+    use crate::privacy::PrivacyGuarantee;
+    use crate::replicated::SharingScheme;
+    use super::debias;
+
+    #[test]
+    fn test_debias() {
+        let scheme = SharingScheme::new(3, 1);
+        let enc = FixedPoint::new(10, 1.0);
+        let pp = enc.public_parameters(b"seed", PrivacyGuarantee::new(1.0, 1e-6)).with_noise_bits(5);
+        // 噪声均值 3 · 5 / 2 = 7.5，四个客户端的编码和 4 · 10 + 7 = 47
+        let noise = 8;
+        let est = debias(&scheme, &pp, Scalar::from(47u64 + noise)).unwrap();
+        assert_eq!(est.value, 47.0 + 0.5);
+        assert_eq!(est.variance(), 15.0 / 4.0);
+        let (lo, hi) = est.confidence_interval(0.95);
+        assert!(lo < 47.0 && 47.0 < hi);
+
+        let mean = enc.decode_estimate(&est, 4);
+        assert!((mean.value - (0.175 + 0.5 / 40.0)).abs() < 1e-9);
+        assert!((mean.std_dev() - est.std_dev() / 40.0).abs() < 1e-9);
+
+        // 噪声低于均值时结果为负
+        let est = debias(&scheme, &pp, signed_to_scalar(-3)).unwrap();
+        assert_eq!(est.value, -10.5);
+    }
+}
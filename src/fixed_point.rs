@@ -0,0 +1,160 @@
+// 实数输入的定点编码：x 截断到 [-clip, clip] 后乘以 scale 取整得到 q，
+// 客户端提交 q + bound ∈ [0, 2·bound]（bound = round(clip·scale)），用范围证明约束；
+// 有符号的聚合结果在域中以 p - |v| 表示负数
+use blstrs::Scalar;
+use crate::estimate::Estimate;
+use crate::privacy::PrivacyGuarantee;
+use crate::public_parameters::PublicParameters;
+
+/// Fixed-point encoding of real client inputs clipped to `[-clip, clip]`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FixedPoint {
+    scale: u64,
+    clip: f64,
+}
+
+impl FixedPoint {
+    /// `scale` is the number of encoding steps per unit, e.g. 1000 for three decimal places.
+    pub fn new(scale: u64, clip: f64) -> Self {
+        if scale == 0 || !clip.is_finite() || clip <= 0.0 {
+            panic!("Invalid fixed-point encoding");
+        }
+        if clip * scale as f64 >= (1u64 << 62) as f64 {
+            panic!("Fixed-point range does not fit in 63 bits");
+        }
+        Self { scale, clip }
+    }
+
+    pub fn get_scale(&self) -> u64 {
+        self.scale
+    }
+
+    pub fn get_clip(&self) -> f64 {
+        self.clip
+    }
+
+    /// The encoding of `clip`.
+    pub fn bound(&self) -> u64 {
+        (self.clip * self.scale as f64).round() as u64
+    }
+
+    /// Clips `x` and rounds it to the nearest encoding step.
+    pub fn quantise(&self, x: f64) -> i64 {
+        let x = if x.is_nan() { 0.0 } else { x.clamp(-self.clip, self.clip) };
+        (x * self.scale as f64).round() as i64
+    }
+
+    /// `x` in the field, negative values as `-|q|`.
+    pub fn encode_signed(&self, x: f64) -> Scalar {
+        signed_to_scalar(self.quantise(x) as i128)
+    }
+
+    /// The non-negative value a client commits to: `quantise(x) + bound()`.
+    pub fn encode(&self, x: f64) -> u64 {
+        (self.quantise(x) + self.bound() as i64) as u64
+    }
+
+    /// Number of bits the range proof on `encode(x)` must cover.
+    pub fn input_bits(&self) -> usize {
+        (64 - (2 * self.bound()).leading_zeros() as usize).max(1)
+    }
+
+    /// Sensitivity of the encoded sum to one client. The range proof only bounds a malicious
+    /// client by `2^input_bits() - 1`, not by `2 * bound()`, so that is what the noise must cover.
+    pub fn sensitivity(&self) -> u64 {
+        (1u64 << self.input_bits()) - 1
+    }
+
+    /// Public parameters for this encoding: range proofs over `input_bits()` and enough unit
+    /// noise coins for `target` at `sensitivity()`. Scaling the coins instead would keep the
+    /// result congruent to the true sum modulo the sensitivity.
+    pub fn public_parameters(&self, seed: &[u8], target: PrivacyGuarantee) -> PublicParameters {
+        PublicParameters::new_with_input_bits(seed, self.input_bits()).with_privacy(target)
+    }
+
+    /// Decodes the (debiased) sum of `clients` encoded inputs; `None` if it is not a small
+    /// signed integer.
+    pub fn decode_sum(&self, total: Scalar, clients: usize) -> Option<f64> {
+        let q = scalar_to_signed(total)? - clients as i128 * self.bound() as i128;
+        Some(q as f64 / self.scale as f64)
+    }
+
+    /// Decodes the (debiased) sum of `clients` encoded inputs into their mean.
+    pub fn decode_mean(&self, total: Scalar, clients: usize) -> Option<f64> {
+        if clients == 0 {
+            return None;
+        }
+        Some(self.decode_sum(total, clients)? / clients as f64)
+    }
+
+    /// Turns the debiased sum of `clients` encoded inputs into an estimate of their mean.
+    pub fn decode_estimate(&self, sum: &Estimate, clients: usize) -> Estimate {
+        let n = clients.max(1) as f64;
+        let factor = 1.0 / (self.scale as f64 * n);
+        sum.affine(factor, -(self.bound() as f64) / self.scale as f64)
+    }
+}
+
+pub fn signed_to_scalar(v: i128) -> Scalar {
+    let mut bytes = [0u8; 32];
+    bytes[..16].copy_from_slice(&v.unsigned_abs().to_le_bytes());
+    let abs = Scalar::from_bytes_le(&bytes).unwrap();
+    if v < 0 { -abs } else { abs }
+}
+
+/// Reads a field element as a signed integer, `p - v` being `-v`; `None` if neither it nor its
+/// negation fits in an `i128`.
+pub fn scalar_to_signed(s: Scalar) -> Option<i128> {
+    let small = |s: Scalar| -> Option<i128> {
+        let bytes = s.to_bytes_le();
+        if bytes[16..].iter().any(|&b| b != 0) {
+            return None;
+        }
+        i128::try_from(u128::from_le_bytes(bytes[..16].try_into().unwrap())).ok()
+    };
+    small(s).or_else(|| small(-s).map(|v| -v))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use blstrs::Scalar;
+    use crate::client::Client;
+    use crate::privacy::{required_coins, PrivacyGuarantee};
+    use crate::replicated::{recon_shares, ReplicaShare, SharingScheme};
+    use crate::user_store::{MemoryUserStore, UserStore};
+    use super::{FixedPoint, scalar_to_signed};
+
+    #[test]
+    fn test_fixed_point_mean() {
+        let enc = FixedPoint::new(100, 2.5);
+        assert_eq!(enc.bound(), 250);
+        assert_eq!(enc.input_bits(), 9);
+        assert_eq!(enc.encode(-7.0), 0);
+        assert_eq!(enc.encode(1.234), 373);
+        assert_eq!(scalar_to_signed(enc.encode_signed(-1.5)), Some(-150));
+
+        let scheme = SharingScheme::new(3, 1);
+        let target = PrivacyGuarantee::new(1.0, 1e-6);
+        let pp = enc.public_parameters(b"seed", target);
+        assert_eq!(enc.sensitivity(), 511);
+        assert_eq!(pp.get_noise_bits(), required_coins(target, 511));
+        let inputs = [1.25, -0.5, 3.0, 0.75];
+        let mut broad = MemoryUserStore::new(&scheme);
+        let clients: Vec<Client> = inputs.iter().enumerate()
+            .map(|(id, &x)| Client::new_fixed_point(0, id as u64, x, &enc, &pp, &scheme, Vec::new()).unwrap())
+            .collect();
+        for (id, client) in clients.iter().enumerate() {
+            client.send_proof_coms(&mut broad).unwrap();
+            assert_eq!(broad.get_user(id as u64).unwrap().check_proof(&scheme, 0, &pp), Ok(()));
+        }
+
+        let shares: Vec<ReplicaShare> = (1..scheme.prover_num())
+            .map(|ind| clients.iter().fold(ReplicaShare::new_zero(&scheme, ind, 1), |acc, c| acc + c.send_share(ind).1))
+            .collect();
+        let total = recon_shares(&scheme, shares).unwrap();
+        // 3.0 被截断为 2.5
+        assert_eq!(enc.decode_mean(total, inputs.len()), Some(1.0));
+        assert_eq!(enc.decode_sum(-Scalar::from(1000u64), 0), Some(-10.0));
+    }
+}
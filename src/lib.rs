@@ -2,6 +2,8 @@
 pub mod public_parameters;
 pub mod prover;
 pub mod client;
+pub mod client_session;
+pub mod client_auth;
 pub mod verifier;
 pub mod util;
 pub mod constants;
@@ -11,7 +13,35 @@ pub mod hash;
 pub mod user_store;
 pub mod replicated;
 pub mod sign;
+pub mod receipt;
 pub mod share_store;
+pub mod record_log;
+pub mod bulletin_board;
+pub mod communicator;
+pub mod async_communicator;
+pub mod tls;
+pub mod protocol;
+pub mod board_service;
+pub mod prover_service;
+pub mod deployment;
+pub mod public_randomness;
+pub mod beacon;
+pub mod error;
+pub mod range_proof;
+pub mod one_hot;
+pub mod fixed_point;
+pub mod privacy;
+pub mod estimate;
+#[cfg(test)]
+mod test_utils;
 
 pub const DST_ROBUST_DP_PUBLIC_PARAMS_GENERATION : &[u8; 41] = b"DSTofRobustDP'sPublicParametersGeneration";
 pub const DST_ROBUST_DP_SIGMA_OR_GENERATION : &[u8; 37] = b"DSTofRobustDP'sSigmaORProofGeneration";
+pub const DST_ROBUST_DP_COIN_TOSS_COMMITMENT : &[u8; 33] = b"DSTofRobustDP'sCoinTossCommitment";
+pub const DST_ROBUST_DP_RAND_BITS_EXPANSION : &[u8; 34] = b"DSTofRobustDP'sRandomBitsExpansion";
+pub const DST_ROBUST_DP_BEACON_HASH_CHAIN : &[u8; 30] = b"DSTofRobustDP'sBeaconHashChain";
+pub const DST_ROBUST_DP_TREE_HEAD : &[u8; 29] = b"DSTofRobustDP'sSignedTreeHead";
+pub const DST_ROBUST_DP_ONE_HOT_SUM_PROOF : &[u8; 29] = b"DSTofRobustDP'sOneHotSumProof";
+pub const DST_ROBUST_DP_INCLUSION_RECEIPT : &[u8; 31] = b"DSTofRobustDP'sInclusionReceipt";
+pub const DST_ROBUST_DP_CLIENT_SUBMISSION : &[u8; 31] = b"DSTofRobustDP'sClientSubmission";
+pub const DST_ROBUST_DP_VALID_USER_SET : &[u8; 27] = b"DSTofRobustDP'sValidUserSet";
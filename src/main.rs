@@ -9,19 +9,24 @@ use dp::constants;
 use dp::sign;
 use dp::share_store::MemoryShareStore;
 use dp::user_store::MemoryUserStore;
-use dp::replicated::{recon_shares,ReplicaShare};
+use dp::replicated::{ReplicaShare,SharingScheme};
+use dp::estimate::debias;
+use dp::public_randomness::{CoinToss, CoinTossParty, expand_seed};
 use std::time::Instant;
 
+const SESSION_ID: u64 = 0;
 const NUM_CLIENTS: usize = 100;
 const BAD_PROVERS: usize = 0;
 
 fn main(){
     println!("Number of clients is: {}", NUM_CLIENTS);
-    println!("Number of bits is: {}", constants::BITS_NUM);
 
     // Create public parameters
     //生成公共参数
     let pp = PublicParameters::new( b"seed");
+    println!("Number of bits is: {}", pp.get_noise_bits());
+    println!("Privacy of one query: epsilon = {:.4} at delta = 1e-5", pp.privacy(1e-5).epsilon);
+    let scheme = SharingScheme::new(constants::PROVER_NUM, constants::THRESHOLD);
 
     let mut pks=Vec::new();
     let mut sig_keys =Vec::new();
@@ -42,20 +47,23 @@ fn main(){
     let mut provers: Vec<Prover<MemoryShareStore>> = share_stores
     .iter_mut()
     .enumerate()
-    .map(|(i, store)| Prover::new(i, &pp, sig_keys[i].clone(),&pks, store))
+    .map(|(i, store)| Prover::new(i, SESSION_ID, &pp, &scheme, sig_keys[i].clone(),&pks, store).unwrap())
     .collect();
 
     let duration_prover = start_of_prover.elapsed();
     println!("Time elapsed in prover is: {:?}", duration_prover);
     
     let mut coms_v_ks = Vec::new();
+    let mut noise_proofs = Vec::new();
     for i in 0..constants::PROVER_NUM {
         coms_v_ks.push(provers[i].get_coms_v_k());
+        noise_proofs.push(provers[i].get_noise_proofs());
     }
 
-    let mut broad = MemoryUserStore::new();
+    let mut broad = MemoryUserStore::new(&scheme);
 
-    let verifier= Verifier::new(coms_v_ks, pks.clone());
+    let verifier= Verifier::new(&scheme, SESSION_ID, &pp, coms_v_ks, noise_proofs, pks.clone());
+    assert!(verifier.get_rejected_provers().is_empty());
 
 
     //客户上传数据过程
@@ -67,8 +75,8 @@ fn main(){
     let mut clients=Vec::new();
     for i in 0..NUM_CLIENTS{
         let random_bool = rand::random();
-        let client = Client::new(i as u64 ,random_bool,&pp, pks.clone().try_into().unwrap());
-        client.send_proof_coms(&mut broad);
+        let client = Client::new(SESSION_ID, i as u64 ,random_bool,&pp, &scheme, pks.clone());
+        client.send_proof_coms(&mut broad).unwrap();
         let mut tuples=Vec::new();
         for j in 0..constants::PROVER_NUM{
             let tuple: (u64, ReplicaShare) = client.send_share(j);
@@ -86,8 +94,7 @@ fn main(){
     for i in 0..NUM_CLIENTS{
         for j in 0..constants::PROVER_NUM-BAD_PROVERS{
             let tuple=client_prover_tuple[i][j].clone();
-            let res=provers[j].handle_client(tuple, &mut broad);
-            assert!(res);
+            provers[j].handle_client(tuple, &mut broad).unwrap();
             
         }
     }
@@ -95,15 +102,15 @@ fn main(){
 
     let start_of_VDDC2 = Instant::now();
     for i in 0..NUM_CLIENTS{
-        clients[i].reveal_share(&mut broad);
+        clients[i].reveal_share(&mut broad).unwrap();
     }
     let duration_client_2 = start_of_VDDC2.elapsed();
 
     let start_of_VDDP2 = Instant::now();
 
-    let user_ids = provers[0].check_all_users(&broad); // every prover's user_ids are the same
+    let user_ids = provers[0].check_all_users(&broad).unwrap(); // every prover's user_ids are the same
     for j in 1..constants::PROVER_NUM-BAD_PROVERS{
-        provers[j].check_all_users(&broad);
+        provers[j].check_all_users(&broad).unwrap();
     }
 
     let duration_VDDP2 = start_of_VDDP2.elapsed();
@@ -113,20 +120,24 @@ fn main(){
     println!("Time elapsed in VDDP is: {:?}", duration_VDDP1+duration_VDDP2);
 
 
-    //gen public random bits
-    let mut rand_bits: Vec<Vec<bool>> = Vec::new();
-    for _ in 0..constants::SPLIT_LEN {
-        let bits: Vec<bool> = (0..constants::BITS_NUM).map(|_| rand::random()).collect();
-        rand_bits.push(bits);
+    //gen public random bits: provers 0..PROVER_NUM and the verifier toss coins together
+    let parties: Vec<CoinTossParty> = (0..=constants::PROVER_NUM).map(|i| CoinTossParty::new(SESSION_ID, i)).collect();
+    let mut coin_toss = CoinToss::new(SESSION_ID, parties.len());
+    for party in &parties {
+        coin_toss.add_commitment(party.get_party(), party.commitment()).unwrap();
     }
+    for party in &parties {
+        coin_toss.add_reveal(party.get_party(), party.reveal()).unwrap();
+    }
+    let rand_bits = expand_seed(&coin_toss.finish().unwrap(), scheme.share_len() * pp.get_buckets(), pp.get_noise_bits());
 
     //验证过程
     let start_of_VDPP = Instant::now();
 
     let mut shares_with_noise: Vec<ReplicaShare> = Vec::new();
     for j in 0..constants::PROVER_NUM-BAD_PROVERS{
-        let share=provers[j].sum_share(&broad, &user_ids);
-        let share_with_noise=provers[j].add_noise_from_rand_bits(&rand_bits, share);
+        let share=provers[j].sum_share(&broad, &user_ids).unwrap();
+        let share_with_noise=provers[j].add_noise_from_rand_bits(&rand_bits, share).unwrap();
         shares_with_noise.push(share_with_noise.clone());
     }
 
@@ -135,16 +146,19 @@ fn main(){
 
     let start_of_VDPV = Instant::now();
 
-    let aggregated_com = verifier.check_all_users_and_sum_coms(&broad, &pp);
-    for j in 0..constants::PROVER_NUM-BAD_PROVERS{
-        let res=verifier.handle_prover_share(j,shares_with_noise[j].clone(),aggregated_com.clone(),&rand_bits, &pp);
-        assert!(res);
-    }
+    let aggregated_com = verifier.check_all_users_and_sum_coms(&broad, &pp).unwrap();
+    let res = verifier.aggregate(shares_with_noise, &aggregated_com, &rand_bits, &pp);
     let duration = start_of_VDPV.elapsed();
     println!("Time elapsed in VDPV is: {:?}", duration);
 
-    let res=recon_shares(shares_with_noise);
-    assert!(res.is_some());
+    match res {
+        Ok(res) => {
+            let est = debias(&scheme, &pp, res[0]).unwrap();
+            let (lo, hi) = est.confidence_interval(0.95);
+            println!("Result estimate is: {:.1} (95% interval [{:.1}, {:.1}])", est.value, lo, hi);
+        }
+        Err(e) => println!("Reconstruction impossible: {}", e),
+    }
     //println!("Result in HEX is: {}",res.unwrap().to_string());
     //println!("All tests passed!");
 }
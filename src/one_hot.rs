@@ -0,0 +1,132 @@
+// 直方图输入的 one-hot 证明：x ∈ {0,1}^d 且 Σ x_b = 1
+// 每个桶的承诺之和 C_b = g^{x_b} h^{r_b} 各自给出 OR 证明；
+// 由于 Σ x_b = 1，Π C_b / g = h^{Σ r_b}，再用 Schnorr 证明知道该指数即可
+use blstrs::{G1Projective, Scalar};
+use rand::thread_rng;
+use serde::{Serialize, Deserialize};
+use crate::commitment::CommitBase;
+use crate::sigma_or::{ProofContext, ProofStruct, SigmaOrError, create_bit_proof};
+use crate::util::{hash_to_scalar, random_scalar};
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct OneHotProof {
+    pub bit_proofs: Vec<ProofStruct>,
+    pub sum_a: G1Projective,
+    pub sum_z: Scalar,
+}
+
+/// Why a one-hot proof was rejected.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum OneHotError {
+    /// The proof covers a different number of buckets than the deployment.
+    WrongBuckets { got: usize, expected: usize },
+    /// The OR proof of this bucket does not verify.
+    Bit(usize, SigmaOrError),
+    /// The buckets do not sum to one.
+    Sum,
+}
+
+impl std::fmt::Display for OneHotError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            OneHotError::WrongBuckets { got, expected } => write!(f, "proof has {} buckets, expected {}", got, expected),
+            OneHotError::Bit(b, e) => write!(f, "bucket {}: {}", b, e),
+            OneHotError::Sum => write!(f, "buckets do not sum to one"),
+        }
+    }
+}
+
+impl std::error::Error for OneHotError {}
+
+// c = H(context, P, A)
+fn sum_challenge(ctx: &ProofContext, p: &G1Projective, a: &G1Projective) -> Scalar {
+    let mut input_to_rom: Vec<u8> = Vec::new();
+    input_to_rom.extend(ctx.as_bytes());
+    input_to_rom.extend(p.to_compressed());
+    input_to_rom.extend(a.to_compressed());
+    hash_to_scalar(&input_to_rom, crate::DST_ROBUST_DP_ONE_HOT_SUM_PROOF)
+}
+
+/// Proves that the bucket commitments `commit_base.commit(b == bucket, ct_rands[b])` open to a
+/// one-hot vector. `bucket` must be smaller than `ct_rands.len()`.
+pub fn create_one_hot_proof(commit_base: &CommitBase, bucket: usize, ct_rands: &[Scalar], ctx: &ProofContext) -> OneHotProof {
+    let mut rng = thread_rng();
+    let bit_proofs = ct_rands.iter().enumerate()
+        .map(|(b, &r)| create_bit_proof(commit_base, b == bucket, r, ctx))
+        .collect();
+
+    let r_sum: Scalar = ct_rands.iter().sum();
+    let h = commit_base.get_h();
+    let k = random_scalar(&mut rng);
+    let sum_a = h * k;
+    let c = sum_challenge(ctx, &(h * r_sum), &sum_a);
+    OneHotProof {
+        bit_proofs,
+        sum_a,
+        sum_z: k + c * r_sum,
+    }
+}
+
+impl OneHotProof {
+    pub fn buckets(&self) -> usize {
+        self.bit_proofs.len()
+    }
+
+    /// Checks the proof against the commitment of every bucket.
+    pub fn check(&self, commit_base: &CommitBase, bucket_coms: &[G1Projective], ctx: &ProofContext) -> Result<(), OneHotError> {
+        if self.buckets() != bucket_coms.len() {
+            return Err(OneHotError::WrongBuckets { got: self.buckets(), expected: bucket_coms.len() });
+        }
+        for (b, (com, proof)) in bucket_coms.iter().zip(self.bit_proofs.iter()).enumerate() {
+            proof.check(commit_base, *com, ctx).map_err(|e| OneHotError::Bit(b, e))?;
+        }
+        // h^z == A · (Π C_b / g)^c
+        let p = bucket_coms.iter().sum::<G1Projective>() - commit_base.get_g();
+        let c = sum_challenge(ctx, &p, &self.sum_a);
+        if commit_base.get_h() * self.sum_z != self.sum_a + p * c {
+            return Err(OneHotError::Sum);
+        }
+        Ok(())
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::client::Client;
+    use crate::public_parameters::PublicParameters;
+    use crate::replicated::{recon_buckets, ReplicaShare, SharingScheme};
+    use crate::user_store::{MemoryUserStore, UserStore};
+    use crate::error::Error;
+
+    #[test]
+    fn test_histogram_clients() {
+        let scheme = SharingScheme::new(3, 1);
+        let pp = PublicParameters::new_histogram(b"seed", 4);
+        assert!(matches!(Client::new_histogram(0, 9, 4, &pp, &scheme, Vec::new()), Err(Error::UnknownBucket { bucket: 4, buckets: 4 })));
+
+        let mut broad = MemoryUserStore::new(&scheme);
+        let picks = [2usize, 0, 2, 3, 2];
+        let clients: Vec<Client> = picks.iter().enumerate()
+            .map(|(id, &bucket)| Client::new_histogram(0, id as u64, bucket, &pp, &scheme, Vec::new()).unwrap())
+            .collect();
+        for (id, client) in clients.iter().enumerate() {
+            client.send_proof_coms(&mut broad).unwrap();
+            let user = broad.get_user(id as u64).unwrap();
+            assert_eq!(user.check_proof(&scheme, 0, &pp), Ok(()));
+            assert!(user.check_proof(&scheme, 1, &pp).is_err());
+        }
+
+        // 单比特部署不接受 one-hot 证明
+        let bit_pp = PublicParameters::new(b"seed");
+        assert!(broad.get_user(0).unwrap().check_proof(&scheme, 0, &bit_pp).is_err());
+
+        let shares: Vec<ReplicaShare> = (0..scheme.prover_num()).map(|ind| {
+            let sum = clients.iter().fold(ReplicaShare::new_zero(&scheme, ind, 4), |acc, c| acc + c.send_share(ind).1);
+            assert!(sum.check_com(&scheme, pp.get_commit_base(), clients.iter().skip(1).fold(clients[0].get_coms(), |acc, c| acc + c.get_coms())));
+            sum
+        }).collect();
+        let counts = recon_buckets(&scheme, shares).unwrap();
+        assert_eq!(counts, [1u64, 0, 3, 1].map(blstrs::Scalar::from).to_vec());
+    }
+}
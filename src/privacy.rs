@@ -0,0 +1,123 @@
+// 二项机制的隐私核算
+// 对敏感度为 Δ（以噪声单位计）的求和查询加 Bin(N, 1/2) 噪声，
+// 当 N >= 64 Δ² ln(2/δ) / ε² 时满足 (ε, δ)-DP（Dwork 等, "Our Data, Ourselves"，Δ = 1 的界按 Δ² 放大）。
+// 每个 split 的噪声来自其某个持有者，恶意 prover 知道自己的噪声，
+// 因此只把单个 split 的噪声计入保证，即按每个 split 的硬币数计算
+use crate::error::{Error, Result};
+
+/// An (ε, δ) differential privacy guarantee.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PrivacyGuarantee {
+    pub epsilon: f64,
+    pub delta: f64,
+}
+
+impl PrivacyGuarantee {
+    pub fn new(epsilon: f64, delta: f64) -> Self {
+        if !(epsilon > 0.0 && epsilon.is_finite() && delta > 0.0 && delta < 1.0) {
+            panic!("Invalid privacy guarantee");
+        }
+        Self { epsilon, delta }
+    }
+
+    /// Whether this guarantee is at least as strong as `other`.
+    pub fn within(&self, other: &PrivacyGuarantee) -> bool {
+        self.epsilon <= other.epsilon && self.delta <= other.delta
+    }
+}
+
+/// Number of noise coins per split needed for `target` when one client changes the sum by at
+/// most `sensitivity` noise units.
+pub fn required_coins(target: PrivacyGuarantee, sensitivity: u64) -> usize {
+    let s = sensitivity.max(1) as f64;
+    (64.0 * s * s * (2.0 / target.delta).ln() / (target.epsilon * target.epsilon)).ceil() as usize
+}
+
+/// The ε achieved at `delta` with `coins` noise coins per split.
+pub fn achieved(coins: usize, delta: f64, sensitivity: u64) -> PrivacyGuarantee {
+    let s = sensitivity.max(1) as f64;
+    let epsilon = 8.0 * s * ((2.0 / delta).ln() / coins as f64).sqrt();
+    PrivacyGuarantee { epsilon, delta }
+}
+
+/// Basic composition: the ε and δ of all queries add up.
+pub fn compose(guarantees: &[PrivacyGuarantee]) -> PrivacyGuarantee {
+    PrivacyGuarantee {
+        epsilon: guarantees.iter().map(|g| g.epsilon).sum(),
+        delta: guarantees.iter().map(|g| g.delta).sum(),
+    }
+}
+
+/// `k` repetitions of `g`: the better of basic composition and the advanced composition theorem
+/// (Dwork–Rothblum–Vadhan), which spends an extra `delta_slack`.
+pub fn compose_repeated(g: PrivacyGuarantee, k: usize, delta_slack: f64) -> PrivacyGuarantee {
+    let kf = k as f64;
+    let basic = PrivacyGuarantee { epsilon: kf * g.epsilon, delta: kf * g.delta };
+    let advanced = PrivacyGuarantee {
+        epsilon: (2.0 * kf * (1.0 / delta_slack).ln()).sqrt() * g.epsilon + kf * g.epsilon * g.epsilon.exp_m1(),
+        delta: kf * g.delta + delta_slack,
+    };
+    if advanced.epsilon < basic.epsilon { advanced } else { basic }
+}
+
+/// Tracks the privacy spent by the queries of a deployment against a total budget.
+pub struct PrivacyAccountant {
+    budget: PrivacyGuarantee,
+    queries: Vec<PrivacyGuarantee>,
+}
+
+impl PrivacyAccountant {
+    pub fn new(budget: PrivacyGuarantee) -> Self {
+        Self { budget, queries: Vec::new() }
+    }
+
+    pub fn get_budget(&self) -> PrivacyGuarantee {
+        self.budget
+    }
+
+    /// Privacy spent so far, by basic composition.
+    pub fn spent(&self) -> PrivacyGuarantee {
+        compose(&self.queries)
+    }
+
+    /// Records a query, unless it would exceed the budget.
+    pub fn spend(&mut self, g: PrivacyGuarantee) -> Result<()> {
+        let total = compose(&[self.spent(), g]);
+        if !total.within(&self.budget) {
+            return Err(Error::PrivacyBudgetExceeded);
+        }
+        self.queries.push(g);
+        Ok(())
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::constants;
+    use crate::public_parameters::PublicParameters;
+    use super::*;
+
+    #[test]
+    fn test_accountant() {
+        let target = PrivacyGuarantee::new(1.0, 1e-6);
+        let coins = required_coins(target, 1);
+        assert!(achieved(coins, 1e-6, 1).epsilon <= 1.0);
+        assert!(achieved(coins - 1, 1e-6, 1).epsilon > 1.0);
+        assert!(required_coins(target, 3) > 8 * coins);
+        assert!(achieved(constants::BITS_NUM, 1e-5, 1).epsilon < 0.1);
+
+        let pp = PublicParameters::new(b"seed").with_privacy(target);
+        assert_eq!(pp.get_noise_bits(), coins);
+        assert_eq!(PublicParameters::new_histogram(b"seed", 4).with_privacy(target).get_noise_bits(), coins);
+
+        let repeated = compose_repeated(PrivacyGuarantee::new(0.01, 1e-9), 10000, 1e-6);
+        assert!(repeated.epsilon < 100.0 && repeated.delta < 2e-5);
+
+        let mut accountant = PrivacyAccountant::new(PrivacyGuarantee::new(2.5, 1e-5));
+        accountant.spend(target).unwrap();
+        accountant.spend(target).unwrap();
+        assert!(accountant.spend(target).is_err());
+        assert_eq!(accountant.spent().epsilon, 2.0);
+    }
+}
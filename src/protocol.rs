@@ -0,0 +1,214 @@
+// 协议消息：所有参与方之间的消息都装在带版本号、会话号和发送方角色的信封里，
+// 接收方据此拒绝其它会话或不兼容版本的消息，而不必事先约定下一条消息的类型
+use blstrs::{G1Projective, Scalar};
+use serde::{Serialize, Deserialize};
+use std::fmt;
+use std::io;
+use crate::bulletin_board::{BoardEntry, Hash, ProvenEntry, SignedTreeHead};
+use crate::communicator::{decode_payload, encode_frame};
+use crate::error::Error;
+use crate::public_randomness::CoinCommitment;
+use crate::range_proof::InputProof;
+use crate::receipt::{InclusionReceipt, ValidUserSet};
+use crate::replicated::{ReplicaCommitment, ReplicaShare};
+use crate::sigma_or::ProofStruct;
+use crate::sign::MySignature;
+use crate::user_store::User;
+
+// 2: Reject 带回类型化的 Error；噪声承诺分页传送；按用户取签名和公开份额的包含证明
+pub const PROTOCOL_VERSION: u16 = 2;
+
+/// Noise commitments sent per `NoiseCommitments` reply; a commitment and its bit proof take a
+/// few hundred bytes, so a page stays well under `MAX_MESSAGE_SIZE`.
+pub const NOISE_PAGE: usize = 8192;
+
+/// Who sent a message.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Role {
+    Client(u64),
+    Prover(usize),
+    Verifier,
+    Board,
+}
+
+impl fmt::Display for Role {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Role::Client(id) => write!(f, "client {}", id),
+            Role::Prover(ind) => write!(f, "prover {}", ind),
+            Role::Verifier => write!(f, "verifier"),
+            Role::Board => write!(f, "bulletin board"),
+        }
+    }
+}
+
+#[allow(clippy::large_enum_variant)]
+#[derive(Clone, Serialize, Deserialize)]
+pub enum Message {
+    /// Client → board: commitments and input proof, signed with the client's identity key if
+    /// the board authenticates clients.
+    SubmitCommitment { id: u64, commitment: ReplicaCommitment, proof: InputProof, signature: Option<MySignature> },
+    /// Client → prover: the prover's share; answered with `Receipt`.
+    SendShare { id: u64, share: ReplicaShare },
+    /// Prover → board: the prover checked the share of client `id`.
+    ShareSignature { id: u64, prover: usize, sig: MySignature },
+    /// Prover → client: the prover stored the client's share.
+    Receipt(InclusionReceipt),
+    /// Client → board: the share of a prover that did not sign.
+    RevealShare { id: u64, prover: usize, share: ReplicaShare },
+    /// Verifier → prover: noise commitments `from..to` of row `row` and their bit proofs;
+    /// answered with at most `NOISE_PAGE` of them in `NoiseCommitments`.
+    RequestNoiseCommitments { row: usize, from: usize, to: usize },
+    NoiseCommitments { row: usize, from: usize, coms: Vec<G1Projective>, proofs: Vec<ProofStruct> },
+    /// Verifier → prover; answered with `ValidUsers`.
+    RequestValidUsers,
+    ValidUsers(Vec<u64>),
+    /// Verifier → prover: the noisy sum of `user_ids`, flipped by the session's coin toss;
+    /// answered with `NoisyShare` once per session.
+    RequestNoisyShare { user_ids: Vec<u64> },
+    NoisyShare(ReplicaShare),
+    /// Verifier → prover; answered with the prover's `RandomnessCommit`.
+    RequestRandomnessCommit,
+    /// Verifier → prover: the commitment of another party of the coin toss, or the reply to
+    /// `RequestRandomnessCommit`.
+    RandomnessCommit { party: usize, commitment: CoinCommitment },
+    /// Verifier → prover once every commitment is in; answered with the prover's
+    /// `RandomnessReveal`.
+    RequestRandomnessReveal,
+    RandomnessReveal { party: usize, contribution: [u8; 32] },
+    /// Verifier → anyone: the reconstructed result of every bucket.
+    Result(Vec<Scalar>),
+    /// Verifier → anyone: the users counted in the result.
+    ValidUserSet(ValidUserSet),
+    /// Reads from the board; answered with `User`, `Commitment` and `Users`.
+    RequestUser(u64),
+    User(Option<User>),
+    RequestCommitment(u64),
+    Commitment(Option<(ReplicaCommitment, InputProof)>),
+    RequestAllUsers,
+    Users(Vec<User>),
+    /// Audits of the board's log; answered with `TreeHead`, `Consistency`, `Entries`,
+    /// `UserEntry` and `UserPosts`.
+    RequestTreeHead,
+    TreeHead(SignedTreeHead),
+    RequestConsistency { old_size: u64, new_size: u64 },
+    Consistency(Vec<Hash>),
+    RequestEntries { from: u64, to: u64 },
+    Entries(Vec<BoardEntry>),
+    RequestUserEntry { id: u64, size: u64 },
+    UserEntry { index: u64, entry: BoardEntry, proof: Vec<Hash> },
+    RequestUserPosts { id: u64, size: u64 },
+    UserPosts(Vec<ProvenEntry>),
+    Shutdown,
+    Ack,
+    Reject(Error),
+}
+
+impl Message {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Message::SubmitCommitment { .. } => "SubmitCommitment",
+            Message::SendShare { .. } => "SendShare",
+            Message::ShareSignature { .. } => "ShareSignature",
+            Message::Receipt(_) => "Receipt",
+            Message::RevealShare { .. } => "RevealShare",
+            Message::RequestNoiseCommitments { .. } => "RequestNoiseCommitments",
+            Message::NoiseCommitments { .. } => "NoiseCommitments",
+            Message::RequestValidUsers => "RequestValidUsers",
+            Message::ValidUsers(_) => "ValidUsers",
+            Message::RequestNoisyShare { .. } => "RequestNoisyShare",
+            Message::NoisyShare(_) => "NoisyShare",
+            Message::RequestRandomnessCommit => "RequestRandomnessCommit",
+            Message::RandomnessCommit { .. } => "RandomnessCommit",
+            Message::RequestRandomnessReveal => "RequestRandomnessReveal",
+            Message::RandomnessReveal { .. } => "RandomnessReveal",
+            Message::Result(_) => "Result",
+            Message::ValidUserSet(_) => "ValidUserSet",
+            Message::RequestUser(_) => "RequestUser",
+            Message::User(_) => "User",
+            Message::RequestCommitment(_) => "RequestCommitment",
+            Message::Commitment(_) => "Commitment",
+            Message::RequestAllUsers => "RequestAllUsers",
+            Message::Users(_) => "Users",
+            Message::RequestTreeHead => "RequestTreeHead",
+            Message::TreeHead(_) => "TreeHead",
+            Message::RequestConsistency { .. } => "RequestConsistency",
+            Message::Consistency(_) => "Consistency",
+            Message::RequestEntries { .. } => "RequestEntries",
+            Message::Entries(_) => "Entries",
+            Message::RequestUserEntry { .. } => "RequestUserEntry",
+            Message::UserEntry { .. } => "UserEntry",
+            Message::RequestUserPosts { .. } => "RequestUserPosts",
+            Message::UserPosts(_) => "UserPosts",
+            Message::Shutdown => "Shutdown",
+            Message::Ack => "Ack",
+            Message::Reject(_) => "Reject",
+        }
+    }
+
+    /// Error for a reply of the wrong kind; a `Reject` gives back the peer's error.
+    pub fn unexpected(&self) -> Error {
+        match self {
+            Message::Reject(e) => e.clone(),
+            other => Error::Protocol(format!("unexpected message {}", other.name())),
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Envelope {
+    pub version: u16, // 必须是第一个字段，解码时先检查
+    pub session_id: u64,
+    pub sender: Role,
+    pub message: Message,
+}
+
+impl Envelope {
+    pub fn new(session_id: u64, sender: Role, message: Message) -> Self {
+        Self { version: PROTOCOL_VERSION, session_id, sender, message }
+    }
+
+    /// The sender and message, if the envelope belongs to `session_id`.
+    pub fn open(self, session_id: u64) -> io::Result<(Role, Message)> {
+        if self.session_id != session_id {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, format!("Message of session {} in session {}", self.session_id, session_id)));
+        }
+        Ok((self.sender, self.message))
+    }
+}
+
+/// A length-prefixed frame carrying `envelope`.
+pub fn encode(envelope: &Envelope) -> io::Result<Vec<u8>> {
+    encode_frame(envelope)
+}
+
+/// Decodes the payload of a frame, rejecting other protocol versions before parsing the rest.
+pub fn decode(payload: &[u8]) -> io::Result<Envelope> {
+    let version = payload.get(..2).map(|v| u16::from_le_bytes([v[0], v[1]]))
+        .ok_or_else(|| io::Error::new(io::ErrorKind::UnexpectedEof, "Truncated envelope"))?;
+    if version != PROTOCOL_VERSION {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, format!("Unsupported protocol version {}", version)));
+    }
+    decode_payload(payload)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_envelope() {
+        let env = Envelope::new(5, Role::Prover(2), Message::ValidUsers(vec![1, 3]));
+        let frame = encode(&env).unwrap();
+        let (sender, message) = decode(&frame[4..]).unwrap().open(5).unwrap();
+        assert_eq!(sender, Role::Prover(2));
+        assert!(matches!(message, Message::ValidUsers(ids) if ids == vec![1, 3]));
+        assert!(decode(&frame[4..]).unwrap().open(6).is_err());
+
+        let mut old = frame[4..].to_vec();
+        old[0] = 0;
+        assert!(decode(&old).err().unwrap().to_string().contains("version"));
+        assert!(decode(&[1]).is_err());
+    }
+}
@@ -2,22 +2,30 @@
 use blstrs::{G1Projective, Scalar};
 use ff::Field;
 use ed25519_dalek::{SigningKey, VerifyingKey};
-use rand::Rng;
-use crate::{constants, util};
+use rand::{Rng, RngCore, SeedableRng};
+use rand::rngs::StdRng;
 use crate::commitment::Commit;
 use crate::public_parameters::PublicParameters;
-use crate::sign::sign_verified_deal;
-use crate::share_store::ShareStore;
-use crate::replicated::ReplicaShare;
+use crate::public_randomness::check_shape;
+use crate::receipt::InclusionReceipt;
+use crate::sign::{sign_verified_deal, MySignature};
+use crate::sigma_or::{ProofContext, ProofStruct, create_bit_proof};
+use crate::share_store::{SessionNoise, ShareStore};
+use crate::range_proof::InputProof;
+use crate::replicated::{ReplicaCommitment, ReplicaShare, SharingScheme};
 use crate::util::random_scalars;
 use crate::user_store::UserStore;
+use crate::error::{Error, Result};
 
 pub struct Prover<'a, D:ShareStore> {
     pp: PublicParameters,
+    scheme: SharingScheme,
+    session_id: u64,
     index: usize,
-    bit_vector: Vec<Vec<Scalar>>, //随机比特向量
+    bit_vector: Vec<Vec<Scalar>>, //随机比特向量，每个桶的每个份额位置一行
     s_blinding: Vec<Vec<Scalar>>,
     coms_v_k: Vec<Vec<G1Projective>>,
+    noise_proofs: Vec<Vec<ProofStruct>>, // coms_v_k 中每个承诺是比特的证明
     sig_key: SigningKey,
     pks: Vec<VerifyingKey>,
     share_store: &'a mut D, //the database of shares from clients
@@ -25,126 +33,202 @@ pub struct Prover<'a, D:ShareStore> {
 }
 
 impl <'a, D:ShareStore> Prover<'a, D> {
-    pub fn new(index:usize, pp:&PublicParameters, sig_key:SigningKey,pks:&Vec<VerifyingKey>, share_store: &'a mut D) -> Self {
-        let mut rng = rand::thread_rng();
+    /// The noise of the session comes from a seed kept in `share_store`: a restarted prover
+    /// rebuilds the same noise, and fresh noise is drawn only for a session the store has not
+    /// seen.
+    pub fn new(index:usize, session_id:u64, pp:&PublicParameters, scheme:&SharingScheme, sig_key:SigningKey,pks:&[VerifyingKey], share_store: &'a mut D) -> Result<Self> {
+        let seed = match share_store.get_noise(session_id) {
+            Some(noise) => noise.seed,
+            None => {
+                let mut seed = [0u8; 32];
+                rand::thread_rng().fill_bytes(&mut seed);
+                share_store.put_noise(session_id, SessionNoise { seed, released: false })?;
+                seed
+            }
+        };
+        // 比特和盲化因子由种子确定，承诺因此也确定；证明用新的随机数，重建后仍然有效
+        let mut rng = StdRng::from_seed(seed);
         let mut s_blinding=Vec::new();
         let mut bit_vector = Vec::new();
+        let rows = scheme.share_len() * pp.get_buckets();
+        let noise_bits = pp.get_noise_bits(); // 由隐私核算决定
 
 
-        for _ in 0..constants::SHARE_LEN {
-            s_blinding.push(random_scalars(constants::BITS_NUM, &mut rng));
+        for _ in 0..rows {
+            s_blinding.push(random_scalars(noise_bits, &mut rng));
             bit_vector.push(Vec::new());
         }
 
-        for i in 0..constants::SHARE_LEN {
-            for _ in 0..constants::BITS_NUM {
+        for bits in bit_vector.iter_mut() {
+            for _ in 0..noise_bits {
                 if rng.gen_bool(0.5) {
-                    bit_vector[i].push(Scalar::one());
+                    bits.push(Scalar::one());
                 } else {
-                    bit_vector[i].push(Scalar::zero());
+                    bits.push(Scalar::zero());
                 }
             }
         }
 
         let mut coms_v_k = Vec::new();
-        for i in 0..constants::SHARE_LEN {
+        for i in 0..rows {
             coms_v_k.push(Vec::new());
-            for j in 0..constants::BITS_NUM {
+            for j in 0..noise_bits {
                 coms_v_k[i].push(pp.get_commit_base().commit(bit_vector[i][j], s_blinding[i][j]));
             }
         }
 
+        let ctx = ProofContext::for_noise(session_id, index, pp);
+        let noise_proofs = bit_vector.iter().zip(s_blinding.iter()).map(|(bits, blindings)| {
+            bits.iter().zip(blindings.iter())
+                .map(|(&bit, &blinding)| create_bit_proof(pp.get_commit_base(), bit == Scalar::one(), blinding, &ctx))
+                .collect()
+        }).collect();
+
 
-        Self {
+        Ok(Self {
             pp: pp.clone(),
+            scheme: scheme.clone(),
+            session_id,
             index,
             bit_vector,
             s_blinding,
             coms_v_k,
+            noise_proofs,
             sig_key,
-            pks:pks.clone(),
+            pks:pks.to_vec(),
             share_store,
-        }
+        })
 
     }
     
+    pub fn get_index(&self) -> usize {
+        self.index
+    }
+
+    pub fn get_session_id(&self) -> u64 {
+        self.session_id
+    }
+
+    pub fn get_scheme(&self) -> &SharingScheme {
+        &self.scheme
+    }
+
+    /// Whether the noisy share of this session was released, possibly before a restart.
+    pub fn is_released(&self) -> bool {
+        self.share_store.get_noise(self.session_id).is_some_and(|noise| noise.released)
+    }
+
+    /// Records that the noisy share of this session is released; call it before sending the
+    /// share.
+    pub fn mark_released(&mut self) -> Result<()> {
+        let noise = self.share_store.get_noise(self.session_id).ok_or(Error::MissingNoise(self.session_id))?;
+        self.share_store.put_noise(self.session_id, SessionNoise { released: true, ..noise })
+    }
+
     pub fn get_coms_v_k(&self) -> Vec<Vec<G1Projective>> {
         self.coms_v_k.clone()
     }
+
+    /// Noise commitments `from..to` of row `row` (cut at the row's end) and their bit proofs.
+    pub fn noise_commitments(&self, row: usize, from: usize, to: usize) -> Option<(Vec<G1Projective>, Vec<ProofStruct>)> {
+        let coms = self.coms_v_k.get(row)?;
+        let to = to.min(coms.len());
+        if from >= to {
+            return None;
+        }
+        Some((coms[from..to].to_vec(), self.noise_proofs[row][from..to].to_vec()))
+    }
+
+    /// OR proofs that every commitment in `get_coms_v_k()` opens to 0 or 1.
+    pub fn get_noise_proofs(&self) -> Vec<Vec<ProofStruct>> {
+        self.noise_proofs.clone()
+    }
     
-    pub fn handle_client<'b, B :UserStore>(&mut self,client:(u64, ReplicaShare), broad: &'b mut B) -> bool {
+    /// Checks and stores a client's share, then signs the client's commitments on the board.
+    /// Returns the client's receipt for the stored share.
+    pub fn handle_client<B :UserStore>(&mut self,client:(u64, ReplicaShare), broad: &mut B) -> Result<InclusionReceipt> {
         let (id, replica_share): (u64, ReplicaShare) = client;
-        let (coms, proof) = broad.get_user_commitment_proof(id).unwrap();
-        let recon=coms.get_sum();
-        if !proof.verify(self.pp.get_commit_base(), recon) {
-            return false;
+        let (coms, proof) = broad.get_user_commitment_proof(id).ok_or(Error::UnknownUser(id))?;
+        let (sig, receipt) = self.accept_share(id, replica_share, coms, &proof)?;
+        broad.sig_to_user(id, sig, self.index)?;
+        Ok(receipt)
+    }
+
+    /// The local half of `handle_client`: checks the share of `id` against the commitments and
+    /// input proof read from the board and stores it. Returns the signature to post on the
+    /// board and the client's receipt, to be handed out once the signature is posted.
+    pub fn accept_share(&mut self, id: u64, replica_share: ReplicaShare, coms: ReplicaCommitment, proof: &InputProof) -> Result<(MySignature, InclusionReceipt)> {
+        if coms.len() != self.scheme.split_len() * self.pp.get_buckets() {
+            return Err(Error::MalformedCommitment);
+        }
+        let ctx = ProofContext::new(self.session_id, id, &self.pp, &coms);
+        proof.check(&self.pp, &coms.bucket_sums(&self.scheme), &ctx)?;
+        if replica_share.get_ind() != self.index {
+            return Err(Error::WrongProverIndex { expected: self.index, got: replica_share.get_ind() });
         }
-        if !replica_share.check_com(self.pp.get_commit_base(), coms.clone()){
-            return false;
+        if !replica_share.check_com(&self.scheme, self.pp.get_commit_base(), coms.clone()){
+            return Err(Error::BadShareOpening);
         }
-        self.share_store.put(id, replica_share);
-        broad.sig_to_user(id, sign_verified_deal(&self.sig_key, &coms).into(), self.index)
+        self.share_store.put(id, replica_share)?;
+        let sig: MySignature = sign_verified_deal(&self.sig_key, &coms).into();
+        Ok((sig, InclusionReceipt::issue(&self.sig_key, self.session_id, id, &coms, self.index)))
     }
     
 
-    pub fn check_all_users_and_sum_share<B:UserStore>(&self, broad:&B) -> ReplicaShare {
-        let mut valid_user_ids=Vec::new();
-        let mut sum_share = ReplicaShare::new_zero(self.index);
-        let mut all_users = broad.iter_all_users().unwrap();
-        while let Some(user) = all_users.next() {
-            if user.check_whole(&self.pks, &self.pp) {
-                valid_user_ids.push(user.id);
-                match self.share_store.get(user.id) {
-                    Some(share) => {
-                        sum_share = sum_share + share;
-                        
-                    }
-                    None => {
-                        sum_share=sum_share+user.share[self.index].clone().unwrap();
-                    }
-                }
+    pub fn check_all_users_and_sum_share<B:UserStore>(&self, broad:&B) -> Result<ReplicaShare> {
+        let mut sum_share = ReplicaShare::new_zero(&self.scheme, self.index, self.pp.get_buckets());
+        for user in broad.iter_all_users()? {
+            if user.check_whole(&self.scheme, self.session_id, &self.pks, &self.pp) {
+                let share = match self.share_store.get(user.id) {
+                    Some(share) => share,
+                    None => user.share.get(self.index).cloned().flatten().ok_or(Error::MissingShare(user.id))?,
+                };
+                sum_share = sum_share + share;
             }
         }
-        sum_share
+        Ok(sum_share)
 
     }
 
-    pub fn check_all_users<B:UserStore>(&mut self, broad:&B) -> Vec<u64> {
+    pub fn check_all_users<B:UserStore>(&mut self, broad:&B) -> Result<Vec<u64>> {
         let mut valid_user_ids=Vec::new();
-        let mut all_users = broad.iter_all_users().unwrap();
-        while let Some(user) = all_users.next() {
-            let (res, share) = user.check_whole_lazy(&self.pks, &self.pp, self.index);
+        for user in broad.iter_all_users()? {
+            let (res, share) = user.check_whole_lazy(&self.scheme, self.session_id, &self.pks, &self.pp, self.index);
             if res {
                 valid_user_ids.push(user.id);
             }
             if let Some(share) = share {
-                self.share_store.put(user.id, share);
+                self.share_store.put(user.id, share)?;
             }
         }
-        valid_user_ids
+        Ok(valid_user_ids)
     }
 
-    pub fn sum_share<B:UserStore>(&self, broad:&B, valid_user_ids:&Vec<u64>) -> ReplicaShare {
-        let mut sum_share = ReplicaShare::new_zero(self.index);
-        for id in valid_user_ids {
-            match self.share_store.get(*id) {
-                Some(share) => {
-                    sum_share = sum_share + share;
-                }
+    pub fn sum_share<B:UserStore>(&self, broad:&B, valid_user_ids:&[u64]) -> Result<ReplicaShare> {
+        let mut sum_share = ReplicaShare::new_zero(&self.scheme, self.index, self.pp.get_buckets());
+        for &id in valid_user_ids {
+            let share = match self.share_store.get(id) {
+                Some(share) => share,
                 None => {
-                    let user = broad.get_user(*id).unwrap();
-                    sum_share = sum_share + user.share[self.index].clone().unwrap();
+                    let user = broad.get_user(id).ok_or(Error::UnknownUser(id))?;
+                    user.share.get(self.index).cloned().flatten().ok_or(Error::MissingShare(id))?
                 }
-            }
+            };
+            sum_share = sum_share + share;
         }
-        sum_share
+        Ok(sum_share)
     }
 
-    pub fn add_noise_from_rand_bits(&self, pub_rand_bits:&Vec<Vec<bool>>,share:ReplicaShare) -> ReplicaShare {
+    /// Adds this prover's noise, flipped by the public random bits, to `share`. The bits must be
+    /// `share_len * buckets` rows of `noise_bits` bits.
+    pub fn add_noise_from_rand_bits(&self, pub_rand_bits:&[Vec<bool>],share:ReplicaShare) -> Result<ReplicaShare> {
         let mut bit_vector_xor = self.bit_vector.clone();
         let mut s_blinding_xor = self.s_blinding.clone();
-        for i in 0..constants::SHARE_LEN {
-            for j in 0..constants::BITS_NUM {
+        let rows = self.bit_vector.len();
+        let noise_bits = self.pp.get_noise_bits();
+        check_shape(pub_rand_bits, self.scheme.share_len() * self.pp.get_buckets(), noise_bits)?;
+        for i in 0..rows {
+            for j in 0..noise_bits {
                 if pub_rand_bits[i][j] {
                     bit_vector_xor[i][j] = Scalar::one() - bit_vector_xor[i][j];
                     s_blinding_xor[i][j] = Scalar::one() - s_blinding_xor[i][j];
@@ -154,19 +238,73 @@ impl <'a, D:ShareStore> Prover<'a, D> {
 
         let mut noise = Vec::new();
         let mut noise_proof = Vec::new();
-        for _ in 0..constants::SHARE_LEN {
+        for _ in 0..rows {
             noise.push(Scalar::zero());
             noise_proof.push(Scalar::zero());
         }
-        for i in 0..constants::SHARE_LEN {
-            for j in 0..constants::BITS_NUM {
+        for i in 0..rows {
+            for j in 0..noise_bits {
                 noise[i] += bit_vector_xor[i][j];
                 noise_proof[i] += s_blinding_xor[i][j];
             }
         }
-        share.add_noise(noise, noise_proof)
+        Ok(share.add_noise(noise, noise_proof))
     }
 
 
 
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::client::Client;
+    use crate::error::Error;
+    use crate::public_parameters::PublicParameters;
+    use crate::public_randomness::expand_seed;
+    use crate::share_store::MemoryShareStore;
+    use crate::test_utils::{setup_with, Setup};
+    use crate::user_store::MemoryUserStore;
+    use crate::verifier::Verifier;
+    use super::Prover;
+
+    #[test]
+    fn test_histogram_noise() {
+        let Setup { scheme, pp, sig_keys, pks, mut provers } = setup_with(3, 1, PublicParameters::new_histogram(b"seed", 4).with_noise_bits(4));
+        let verifier = Verifier::new(&scheme, 0, &pp,
+            provers.iter().map(|p| p.get_coms_v_k()).collect(),
+            provers.iter().map(|p| p.get_noise_proofs()).collect(), pks.clone());
+
+        let mut broad = MemoryUserStore::new(&scheme);
+        let client = Client::new_histogram(0, 1, 2, &pp, &scheme, pks.clone()).unwrap();
+        client.send_proof_coms(&mut broad).unwrap();
+        for (i, prover) in provers.iter_mut().enumerate() {
+            prover.handle_client(client.send_share(i), &mut broad).unwrap();
+        }
+
+        // 每个桶的每个份额位置一行，只扩展 split_len 行是不够的
+        let sum = provers[0].sum_share(&broad, &[1]).unwrap();
+        let short = expand_seed(&[3; 32], scheme.split_len(), pp.get_noise_bits());
+        assert!(matches!(provers[0].add_noise_from_rand_bits(&short, sum),
+            Err(Error::BadPublicRandomness { rows: 8, cols: 4 })));
+
+        let rand_bits = expand_seed(&[3; 32], scheme.share_len() * pp.get_buckets(), pp.get_noise_bits());
+        let shares = provers.iter()
+            .map(|p| p.add_noise_from_rand_bits(&rand_bits, p.sum_share(&broad, &[1]).unwrap()).unwrap())
+            .collect();
+        let com = verifier.check_all_users_and_sum_coms(&broad, &pp).unwrap();
+        assert_eq!(verifier.aggregate(shares, &com, &rand_bits, &pp).unwrap().len(), 4);
+
+        // 重启的 prover 从份额库里的种子重建同样的噪声，公开过的状态也保留下来
+        let mut store = MemoryShareStore::new();
+        let mut prover = Prover::new(0, 0, &pp, &scheme, sig_keys[0].clone(), &pks, &mut store).unwrap();
+        let coms = prover.get_coms_v_k();
+        prover.mark_released().unwrap();
+        drop(prover);
+        let restarted = Prover::new(0, 0, &pp, &scheme, sig_keys[0].clone(), &pks, &mut store).unwrap();
+        assert_eq!(restarted.get_coms_v_k(), coms);
+        assert!(restarted.is_released());
+        drop(restarted);
+        let other = Prover::new(0, 1, &pp, &scheme, sig_keys[0].clone(), &pks, &mut store).unwrap();
+        assert!(other.get_coms_v_k() != coms && !other.is_released());
+    }
+}
@@ -0,0 +1,420 @@
+// prover 守护进程的请求处理：客户端上传份额，verifier 驱动聚合阶段。
+// 聚合阶段只在审计过的公告板日志上计算：先用一致性证明同步树头，再重放树头下的全部记录。
+// 聚合阶段的请求只接受认证为 verifier 的连接；噪声所用的随机比特来自本会话的掷硬币，
+// prover 自己参与并检查每个公开值，带噪声的份额每个会话只公开一次
+use blstrs::G1Projective;
+use ed25519_dalek::VerifyingKey;
+use rustls::ClientConfig;
+use std::collections::BTreeSet;
+use std::io;
+use std::net::ToSocketAddrs;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use crate::async_communicator::AsyncCommunicator;
+use crate::bulletin_board::{BoardLog, BoardMonitor};
+use crate::communicator::Communicator;
+use crate::constants::MIN_USERS;
+use crate::error::{Error, Result};
+use crate::prover::Prover;
+use crate::protocol::{Message, Role, NOISE_PAGE};
+use crate::public_randomness::{expand_seed, CoinToss, CoinTossParty};
+use crate::receipt::InclusionReceipt;
+use crate::replicated::{ReplicaShare, SharingScheme};
+use crate::share_store::ShareStore;
+use crate::sigma_or::ProofStruct;
+use crate::tls::connect_sync;
+use crate::user_store::{MemoryUserStore, UserStore};
+
+// prover 自己的状态，只在读写本地数据时锁住
+struct ProverState<'a, D: ShareStore> {
+    prover: Prover<'a, D>,
+    coin: CoinTossParty,
+    toss: CoinToss, // provers 用各自的编号，verifier 用 prover_num
+}
+
+// 公告板连接和它的审计状态，与 prover 的状态分开加锁，公告板的往返不挡住其它请求
+struct AuditedBoard<B> {
+    board: B,
+    monitor: BoardMonitor,
+}
+
+/// A prover together with the bulletin board it works against. The prover's state and the
+/// board are locked separately, so requests only wait on a board round trip of another
+/// request when they need the board themselves.
+pub struct ProverService<'a, D: ShareStore, B: UserStore + BoardLog> {
+    state: Mutex<ProverState<'a, D>>,
+    board: Mutex<AuditedBoard<B>>,
+    session_id: u64,
+    index: usize,
+    scheme: SharingScheme,
+    min_users: usize,
+}
+
+impl<'a, D: ShareStore, B: UserStore + BoardLog> ProverService<'a, D, B> {
+    /// `board_pk` is the key the board signs its tree heads with.
+    pub fn new(prover: Prover<'a, D>, board: B, board_pk: VerifyingKey) -> Self {
+        let (session_id, index, scheme) = (prover.get_session_id(), prover.get_index(), prover.get_scheme().clone());
+        let coin = CoinTossParty::new(session_id, index);
+        let mut toss = CoinToss::new(session_id, scheme.prover_num() + 1);
+        toss.add_commitment(index, coin.commitment()).expect("own commitment comes first");
+        Self {
+            state: Mutex::new(ProverState { prover, coin, toss }),
+            board: Mutex::new(AuditedBoard { board, monitor: BoardMonitor::new(board_pk) }),
+            session_id,
+            index,
+            scheme,
+            min_users: MIN_USERS,
+        }
+    }
+
+    /// Releases noisy sums of at least `min_users` users instead of `constants::MIN_USERS`.
+    pub fn with_min_users(mut self, min_users: usize) -> Self {
+        self.min_users = min_users;
+        self
+    }
+
+    pub fn get_session_id(&self) -> u64 {
+        self.session_id
+    }
+
+    pub fn role(&self) -> Role {
+        Role::Prover(self.index)
+    }
+
+    // 公告板在最新的、与之前一致的树头下的内容
+    fn audited_board(&self) -> Result<MemoryUserStore> {
+        let mut audited = self.board.lock()?;
+        let AuditedBoard { board, monitor } = &mut *audited;
+        monitor.sync(board)?;
+        monitor.audit(&self.scheme, board)
+    }
+
+    // 先向公告板读出承诺和证明，在本地检查并保存份额，再把签名贴到公告板上；收据在签名贴出后才交给客户端
+    fn accept_client(&self, id: u64, share: ReplicaShare) -> Result<InclusionReceipt> {
+        let (coms, proof) = self.board.lock()?.board.get_user_commitment_proof(id).ok_or(Error::UnknownUser(id))?;
+        let (sig, receipt) = self.state.lock()?.prover.accept_share(id, share, coms, &proof)?;
+        self.board.lock()?.board.sig_to_user(id, sig, self.index)?;
+        Ok(receipt)
+    }
+
+    // 带噪声的和：用户必须在审计过的公告板上有效、互不相同且足够多，随机比特来自掷硬币。
+    // 审计公告板时不持有 prover 的锁，之后重新检查是否已经公开过
+    fn noisy_share(&self, user_ids: Vec<u64>) -> Result<ReplicaShare> {
+        let check_unreleased = |state: &ProverState<'a, D>| match state.prover.is_released() {
+            true => Err(Error::NoisyShareReleased),
+            false => Ok(state.toss.finish()?),
+        };
+        check_unreleased(&*self.state.lock()?)?;
+        let user_ids: Vec<u64> = user_ids.into_iter().collect::<BTreeSet<_>>().into_iter().collect();
+        if user_ids.len() < self.min_users {
+            return Err(Error::TooFewUsers { got: user_ids.len(), min: self.min_users });
+        }
+        let board = self.audited_board()?;
+        let mut state = self.state.lock()?;
+        let seed = check_unreleased(&state)?;
+        let valid: BTreeSet<u64> = state.prover.check_all_users(&board)?.into_iter().collect();
+        if let Some(&id) = user_ids.iter().find(|id| !valid.contains(id)) {
+            return Err(Error::InvalidUser(id));
+        }
+        let share = state.prover.sum_share(&board, &user_ids)?;
+        let rows = state.prover.get_coms_v_k();
+        let rand_bits = expand_seed(&seed, rows.len(), rows[0].len());
+        let share = state.prover.add_noise_from_rand_bits(&rand_bits, share)?;
+        // 先落盘再公开，重启后也不会第二次公开
+        state.prover.mark_released()?;
+        Ok(share)
+    }
+
+    // peer 是 TLS 信道认证出的身份；信封里的 sender 由对端自己填写，不作为依据
+    fn try_handle(&self, peer: Option<Role>, request: Message) -> Result<Message> {
+        let verifier_only = |message: &str| match peer {
+            Some(Role::Verifier) => Ok(()),
+            _ => Err(Error::Forbidden { message: message.to_string(), peer }),
+        };
+        Ok(match request {
+            Message::SendShare { id, share } => {
+                if peer != Some(Role::Client(id)) {
+                    return Err(Error::Forbidden { message: "SendShare".to_string(), peer });
+                }
+                Message::Receipt(self.accept_client(id, share)?)
+            }
+            Message::RequestNoiseCommitments { row, from, to } => {
+                let to = to.min(from.saturating_add(NOISE_PAGE));
+                let (coms, proofs) = self.state.lock()?.prover.noise_commitments(row, from, to)
+                    .ok_or_else(|| Error::Protocol(format!("no noise commitments {}..{} in row {}", from, to, row)))?;
+                Message::NoiseCommitments { row, from, coms, proofs }
+            }
+            Message::RequestValidUsers => {
+                verifier_only("RequestValidUsers")?;
+                let board = self.audited_board()?;
+                Message::ValidUsers(self.state.lock()?.prover.check_all_users(&board)?)
+            }
+            Message::RequestRandomnessCommit => {
+                verifier_only("RequestRandomnessCommit")?;
+                let state = self.state.lock()?;
+                Message::RandomnessCommit { party: state.coin.get_party(), commitment: state.coin.commitment() }
+            }
+            Message::RandomnessCommit { party, commitment } => {
+                verifier_only("RandomnessCommit")?;
+                self.state.lock()?.toss.add_commitment(party, commitment)?;
+                Message::Ack
+            }
+            Message::RequestRandomnessReveal => {
+                verifier_only("RequestRandomnessReveal")?;
+                // 只有收齐所有承诺之后才公开自己的贡献
+                let mut state = self.state.lock()?;
+                let (party, contribution) = (state.coin.get_party(), state.coin.reveal());
+                if state.toss.missing_reveals().contains(&party) {
+                    state.toss.add_reveal(party, contribution)?;
+                }
+                Message::RandomnessReveal { party, contribution }
+            }
+            Message::RandomnessReveal { party, contribution } => {
+                verifier_only("RandomnessReveal")?;
+                self.state.lock()?.toss.add_reveal(party, contribution)?;
+                Message::Ack
+            }
+            Message::RequestNoisyShare { user_ids } => {
+                verifier_only("RequestNoisyShare")?;
+                Message::NoisyShare(self.noisy_share(user_ids)?)
+            }
+            Message::Shutdown => {
+                verifier_only("Shutdown")?;
+                Message::Ack
+            }
+            other => return Err(Error::Protocol(format!("prover does not serve {}", other.name()))),
+        })
+    }
+
+    /// The reply to `request` from `peer`, the role the peer authenticated as; failures are
+    /// reported as `Reject`.
+    pub fn handle(&self, peer: Option<Role>, request: Message) -> Message {
+        self.try_handle(peer, request).unwrap_or_else(Message::Reject)
+    }
+
+    /// Answers the requests of one connection from `peer` until the peer hangs up. Returns
+    /// `false` if the verifier asked the prover to shut down.
+    pub fn serve_connection<C: Communicator>(&self, peer: Option<Role>, comm: &mut C) -> io::Result<bool> {
+        loop {
+            let request = match comm.receive_message(self.get_session_id()) {
+                Ok((_, request)) => request,
+                Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(true),
+                Err(e) => return Err(e),
+            };
+            let shutdown = matches!(request, Message::Shutdown) && peer == Some(Role::Verifier);
+            let reply = self.handle(peer, request);
+            comm.send_message(self.get_session_id(), self.role(), reply)?;
+            if shutdown {
+                return Ok(false);
+            }
+        }
+    }
+}
+
+/// Async counterpart of `serve_connection`. Requests are handled on the blocking thread pool,
+/// so many clients can stay connected and a request waiting on the board does not hold up the
+/// others.
+pub async fn serve_connection_async<D, B, C>(service: Arc<ProverService<'static, D, B>>, peer: Option<Role>, comm: &mut C) -> io::Result<bool>
+where
+    D: ShareStore + Send + 'static,
+    B: UserStore + BoardLog + Send + 'static,
+    C: AsyncCommunicator,
+{
+    let (session_id, role) = (service.get_session_id(), service.role());
+    loop {
+        let request = match comm.receive_message(session_id).await {
+            Ok((_, request)) => request,
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(true),
+            Err(e) => return Err(e),
+        };
+        let shutdown = matches!(request, Message::Shutdown) && peer == Some(Role::Verifier);
+        let service = service.clone();
+        let reply = tokio::task::spawn_blocking(move || service.handle(peer, request))
+            .await.map_err(io::Error::other)?;
+        comm.send_message(session_id, role, reply).await?;
+        if shutdown {
+            return Ok(false);
+        }
+    }
+}
+
+/// Sends a client's share to the prover at `addr` over TLS and returns the prover's receipt.
+/// `tls` pins the prover's key and carries the client's identity. The prover also posts its
+/// signature on the board.
+pub fn submit_share<A: ToSocketAddrs>(addr: A, tls: Arc<ClientConfig>, session_id: u64, client_share: (u64, ReplicaShare), timeout: Duration) -> Result<InclusionReceipt> {
+    let addr = addr.to_socket_addrs().map_err(Error::connection)?.next()
+        .ok_or_else(|| Error::Connection("prover address does not resolve".to_string()))?;
+    let mut comm = connect_sync(&addr, tls, timeout).map_err(Error::connection)?;
+    let (id, share) = client_share;
+    comm.send_message(session_id, Role::Client(id), Message::SendShare { id, share }).map_err(Error::connection)?;
+    match comm.receive_message(session_id).map_err(Error::connection)? {
+        (_, Message::Receipt(receipt)) if receipt.client_id == id => Ok(receipt),
+        (_, other) => Err(other.unexpected()),
+    }
+}
+
+/// A prover's noise commitments and their bit proofs, row by row.
+pub type NoiseCommitments = (Vec<Vec<G1Projective>>, Vec<Vec<ProofStruct>>);
+
+/// Fetches a prover's noise commitments and their bit proofs, `rows` rows of `cols` each, one
+/// page at a time.
+pub fn fetch_noise_commitments<C: Communicator>(comm: &mut C, session_id: u64, rows: usize, cols: usize) -> Result<NoiseCommitments> {
+    let (mut all_coms, mut all_proofs) = (Vec::new(), Vec::new());
+    for row in 0..rows {
+        let (mut row_coms, mut row_proofs) = (Vec::new(), Vec::new());
+        while row_coms.len() < cols {
+            let from = row_coms.len();
+            let to = cols.min(from + NOISE_PAGE);
+            comm.send_message(session_id, Role::Verifier, Message::RequestNoiseCommitments { row, from, to }).map_err(Error::connection)?;
+            match comm.receive_message(session_id).map_err(Error::connection)?.1 {
+                Message::NoiseCommitments { row: r, from: f, coms, proofs }
+                    if r == row && f == from && !coms.is_empty() && coms.len() <= to - from && proofs.len() == coms.len() => {
+                    row_coms.extend(coms);
+                    row_proofs.extend(proofs);
+                }
+                other => return Err(other.unexpected()),
+            }
+        }
+        all_coms.push(row_coms);
+        all_proofs.push(row_proofs);
+    }
+    Ok((all_coms, all_proofs))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::net::TcpListener;
+    use std::sync::{Arc, Mutex};
+    use std::thread;
+    use crate::async_communicator::ServerConfig;
+    use crate::board_service::{serve_board, BoardContext, RemoteBoard};
+    use crate::bulletin_board::MerkleBoard;
+    use crate::client::Client;
+    use crate::client_auth::ClientRegistry;
+    use crate::communicator::serve;
+    use crate::prover::Prover;
+    use crate::public_parameters::PublicParameters;
+    use crate::public_randomness::commit_contribution;
+    use crate::replicated::SharingScheme;
+    use crate::share_store::MemoryShareStore;
+    use crate::sign;
+    use crate::tls::{accept_sync, client_config, server_config, sync_client_key, PeerDirectory};
+    use super::*;
+
+    #[test]
+    fn test_prover_service() {
+        let scheme = SharingScheme::new(3, 1);
+        let pp = PublicParameters::new(b"seed").with_noise_bits(4);
+        let keys: Vec<_> = (0..3).map(|_| sign::gen_keys()).collect();
+        let pks: Vec<_> = keys.iter().map(|k| k.1).collect();
+        let (board_key, board_pk) = sign::gen_keys();
+        let (verifier_key, verifier_pk) = sign::gen_keys();
+        let (identity, identity_pk) = sign::gen_keys();
+        let (other, other_pk) = sign::gen_keys();
+        let mut registry = ClientRegistry::new();
+        registry.register(7, identity_pk).unwrap();
+        registry.register(8, other_pk).unwrap();
+        let peers = Arc::new(PeerDirectory::new(&pks, verifier_pk, registry));
+        let timeout = Duration::from_secs(10);
+
+        let board_listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let board_addr = board_listener.local_addr().unwrap();
+        let board_tls = server_config(&board_key, peers.clone(), false).unwrap();
+        let store = Arc::new(Mutex::new(MerkleBoard::new(&scheme, board_key)));
+        let ctx = BoardContext { session_id: 0, scheme: scheme.clone(), pp: pp.clone(), peers: peers.clone() };
+        thread::spawn(move || serve_board(board_listener, store, ctx, board_tls, ServerConfig::default(), |_| {}));
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let prover_addr = listener.local_addr().unwrap();
+        let (sk, pp_, scheme_, pks_) = (keys[0].0.clone(), pp.clone(), scheme.clone(), pks.clone());
+        let tls = server_config(&sk, peers.clone(), true).unwrap();
+        let server = thread::spawn(move || {
+            let mut share_store = MemoryShareStore::new();
+            let board = RemoteBoard::connect(board_addr, client_config(board_pk, Some(&sk)).unwrap(), &scheme_, 0, Role::Prover(0)).unwrap();
+            let prover = Prover::new(0, 0, &pp_, &scheme_, sk, &pks_, &mut share_store).unwrap();
+            let service = ProverService::new(prover, board, board_pk).with_min_users(2);
+            serve(&listener, |comm| {
+                let mut comm = accept_sync(comm.into_inner(), tls.clone(), Some(timeout))?;
+                let peer = sync_client_key(&comm).and_then(|pk| peers.role_of(&pk));
+                service.serve_connection(peer, &mut comm)
+            }).unwrap();
+        });
+
+        let mut board = RemoteBoard::connect(board_addr, client_config(board_pk, Some(&identity)).unwrap(), &scheme, 0, Role::Client(7)).unwrap();
+        let client = Client::new(0, 7, true, &pp, &scheme, pks.clone());
+        client.send_proof_coms(&mut board).unwrap();
+        let to_prover = client_config(pks[0], Some(&identity)).unwrap();
+        let receipt = submit_share(prover_addr, to_prover.clone(), 0, client.send_share(0), timeout).unwrap();
+        assert!(receipt.verify(&pks[0], 0, 7, &client.get_coms()));
+        assert!(board.get_user(7).unwrap().check_signature(&pks).contains(&0));
+        // 公告板的拒绝原样带回类型化的错误
+        assert_eq!(client.send_proof_coms(&mut board), Err(Error::DuplicateUser(7)));
+        // 错误的 prover 编号、其它会话、未登记的客户端密钥、冒充的 prover 都被拒绝
+        assert!(submit_share(prover_addr, to_prover.clone(), 0, client.send_share(1), timeout).is_err());
+        assert!(submit_share(prover_addr, to_prover.clone(), 1, client.send_share(0), timeout).is_err());
+        let stranger = client_config(pks[0], Some(&sign::gen_keys().0)).unwrap();
+        assert!(submit_share(prover_addr, stranger, 0, client.send_share(0), timeout).is_err());
+        let impostor = client_config(pks[1], Some(&identity)).unwrap();
+        assert!(submit_share(prover_addr, impostor, 0, client.send_share(0), timeout).is_err());
+        // 其余 prover 不在线，客户端公开它们的份额
+        client.reveal_share(&mut board).unwrap();
+
+        // 身份取自 TLS 证书：已登记的客户端 8 在信封里冒充客户端 7 或 verifier 都不行
+        let mut comm = connect_sync(&prover_addr, client_config(pks[0], Some(&other)).unwrap(), timeout).unwrap();
+        comm.send_message(0, Role::Client(7), Message::SendShare { id: 7, share: client.send_share(0).1 }).unwrap();
+        assert!(matches!(comm.receive_message(0).unwrap().1, Message::Reject(Error::Forbidden { .. })));
+        for request in [Message::RequestNoisyShare { user_ids: vec![7] }, Message::RequestRandomnessCommit, Message::Shutdown] {
+            comm.send_message(0, Role::Verifier, request).unwrap();
+            assert!(matches!(comm.receive_message(0).unwrap().1, Message::Reject(_)));
+        }
+        drop(comm);
+        let mut board8 = RemoteBoard::connect(board_addr, client_config(board_pk, Some(&other)).unwrap(), &scheme, 0, Role::Client(8)).unwrap();
+        let client8 = Client::new(0, 8, false, &pp, &scheme, pks.clone());
+        client8.send_proof_coms(&mut board8).unwrap();
+        submit_share(prover_addr, client_config(pks[0], Some(&other)).unwrap(), 0, client8.send_share(0), timeout).unwrap();
+        client8.reveal_share(&mut board8).unwrap();
+
+        let mut comm = connect_sync(&prover_addr, client_config(pks[0], Some(&verifier_key)).unwrap(), timeout).unwrap();
+        // 噪声承诺逐页取回，每行 noise_bits 个；不存在的行被拒绝
+        let (coms, proofs) = fetch_noise_commitments(&mut comm, 0, scheme.share_len(), 4).unwrap();
+        assert_eq!((coms.len(), proofs.len()), (2, 2));
+        assert!(coms.iter().zip(&proofs).all(|(c, p)| c.len() == 4 && p.len() == 4));
+        assert!(matches!(fetch_noise_commitments(&mut comm, 0, 3, 4), Err(Error::Protocol(_))));
+        let mut call = |request| {
+            comm.send_message(0, Role::Verifier, request).unwrap();
+            comm.receive_message(0).unwrap().1
+        };
+        // 掷硬币之前没有随机比特，也不能在收齐承诺之前要求公开
+        assert!(matches!(call(Message::RequestNoisyShare { user_ids: vec![7, 8] }), Message::Reject(_)));
+        let commitment = match call(Message::RequestRandomnessCommit) {
+            Message::RandomnessCommit { party: 0, commitment } => commitment,
+            _ => panic!("expected a commitment"),
+        };
+        let parties: Vec<_> = (1..4).map(|i| CoinTossParty::new(0, i)).collect();
+        assert!(matches!(call(Message::RequestRandomnessReveal), Message::Reject(_)));
+        for p in &parties {
+            assert!(matches!(call(Message::RandomnessCommit { party: p.get_party(), commitment: p.commitment() }), Message::Ack));
+        }
+        let contribution = match call(Message::RequestRandomnessReveal) {
+            Message::RandomnessReveal { party: 0, contribution } => contribution,
+            _ => panic!("expected a reveal"),
+        };
+        assert_eq!(commit_contribution(0, 0, &contribution), commitment);
+        assert!(matches!(call(Message::RandomnessReveal { party: 1, contribution: [0; 32] }), Message::Reject(_)));
+        for p in &parties {
+            assert!(matches!(call(Message::RandomnessReveal { party: p.get_party(), contribution: p.reveal() }), Message::Ack));
+        }
+
+        // 用户太少、无效的用户都被拒绝，带噪声的份额只公开一次
+        assert!(matches!(call(Message::RequestNoisyShare { user_ids: vec![7, 7] }), Message::Reject(Error::TooFewUsers { got: 1, min: 2 })));
+        assert!(matches!(call(Message::RequestNoisyShare { user_ids: vec![7, 9] }), Message::Reject(Error::InvalidUser(9))));
+        match call(Message::RequestNoisyShare { user_ids: vec![7, 8] }) {
+            Message::NoisyShare(share) => assert_eq!(share.get_ind(), 0),
+            _ => panic!("expected a noisy share"),
+        }
+        assert!(matches!(call(Message::RequestNoisyShare { user_ids: vec![7, 8] }), Message::Reject(Error::NoisyShareReleased)));
+        assert!(matches!(call(Message::Shutdown), Message::Ack));
+        server.join().unwrap();
+    }
+}
@@ -1,19 +1,89 @@
 use crate::commitment::CommitBase;
+use crate::constants;
+use crate::privacy::{self, PrivacyGuarantee};
 use blstrs::G1Projective;
+use sha3::{Digest, Sha3_256};
 
 #[derive(Clone)]
 pub struct PublicParameters {
     commit_base: CommitBase,
-
+    input_bits: usize, // 客户端输入属于 [0, 2^input_bits)
+    buckets: usize, // 直方图的桶数，非直方图查询为 1
+    noise_bits: usize, // 每个 split 的噪声硬币数
 }
 
 impl PublicParameters {
     pub fn new(seed: &[u8]) -> Self {
+        Self::new_with_input_bits(seed, 1)
+    }
+
+    /// Parameters for client inputs in `[0, 2^input_bits)`.
+    pub fn new_with_input_bits(seed: &[u8], input_bits: usize) -> Self {
+        if input_bits == 0 || input_bits > 64 {
+            panic!("Invalid number of input bits");
+        }
+        Self {
+            commit_base: CommitBase::new(seed),
+            input_bits,
+            buckets: 1,
+            noise_bits: constants::BITS_NUM,
+        }
+    }
+
+    /// Parameters for histogram queries: every client input is a one-hot vector over `buckets`.
+    pub fn new_histogram(seed: &[u8], buckets: usize) -> Self {
+        if buckets < 2 {
+            panic!("A histogram needs at least two buckets");
+        }
         Self {
             commit_base: CommitBase::new(seed),
+            input_bits: 1,
+            buckets,
+            noise_bits: constants::BITS_NUM,
         }
     }
 
+    pub fn get_input_bits(&self) -> usize {
+        self.input_bits
+    }
+
+    pub fn get_buckets(&self) -> usize {
+        self.buckets
+    }
+
+    /// Uses `noise_bits` noise coins per split instead of `constants::BITS_NUM`.
+    pub fn with_noise_bits(mut self, noise_bits: usize) -> Self {
+        if noise_bits == 0 {
+            panic!("Number of noise bits must be positive");
+        }
+        self.noise_bits = noise_bits;
+        self
+    }
+
+    /// Sizes the noise so that each query satisfies `target`.
+    pub fn with_privacy(self, target: PrivacyGuarantee) -> Self {
+        let noise_bits = privacy::required_coins(target, self.sensitivity());
+        self.with_noise_bits(noise_bits)
+    }
+
+    pub fn get_noise_bits(&self) -> usize {
+        self.noise_bits
+    }
+
+    /// How much one client can change one bucket of the aggregate, in unit noise coins: a
+    /// histogram vote by one, a range input by at most `2^input_bits - 1`.
+    pub fn sensitivity(&self) -> u64 {
+        if self.buckets > 1 {
+            return 1;
+        }
+        u64::MAX >> (64 - self.input_bits)
+    }
+
+    /// The guarantee of one query at `delta`.
+    pub fn privacy(&self, delta: f64) -> PrivacyGuarantee {
+        privacy::achieved(self.noise_bits, delta, self.sensitivity())
+    }
+
     pub fn get_commit_base(&self) -> &CommitBase {
         &self.commit_base
     }
@@ -25,4 +95,60 @@ impl PublicParameters {
     pub fn get_h(&self) -> G1Projective {
         self.commit_base.bases[1]
     }
+
+    /// SHA3-256 digest of the commitment bases, input range, bucket count and noise, used to bind proofs to these
+    /// parameters.
+    pub fn digest(&self) -> [u8; 32] {
+        let mut hasher = Sha3_256::new();
+        hasher.update(crate::DST_ROBUST_DP_PUBLIC_PARAMS_GENERATION);
+        hasher.update(self.get_g().to_compressed());
+        hasher.update(self.get_h().to_compressed());
+        hasher.update((self.input_bits as u64).to_le_bytes());
+        hasher.update((self.buckets as u64).to_le_bytes());
+        hasher.update((self.noise_bits as u64).to_le_bytes());
+        hasher.finalize().into()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::fixed_point::{scalar_to_signed, FixedPoint};
+    use crate::privacy::{required_coins, PrivacyGuarantee};
+    use crate::prover::Prover;
+    use crate::replicated::{ReplicaShare, SharingScheme};
+    use crate::share_store::MemoryShareStore;
+    use crate::sign;
+    use super::PublicParameters;
+
+    #[test]
+    fn test_sensitivity() {
+        let target = PrivacyGuarantee::new(1.0, 1e-6);
+        let pp = PublicParameters::new_with_input_bits(b"seed", 8);
+        assert_eq!(pp.sensitivity(), 255);
+        assert_eq!(pp.with_privacy(target).get_noise_bits(), required_coins(target, 255));
+        assert_eq!(PublicParameters::new_histogram(b"seed", 4).sensitivity(), 1);
+
+        // 噪声是未加权硬币之和，不会总是敏感度的倍数：全部翻转前后两行噪声之和为奇数 5，
+        // 其中至少一个不是 0 也不是 31 的倍数
+        let enc = FixedPoint::new(10, 1.0);
+        let pp = enc.public_parameters(b"seed", target).with_noise_bits(5);
+        assert_eq!(pp.sensitivity(), 31);
+        let scheme = SharingScheme::new(3, 1);
+        let (sk, pk) = sign::gen_keys();
+        let mut store = MemoryShareStore::new();
+        let prover = Prover::new(0, 0, &pp, &scheme, sk, &[pk], &mut store).unwrap();
+        let rows = scheme.share_len() * pp.get_buckets();
+        let noise = |flip: bool| -> Vec<i128> {
+            let bits = vec![vec![flip; pp.get_noise_bits()]; rows];
+            let zero = ReplicaShare::new_zero(&scheme, 0, pp.get_buckets());
+            let share = prover.add_noise_from_rand_bits(&bits, zero).unwrap();
+            share.get_share().into_iter().map(|n| scalar_to_signed(n).unwrap()).collect()
+        };
+        let (kept, flipped) = (noise(false), noise(true));
+        for (a, b) in kept.iter().zip(flipped.iter()) {
+            assert_eq!(a + b, 5);
+            assert!(a % 31 != 0 || b % 31 != 0);
+        }
+    }
 }
@@ -0,0 +1,222 @@
+// 公共随机比特：provers 和 verifier 之间的 commit-then-reveal 掷硬币协议
+// 1. 每一方在 coms_v_k 公布之后选择随机贡献并公布其哈希承诺
+// 2. 所有承诺收齐后各方公开贡献，任何一方拒绝公开或公开内容与承诺不符都会被发现
+// 3. 所有贡献哈希为联合种子，再确定性地扩展为 (share_len * buckets) x noise_bits 的比特矩阵
+use rand::RngCore;
+use rand::rngs::OsRng;
+use serde::{Serialize, Deserialize};
+use sha3::{Digest, Sha3_256, Shake256};
+use sha3::digest::{ExtendableOutput, Update, XofReader};
+
+pub type CoinCommitment = [u8; 32];
+
+/// Why a coin toss cannot proceed or produce a seed.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CoinTossError {
+    /// The party index is outside `0..parties`.
+    UnknownParty(usize),
+    /// The party already committed or revealed.
+    Duplicate(usize),
+    /// A reveal arrived before every party committed.
+    CommitPhaseOpen,
+    /// A commitment arrived after the first reveal.
+    RevealPhaseStarted,
+    /// The revealed contribution does not open the party's commitment.
+    BadReveal(usize),
+    /// These parties did not commit or did not reveal.
+    Aborted(Vec<usize>),
+}
+
+impl std::fmt::Display for CoinTossError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CoinTossError::UnknownParty(p) => write!(f, "unknown party {}", p),
+            CoinTossError::Duplicate(p) => write!(f, "party {} already sent this message", p),
+            CoinTossError::CommitPhaseOpen => write!(f, "not every party has committed yet"),
+            CoinTossError::RevealPhaseStarted => write!(f, "commitments are closed once reveals have started"),
+            CoinTossError::BadReveal(p) => write!(f, "reveal of party {} does not match its commitment", p),
+            CoinTossError::Aborted(ps) => write!(f, "parties {:?} aborted the coin toss", ps),
+        }
+    }
+}
+
+impl std::error::Error for CoinTossError {}
+
+/// Commits a party's contribution, bound to the session and the party index.
+pub fn commit_contribution(session_id: u64, party: usize, contribution: &[u8; 32]) -> CoinCommitment {
+    let mut hasher = Sha3_256::new();
+    Digest::update(&mut hasher, crate::DST_ROBUST_DP_COIN_TOSS_COMMITMENT);
+    Digest::update(&mut hasher, session_id.to_le_bytes());
+    Digest::update(&mut hasher, (party as u64).to_le_bytes());
+    Digest::update(&mut hasher, contribution);
+    hasher.finalize().into()
+}
+
+/// One party's secret contribution to the coin toss. Provers use their index, the verifier
+/// uses `prover_num`.
+pub struct CoinTossParty {
+    session_id: u64,
+    party: usize,
+    contribution: [u8; 32],
+}
+
+impl CoinTossParty {
+    pub fn new(session_id: u64, party: usize) -> Self {
+        let mut contribution = [0u8; 32];
+        OsRng.fill_bytes(&mut contribution);
+        Self {
+            session_id,
+            party,
+            contribution,
+        }
+    }
+
+    pub fn get_party(&self) -> usize {
+        self.party
+    }
+
+    pub fn commitment(&self) -> CoinCommitment {
+        commit_contribution(self.session_id, self.party, &self.contribution)
+    }
+
+    /// Must only be sent once every party's commitment has been received.
+    pub fn reveal(&self) -> [u8; 32] {
+        self.contribution
+    }
+}
+
+/// Collects commitments and reveals of all parties and derives the joint seed.
+pub struct CoinToss {
+    session_id: u64,
+    commitments: Vec<Option<CoinCommitment>>,
+    reveals: Vec<Option<[u8; 32]>>,
+}
+
+impl CoinToss {
+    pub fn new(session_id: u64, parties: usize) -> Self {
+        Self {
+            session_id,
+            commitments: vec![None; parties],
+            reveals: vec![None; parties],
+        }
+    }
+
+    pub fn add_commitment(&mut self, party: usize, com: CoinCommitment) -> Result<(), CoinTossError> {
+        if self.reveals.iter().any(|r| r.is_some()) {
+            // 公开阶段开始后不再接受承诺，否则最后一方可以根据已公开的贡献选择自己的贡献
+            return Err(CoinTossError::RevealPhaseStarted);
+        }
+        match self.commitments.get_mut(party) {
+            None => Err(CoinTossError::UnknownParty(party)),
+            Some(Some(_)) => Err(CoinTossError::Duplicate(party)),
+            Some(slot) => {
+                *slot = Some(com);
+                Ok(())
+            }
+        }
+    }
+
+    pub fn add_reveal(&mut self, party: usize, contribution: [u8; 32]) -> Result<(), CoinTossError> {
+        if party >= self.commitments.len() {
+            return Err(CoinTossError::UnknownParty(party));
+        }
+        if !self.missing_commitments().is_empty() {
+            return Err(CoinTossError::CommitPhaseOpen);
+        }
+        if self.reveals[party].is_some() {
+            return Err(CoinTossError::Duplicate(party));
+        }
+        if self.commitments[party] != Some(commit_contribution(self.session_id, party, &contribution)) {
+            return Err(CoinTossError::BadReveal(party));
+        }
+        self.reveals[party] = Some(contribution);
+        Ok(())
+    }
+
+    pub fn missing_commitments(&self) -> Vec<usize> {
+        (0..self.commitments.len()).filter(|&i| self.commitments[i].is_none()).collect()
+    }
+
+    pub fn missing_reveals(&self) -> Vec<usize> {
+        (0..self.reveals.len()).filter(|&i| self.reveals[i].is_none()).collect()
+    }
+
+    /// Joint seed H(session, contribution_0, ..., contribution_{n-1}), or the parties that
+    /// aborted.
+    pub fn finish(&self) -> Result<[u8; 32], CoinTossError> {
+        let missing = self.missing_commitments();
+        if !missing.is_empty() {
+            return Err(CoinTossError::Aborted(missing));
+        }
+        let missing = self.missing_reveals();
+        if !missing.is_empty() {
+            return Err(CoinTossError::Aborted(missing));
+        }
+        let mut hasher = Sha3_256::new();
+        Digest::update(&mut hasher, crate::DST_ROBUST_DP_COIN_TOSS_COMMITMENT);
+        Digest::update(&mut hasher, self.session_id.to_le_bytes());
+        for contribution in self.reveals.iter().flatten() {
+            Digest::update(&mut hasher, contribution);
+        }
+        Ok(hasher.finalize().into())
+    }
+}
+
+/// Checks that `bits` holds exactly `rows` vectors of `cols` bits.
+pub fn check_shape(bits: &[Vec<bool>], rows: usize, cols: usize) -> crate::error::Result<()> {
+    if bits.len() != rows || bits.iter().any(|row| row.len() != cols) {
+        return Err(crate::error::Error::BadPublicRandomness { rows, cols });
+    }
+    Ok(())
+}
+
+/// Deterministically expands `seed` into `rows` vectors of `cols` public random bits with
+/// SHAKE256, e.g. `expand_seed(&seed, scheme.share_len() * pp.get_buckets(), pp.get_noise_bits())`
+/// for the noise of every prover.
+pub fn expand_seed(seed: &[u8; 32], rows: usize, cols: usize) -> Vec<Vec<bool>> {
+    let mut hasher = Shake256::default();
+    hasher.update(crate::DST_ROBUST_DP_RAND_BITS_EXPANSION);
+    hasher.update(seed);
+    hasher.update(&(rows as u64).to_le_bytes());
+    hasher.update(&(cols as u64).to_le_bytes());
+    let mut reader = hasher.finalize_xof();
+
+    let mut bytes = vec![0u8; cols.div_ceil(8)];
+    let mut bits = Vec::with_capacity(rows);
+    for _ in 0..rows {
+        reader.read(&mut bytes);
+        bits.push((0..cols).map(|j| (bytes[j / 8] >> (j % 8)) & 1 == 1).collect());
+    }
+    bits
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::{expand_seed, CoinToss, CoinTossError, CoinTossParty};
+
+    #[test]
+    fn test_coin_toss() {
+        let parties: Vec<CoinTossParty> = (0..4).map(|i| CoinTossParty::new(9, i)).collect();
+        let mut toss = CoinToss::new(9, 4);
+        for p in &parties {
+            toss.add_commitment(p.get_party(), p.commitment()).unwrap();
+        }
+        assert_eq!(toss.add_commitment(4, [0; 32]), Err(CoinTossError::UnknownParty(4)));
+
+        // 假冒的公开会被识别
+        assert_eq!(toss.add_reveal(1, [0; 32]), Err(CoinTossError::BadReveal(1)));
+        for p in &parties[..3] {
+            toss.add_reveal(p.get_party(), p.reveal()).unwrap();
+        }
+        assert_eq!(toss.finish(), Err(CoinTossError::Aborted(vec![3])));
+
+        toss.add_reveal(3, parties[3].reveal()).unwrap();
+        let seed = toss.finish().unwrap();
+        let bits = expand_seed(&seed, 3, 1000);
+        assert_eq!(bits.len(), 3);
+        assert!(bits.iter().all(|row| row.len() == 1000));
+        assert_eq!(bits, expand_seed(&seed, 3, 1000));
+        assert_ne!(bits[0], bits[1]);
+    }
+}
@@ -0,0 +1,199 @@
+// 整数输入的范围证明：x ∈ [0, 2^k)
+// 将 x 按比特分解 x = Σ 2^i b_i，对每个比特单独承诺 C_i = g^{b_i} h^{r_i} 并给出 OR 证明，
+// 其中 Σ 2^i r_i = r_sum，于是 Π C_i^{2^i} 必须等于客户端承诺之和 g^x h^{r_sum}
+use blstrs::{G1Projective, Scalar};
+use ff::Field;
+use rand::thread_rng;
+use serde::{Serialize, Deserialize};
+use crate::commitment::{Commit, CommitBase};
+use crate::error::Error;
+use crate::one_hot::OneHotProof;
+use crate::public_parameters::PublicParameters;
+use crate::sigma_or::{ProofContext, ProofStruct, SigmaOrError, create_bit_proof};
+use crate::util::random_scalars;
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct RangeProof {
+    pub bit_coms: Vec<G1Projective>,
+    pub bit_proofs: Vec<ProofStruct>,
+}
+
+/// Why a range proof was rejected.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RangeProofError {
+    /// The proof decomposes into more bits than the deployment allows.
+    TooManyBits { got: usize, max: usize },
+    /// The numbers of bit commitments and bit proofs differ, or there are none.
+    Malformed,
+    /// The weighted product of the bit commitments is not the client's commitment.
+    Decomposition,
+    /// The OR proof of this bit does not verify.
+    Bit(usize, SigmaOrError),
+}
+
+impl std::fmt::Display for RangeProofError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RangeProofError::TooManyBits { got, max } => write!(f, "range proof has {} bits, at most {} allowed", got, max),
+            RangeProofError::Malformed => write!(f, "bit commitments and bit proofs do not match"),
+            RangeProofError::Decomposition => write!(f, "bit commitments do not recompose to the commitment"),
+            RangeProofError::Bit(i, e) => write!(f, "bit {}: {}", i, e),
+        }
+    }
+}
+
+impl std::error::Error for RangeProofError {}
+
+// 2^0, 2^1, ..., 2^{bits-1}
+fn powers_of_two(bits: usize) -> Vec<Scalar> {
+    let mut pows = Vec::with_capacity(bits);
+    let mut pow = Scalar::one();
+    for _ in 0..bits {
+        pows.push(pow);
+        pow = pow.double();
+    }
+    pows
+}
+
+/// Proves that `commit_base.commit(x, ct_rand)` opens to a value in `[0, 2^bits)`.
+/// `x` must fit in `bits` bits.
+pub fn create_range_proof(commit_base: &CommitBase, x: u64, ct_rand: Scalar, bits: usize, ctx: &ProofContext) -> RangeProof {
+    let mut rng = thread_rng();
+    let pows = powers_of_two(bits);
+    let mut blindings = random_scalars(bits, &mut rng);
+    // r_0 = r_sum - Σ_{i>0} 2^i r_i
+    let rest: Scalar = (1..bits).map(|i| pows[i] * blindings[i]).sum();
+    blindings[0] = ct_rand - rest;
+
+    let mut bit_coms = Vec::with_capacity(bits);
+    let mut bit_proofs = Vec::with_capacity(bits);
+    for (i, &blinding) in blindings.iter().enumerate() {
+        let bit = (x >> i) & 1 == 1;
+        bit_coms.push(commit_base.commit(Scalar::from(bit as u64), blinding));
+        bit_proofs.push(create_bit_proof(commit_base, bit, blinding, ctx));
+    }
+    RangeProof { bit_coms, bit_proofs }
+}
+
+impl RangeProof {
+    pub fn bits(&self) -> usize {
+        self.bit_coms.len()
+    }
+
+    pub fn check(&self, commit_base: &CommitBase, reconcom: G1Projective, max_bits: usize, ctx: &ProofContext) -> Result<(), RangeProofError> {
+        if self.bits() > max_bits {
+            return Err(RangeProofError::TooManyBits { got: self.bits(), max: max_bits });
+        }
+        if self.bits() == 0 || self.bit_proofs.len() != self.bits() {
+            return Err(RangeProofError::Malformed);
+        }
+        if G1Projective::multi_exp(&self.bit_coms, &powers_of_two(self.bits())) != reconcom {
+            return Err(RangeProofError::Decomposition);
+        }
+        for (i, (com, proof)) in self.bit_coms.iter().zip(self.bit_proofs.iter()).enumerate() {
+            proof.check(commit_base, *com, ctx).map_err(|e| RangeProofError::Bit(i, e))?;
+        }
+        Ok(())
+    }
+}
+
+/// The proof a client attaches to its commitments: a single bit, a bounded integer, or a
+/// histogram vote.
+#[allow(clippy::large_enum_variant)]
+#[derive(Clone, Serialize, Deserialize)]
+pub enum InputProof {
+    Bit(ProofStruct),
+    Range(RangeProof),
+    OneHot(OneHotProof),
+}
+
+impl From<ProofStruct> for InputProof {
+    fn from(proof: ProofStruct) -> Self {
+        InputProof::Bit(proof)
+    }
+}
+
+impl From<RangeProof> for InputProof {
+    fn from(proof: RangeProof) -> Self {
+        InputProof::Range(proof)
+    }
+}
+
+impl From<OneHotProof> for InputProof {
+    fn from(proof: OneHotProof) -> Self {
+        InputProof::OneHot(proof)
+    }
+}
+
+impl InputProof {
+    /// Checks the proof against the commitment of every bucket. A histogram deployment only
+    /// accepts one-hot proofs; otherwise `bucket_coms` opens to a value in
+    /// `[0, 2^pp.get_input_bits())`, and a bit proof is accepted for every input range.
+    pub fn check(&self, pp: &PublicParameters, bucket_coms: &[G1Projective], ctx: &ProofContext) -> crate::error::Result<()> {
+        let commit_base = pp.get_commit_base();
+        match (self, bucket_coms) {
+            (InputProof::OneHot(proof), _) if pp.get_buckets() > 1 => proof.check(commit_base, bucket_coms, ctx)?,
+            (InputProof::Bit(proof), [reconcom]) if pp.get_buckets() == 1 => proof.check(commit_base, *reconcom, ctx)?,
+            (InputProof::Range(proof), [reconcom]) if pp.get_buckets() == 1 => proof.check(commit_base, *reconcom, pp.get_input_bits(), ctx)?,
+            _ => return Err(Error::WrongProofKind),
+        }
+        Ok(())
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use blstrs::Scalar;
+
+    use crate::client::Client;
+    use crate::commitment::Commit;
+    use crate::error::Error;
+    use crate::public_parameters::PublicParameters;
+    use crate::replicated::SharingScheme;
+    use crate::sigma_or::ProofContext;
+    use crate::user_store::{MemoryUserStore, UserStore};
+    use super::{create_range_proof, RangeProofError};
+
+    #[test]
+    fn test_range_proof() {
+        let pp = PublicParameters::new(b"seed");
+        let base = pp.get_commit_base();
+        let ctx = ProofContext::default();
+        let r = Scalar::from(11u64);
+        let com = base.commit(Scalar::from(13u64), r);
+
+        let proof = create_range_proof(base, 13, r, 4, &ctx);
+        assert_eq!(proof.check(base, com, 4, &ctx), Ok(()));
+        assert_eq!(proof.check(base, com, 8, &ctx), Ok(()));
+        assert_eq!(proof.check(base, com, 3, &ctx), Err(RangeProofError::TooManyBits { got: 4, max: 3 }));
+        assert_eq!(proof.check(base, base.commit(Scalar::from(12u64), r), 4, &ctx), Err(RangeProofError::Decomposition));
+
+        // 16 超出 4 比特，分解后最高位丢失，无法重组为原承诺
+        let com16 = base.commit(Scalar::from(16u64), r);
+        let proof16 = create_range_proof(base, 16, r, 4, &ctx);
+        assert!(proof16.check(base, com16, 4, &ctx).is_err());
+
+        let mut bad = proof.clone();
+        bad.bit_proofs.swap(0, 1);
+        assert!(matches!(bad.check(base, com, 4, &ctx), Err(RangeProofError::Bit(0, _))));
+    }
+
+    #[test]
+    fn test_range_client_on_board() {
+        let scheme = SharingScheme::new(3, 1);
+        let pp = PublicParameters::new_with_input_bits(b"seed", 8);
+        assert!(matches!(Client::new_range(0, 1, 256, &pp, &scheme, Vec::new()), Err(Error::InputOutOfRange { value: 256, bits: 8 })));
+
+        let mut broad = MemoryUserStore::new(&scheme);
+        let client = Client::new_range(0, 1, 200, &pp, &scheme, Vec::new()).unwrap();
+        client.send_proof_coms(&mut broad).unwrap();
+        let user = broad.get_user(1).unwrap();
+        assert_eq!(user.check_proof(&scheme, 0, &pp), Ok(()));
+        assert!(user.check_proof(&scheme, 1, &pp).is_err());
+
+        // 位数更少的部署会拒绝该证明（公共参数摘要也随之改变）
+        let narrow = PublicParameters::new_with_input_bits(b"seed", 4);
+        assert!(user.check_proof(&scheme, 0, &narrow).is_err());
+    }
+}
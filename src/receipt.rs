@@ -0,0 +1,270 @@
+// 收据：prover 收下份额后直接签给客户端的回执，以及 verifier 签名公布的最终有效用户集合。
+// 集合绑定会话和 verifier 计数时所用的公告板树头。客户端若能证明自己的提交以及每个 prover 的
+// 签名或公开份额都在该树头之下，输入证明也有效，却不在公布的集合里，就可以向任何人证明自己
+// 被 verifier 错误地排除了。收据不是公告板上的内容，verifier 看不到它：prover 收下份额却没有
+// 在树头之前签名时，收据是指控该 prover 的证据
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+use serde::{Serialize, Deserialize};
+use sha3::{Digest, Sha3_256};
+use crate::bulletin_board::{verify_inclusion, BoardEntry, Hash, ProvenEntry, SignedTreeHead};
+use crate::public_parameters::PublicParameters;
+use crate::replicated::{ReplicaCommitment, SharingScheme};
+use crate::sign::verify_sig;
+use crate::user_store::User;
+
+pub fn commitment_hash(coms: &ReplicaCommitment) -> [u8; 32] {
+    Sha3_256::digest(bcs::to_bytes(coms).unwrap()).into()
+}
+
+/// A prover's signed statement that it stored the share of `client_id` under these commitments.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InclusionReceipt {
+    pub session_id: u64,
+    pub client_id: u64,
+    pub commitment_hash: [u8; 32],
+    pub prover: usize,
+    pub signature: Vec<u8>,
+}
+
+fn receipt_message(session_id: u64, client_id: u64, commitment_hash: &[u8; 32], prover: usize) -> Vec<u8> {
+    let mut msg = crate::DST_ROBUST_DP_INCLUSION_RECEIPT.to_vec();
+    msg.extend(session_id.to_le_bytes());
+    msg.extend(client_id.to_le_bytes());
+    msg.extend(commitment_hash);
+    msg.extend((prover as u64).to_le_bytes());
+    msg
+}
+
+impl InclusionReceipt {
+    pub fn issue(sig_key: &SigningKey, session_id: u64, client_id: u64, coms: &ReplicaCommitment, prover: usize) -> Self {
+        let commitment_hash = commitment_hash(coms);
+        let signature = sig_key.sign(&receipt_message(session_id, client_id, &commitment_hash, prover));
+        Self { session_id, client_id, commitment_hash, prover, signature: signature.to_bytes().to_vec() }
+    }
+
+    /// Whether `pk` signed this receipt for `client_id`'s commitments in `session_id`.
+    pub fn verify(&self, pk: &VerifyingKey, session_id: u64, client_id: u64, coms: &ReplicaCommitment) -> bool {
+        let Ok(signature) = Signature::from_slice(&self.signature) else {
+            return false;
+        };
+        self.session_id == session_id
+            && self.client_id == client_id
+            && self.commitment_hash == commitment_hash(coms)
+            && pk.verify(&receipt_message(session_id, client_id, &self.commitment_hash, self.prover), &signature).is_ok()
+    }
+}
+
+/// The ids the verifier counted in a session from the board under the given tree head, signed
+/// by the verifier.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ValidUserSet {
+    pub session_id: u64,
+    pub board_size: u64,
+    pub board_root: Hash,
+    pub user_ids: Vec<u64>, // 升序、无重复
+    pub signature: Vec<u8>,
+}
+
+fn user_set_message(session_id: u64, board_size: u64, board_root: &Hash, user_ids: &[u64]) -> Vec<u8> {
+    let mut msg = crate::DST_ROBUST_DP_VALID_USER_SET.to_vec();
+    msg.extend(session_id.to_le_bytes());
+    msg.extend(board_size.to_le_bytes());
+    msg.extend(board_root);
+    msg.extend(bcs::to_bytes(user_ids).unwrap());
+    msg
+}
+
+impl ValidUserSet {
+    /// Signs the ids counted from the board under `head`.
+    pub fn sign(sig_key: &SigningKey, session_id: u64, head: &SignedTreeHead, mut user_ids: Vec<u64>) -> Self {
+        user_ids.sort_unstable();
+        user_ids.dedup();
+        let (board_size, board_root) = (head.size, head.root);
+        let signature = sig_key.sign(&user_set_message(session_id, board_size, &board_root, &user_ids)).to_bytes().to_vec();
+        Self { session_id, board_size, board_root, user_ids, signature }
+    }
+
+    pub fn verify(&self, verifier_pk: &VerifyingKey) -> bool {
+        let Ok(signature) = Signature::from_slice(&self.signature) else {
+            return false;
+        };
+        let msg = user_set_message(self.session_id, self.board_size, &self.board_root, &self.user_ids);
+        verifier_pk.verify(&msg, &signature).is_ok()
+    }
+
+    pub fn contains(&self, id: u64) -> bool {
+        self.user_ids.binary_search(&id).is_ok()
+    }
+}
+
+/// A client's board record and prover receipts, held against a published user set, with the
+/// proof that the client's submission is entry `index` of the board the set was counted from
+/// and the logged signatures and revealed shares of the user under the same tree head.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ExclusionClaim {
+    pub user: User,
+    pub index: u64,
+    pub entry: BoardEntry,
+    pub proof: Vec<Hash>,
+    pub posts: Vec<ProvenEntry>,
+    pub receipts: Vec<InclusionReceipt>,
+}
+
+// 记录中的承诺和输入证明必须正是公告板日志里的那次提交
+fn is_submission(entry: &BoardEntry, user: &User) -> bool {
+    match entry {
+        BoardEntry::NewUser { id, commitment, proof, .. } => *id == user.id
+            && *commitment == user.commitment
+            && bcs::to_bytes(proof).ok() == bcs::to_bytes(&user.sigma_proof).ok(),
+        _ => false,
+    }
+}
+
+// 对每个 prover：(树头下有它的有效签名, 树头下有它的有效公开份额)。任何一条记录不在树头下
+// 或不属于该用户都使整个声明无效
+fn logged_posts(claim: &ExclusionClaim, published: &ValidUserSet, scheme: &SharingScheme, pks: &[VerifyingKey], pp: &PublicParameters) -> Option<Vec<(bool, bool)>> {
+    let user = &claim.user;
+    let mut posted = vec![(false, false); scheme.prover_num()];
+    for (index, entry, proof) in &claim.posts {
+        if !verify_inclusion(&entry.leaf_hash(), *index as usize, published.board_size as usize, &published.board_root, proof) {
+            return None;
+        }
+        match entry {
+            BoardEntry::Signature { id, sig, prover } if *id == user.id => {
+                let pk = pks.get(*prover)?;
+                posted.get_mut(*prover)?.0 |= verify_sig(&user.commitment, pk, &sig.clone().into());
+            }
+            BoardEntry::Share { id, share, prover } if *id == user.id => {
+                let opens = share.get_ind() == *prover && share.check_com(scheme, pp.get_commit_base(), user.commitment.clone());
+                posted.get_mut(*prover)?.1 |= opens;
+            }
+            _ => return None,
+        }
+    }
+    Some(posted)
+}
+
+impl ExclusionClaim {
+    /// Whether the claim shows that the verifier wrongly left the user out of `published`: the
+    /// set is signed by the verifier and lacks the user, the user's submission is in the board
+    /// under the set's tree head, the input proof is valid, and under the same tree head every
+    /// prover either signed the commitments or has a valid revealed share. Receipts do not
+    /// count, since the verifier never sees them; see `blamed_provers`.
+    pub fn proves_exclusion(&self, published: &ValidUserSet, verifier_pk: &VerifyingKey, scheme: &SharingScheme, pks: &[VerifyingKey], pp: &PublicParameters) -> bool {
+        let user = &self.user;
+        if !published.verify(verifier_pk) || published.contains(user.id) {
+            return false;
+        }
+        if !is_submission(&self.entry, user)
+            || !verify_inclusion(&self.entry.leaf_hash(), self.index as usize, published.board_size as usize, &published.board_root, &self.proof) {
+            return false;
+        }
+        if user.commitment.len() != scheme.split_len() * pp.get_buckets()
+            || user.check_proof(scheme, published.session_id, pp).is_err() {
+            return false;
+        }
+        logged_posts(self, published, scheme, pks, pp).is_some_and(|posted| posted.iter().all(|&(sig, share)| sig || share))
+    }
+
+    /// Provers that issued a valid receipt for the user's logged submission but did not sign
+    /// it on the board under the tree head of `published`: the receipt shows they took the
+    /// share and then withheld their signature.
+    pub fn blamed_provers(&self, published: &ValidUserSet, scheme: &SharingScheme, pks: &[VerifyingKey], pp: &PublicParameters) -> Vec<usize> {
+        let user = &self.user;
+        if !is_submission(&self.entry, user)
+            || !verify_inclusion(&self.entry.leaf_hash(), self.index as usize, published.board_size as usize, &published.board_root, &self.proof) {
+            return Vec::new();
+        }
+        let Some(posted) = logged_posts(self, published, scheme, pks, pp) else {
+            return Vec::new();
+        };
+        let mut blamed: Vec<usize> = self.receipts.iter()
+            .filter(|r| pks.get(r.prover).is_some_and(|pk| r.verify(pk, published.session_id, user.id, &user.commitment)))
+            .map(|r| r.prover)
+            .filter(|&i| !posted[i].0)
+            .collect();
+        blamed.sort_unstable();
+        blamed.dedup();
+        blamed
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::bulletin_board::{BoardLog, MerkleBoard};
+    use crate::client::Client;
+    use crate::sign;
+    use crate::test_utils::{setup, Setup};
+    use crate::user_store::UserStore;
+    use super::*;
+
+    #[test]
+    fn test_exclusion_claim() {
+        let Setup { scheme, pp, sig_keys, pks, mut provers } = setup(3, 1);
+        let (verifier_sk, verifier_pk) = sign::gen_keys();
+        let mut broad = MerkleBoard::new(&scheme, sign::gen_keys().0);
+
+        let client = Client::new(0, 4, true, &pp, &scheme, pks.clone());
+        client.send_proof_coms(&mut broad).unwrap();
+        let mut receipts = Vec::new();
+        for i in 0..2 {
+            let receipt = provers[i].handle_client(client.send_share(i), &mut broad).unwrap();
+            assert!(receipt.verify(&pks[i], 0, 4, &client.get_coms()));
+            assert!(!receipt.verify(&pks[i], 1, 4, &client.get_coms()));
+            assert!(!receipt.verify(&pks[(i + 1) % 3], 0, 4, &client.get_coms()));
+            receipts.push(receipt);
+        }
+        // prover 2 给了收据却没有在公告板上签名，公告板因此不计入该用户
+        receipts.push(InclusionReceipt::issue(&sig_keys[2], 0, 4, &client.get_coms(), 2));
+        assert!(broad.check_all_users(0, &pks, &pp).is_empty());
+
+        let head = broad.signed_head().unwrap();
+        let published = ValidUserSet::sign(&verifier_sk, 0, &head, broad.check_all_users(0, &pks, &pp));
+        let (index, entry, proof) = broad.prove_user(4, head.size).unwrap();
+        let posts = broad.prove_posts(4, head.size).unwrap();
+        assert_eq!(posts.len(), 2);
+        let claim = ExclusionClaim { user: broad.get_user(4).unwrap(), index, entry, proof, posts, receipts };
+        // verifier 看不到收据：只凭收据不能指控 verifier，收据指控的是没签名的 prover 2
+        assert!(!claim.proves_exclusion(&published, &verifier_pk, &scheme, &pks, &pp));
+        assert_eq!(claim.blamed_provers(&published, &scheme, &pks, &pp), vec![2]);
+        let mut stale = claim.clone();
+        stale.receipts[2] = InclusionReceipt::issue(&sig_keys[2], 1, 4, &client.get_coms(), 2);
+        assert!(stale.blamed_provers(&published, &scheme, &pks, &pp).is_empty());
+
+        // 客户端公开 prover 2 的份额后，漏掉该用户的集合可以被证明是错的
+        client.reveal_share(&mut broad).unwrap();
+        let head = broad.signed_head().unwrap();
+        assert_eq!(broad.check_all_users(0, &pks, &pp), vec![4]);
+        let published = ValidUserSet::sign(&verifier_sk, 0, &head, vec![]);
+        let (index, entry, proof) = broad.prove_user(4, head.size).unwrap();
+        let posts = broad.prove_posts(4, head.size).unwrap();
+        let claim = ExclusionClaim { index, entry, proof, posts, ..claim };
+        assert!(claim.proves_exclusion(&published, &verifier_pk, &scheme, &pks, &pp));
+        assert_eq!(claim.blamed_provers(&published, &scheme, &pks, &pp), vec![2]);
+
+        // 被计入的用户、伪造的集合、缺少公开份额的声明都不成立
+        let counted = ValidUserSet::sign(&verifier_sk, 0, &head, vec![4]);
+        assert!(!claim.proves_exclusion(&counted, &verifier_pk, &scheme, &pks, &pp));
+        let mut forged = published.clone();
+        forged.user_ids.push(9);
+        assert!(!claim.proves_exclusion(&forged, &verifier_pk, &scheme, &pks, &pp));
+        let partial = ExclusionClaim { posts: claim.posts[..2].to_vec(), ..claim.clone() };
+        assert!(!partial.proves_exclusion(&published, &verifier_pk, &scheme, &pks, &pp));
+        let mut moved = claim.clone();
+        moved.posts[2].0 += 1;
+        assert!(!moved.proves_exclusion(&published, &verifier_pk, &scheme, &pks, &pp));
+
+        // 其它会话的集合、不在该树头下的提交、换掉的承诺也都不成立
+        let other_session = ValidUserSet::sign(&verifier_sk, 1, &head, vec![]);
+        assert!(!claim.proves_exclusion(&other_session, &verifier_pk, &scheme, &pks, &pp));
+        let mut other_board = MerkleBoard::new(&scheme, sign::gen_keys().0);
+        let late = Client::new(0, 4, false, &pp, &scheme, pks.clone());
+        late.send_proof_coms(&mut other_board).unwrap();
+        let (index, entry, proof) = other_board.prove_user(4, 1).unwrap();
+        let unlogged = ExclusionClaim { user: other_board.get_user(4).unwrap(), index, entry, proof, ..claim.clone() };
+        assert!(!unlogged.proves_exclusion(&published, &verifier_pk, &scheme, &pks, &pp));
+        let swapped = ExclusionClaim { user: other_board.get_user(4).unwrap(), ..claim.clone() };
+        assert!(!swapped.proves_exclusion(&published, &verifier_pk, &scheme, &pks, &pp));
+    }
+}
@@ -0,0 +1,261 @@
+// 追加写的记录日志，供持久化的 ShareStore / UserStore 使用
+// 每条记录为 [长度 u32 LE][SHA3-256(payload) 前 8 字节][payload]；
+// 打开时顺序扫描，只截断写到一半的最后一条记录（写入时崩溃）；
+// 中间的记录损坏时其后还有已确认的记录，不能丢弃，打开失败
+use sha3::{Digest, Sha3_256};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+const HEADER_LEN: u64 = 12;
+
+/// Largest payload a record may hold; longer length fields are treated as corruption.
+pub const MAX_RECORD_LEN: usize = 16 * 1024 * 1024;
+
+/// Intact records of a log as `(offset, payload)`.
+pub type Records = Vec<(u64, Vec<u8>)>;
+
+/// When appended records are flushed to disk.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FsyncPolicy {
+    /// fsync after every record; nothing acknowledged is lost on a crash.
+    Always,
+    /// fsync after every `n` records.
+    Every(usize),
+    /// Leave it to the OS; call `sync` explicitly.
+    Never,
+}
+
+pub struct RecordLog {
+    path: PathBuf,
+    file: File,
+    len: u64,
+    policy: FsyncPolicy,
+    unsynced: usize,
+    read_only: bool,
+}
+
+fn checksum(payload: &[u8]) -> [u8; 8] {
+    let digest = Sha3_256::digest(payload);
+    digest[..8].try_into().unwrap()
+}
+
+fn frame(payload: &[u8]) -> Vec<u8> {
+    let mut record = Vec::with_capacity(HEADER_LEN as usize + payload.len());
+    record.extend((payload.len() as u32).to_le_bytes());
+    record.extend(checksum(payload));
+    record.extend(payload);
+    record
+}
+
+fn corrupted(offset: usize) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("Corrupted record at offset {} is followed by other data", offset))
+}
+
+fn record_len(header: &[u8]) -> io::Result<usize> {
+    let len = u32::from_le_bytes(header[..4].try_into().unwrap()) as usize;
+    if len > MAX_RECORD_LEN {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, format!("Record of {} bytes exceeds the maximum", len)));
+    }
+    Ok(len)
+}
+
+// 返回所有完整记录 (偏移, payload) 以及有效部分的长度。只有延伸到文件末尾的记录才可能是写到一半的，
+// 其它位置校验失败或长度超限都是损坏
+fn scan(file: &mut File) -> io::Result<(Records, u64)> {
+    let mut bytes = Vec::new();
+    file.seek(SeekFrom::Start(0))?;
+    file.read_to_end(&mut bytes)?;
+    let mut records = Vec::new();
+    let mut offset = 0usize;
+    while bytes.len() - offset >= HEADER_LEN as usize {
+        let len = record_len(&bytes[offset..]).map_err(|_| corrupted(offset))?;
+        let start = offset + HEADER_LEN as usize;
+        if bytes.len() - start < len {
+            break;
+        }
+        if bytes[offset + 4..start] != checksum(&bytes[start..start + len]) {
+            if start + len == bytes.len() {
+                break;
+            }
+            return Err(corrupted(offset));
+        }
+        records.push((offset as u64, bytes[start..start + len].to_vec()));
+        offset = start + len;
+    }
+    Ok((records, offset as u64))
+}
+
+fn read_record(mut file: &File, offset: u64) -> io::Result<Vec<u8>> {
+    let mut header = [0u8; HEADER_LEN as usize];
+    file.seek(SeekFrom::Start(offset))?;
+    file.read_exact(&mut header)?;
+    let mut payload = vec![0u8; record_len(&header)?];
+    file.read_exact(&mut payload)?;
+    if header[4..] != checksum(&payload) {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "Record checksum mismatch"));
+    }
+    Ok(payload)
+}
+
+/// Reads records of a `RecordLog` through its own file handle.
+pub struct RecordReader {
+    file: File,
+}
+
+impl RecordReader {
+    pub fn read_at(&self, offset: u64) -> io::Result<Vec<u8>> {
+        read_record(&self.file, offset)
+    }
+}
+
+impl RecordLog {
+    /// Opens (or creates) the log at `path`, dropping a torn final record left by a crash. Fails
+    /// if a record before the end is corrupted. Returns the log and every intact record with its
+    /// offset.
+    pub fn open<P: AsRef<Path>>(path: P, policy: FsyncPolicy) -> io::Result<(Self, Records)> {
+        let path = path.as_ref().to_path_buf();
+        let mut file = OpenOptions::new().read(true).write(true).create(true).truncate(false).open(&path)?;
+        let (records, len) = scan(&mut file)?;
+        if file.metadata()?.len() != len {
+            file.set_len(len)?;
+            file.sync_all()?;
+        }
+        Ok((Self { path, file, len, policy, unsynced: 0, read_only: false }, records))
+    }
+
+    /// Opens an existing log without ever writing to it; a torn tail is ignored, not truncated.
+    pub fn open_read_only<P: AsRef<Path>>(path: P) -> io::Result<(Self, Records)> {
+        let path = path.as_ref().to_path_buf();
+        let mut file = File::open(&path)?;
+        let (records, len) = scan(&mut file)?;
+        Ok((Self { path, file, len, policy: FsyncPolicy::Never, unsynced: 0, read_only: true }, records))
+    }
+
+    pub fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+
+    /// Size of the intact part of the log in bytes.
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Appends a record of at most `MAX_RECORD_LEN` bytes and returns its offset.
+    pub fn append(&mut self, payload: &[u8]) -> io::Result<u64> {
+        if self.read_only {
+            return Err(io::Error::new(io::ErrorKind::PermissionDenied, "Log is opened read-only"));
+        }
+        if payload.len() > MAX_RECORD_LEN {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "Record exceeds the maximum size"));
+        }
+        let offset = self.len;
+        self.file.seek(SeekFrom::Start(offset))?;
+        self.file.write_all(&frame(payload))?;
+        self.len += HEADER_LEN + payload.len() as u64;
+        self.unsynced += 1;
+        match self.policy {
+            FsyncPolicy::Always => self.sync()?,
+            FsyncPolicy::Every(n) if self.unsynced >= n => self.sync()?,
+            _ => {}
+        }
+        Ok(offset)
+    }
+
+    /// Reads the payload of the record at `offset`.
+    pub fn read_at(&self, offset: u64) -> io::Result<Vec<u8>> {
+        read_record(&self.file, offset)
+    }
+
+    /// An independent read handle, e.g. for streaming records while the log keeps growing.
+    pub fn reader(&self) -> io::Result<RecordReader> {
+        Ok(RecordReader { file: File::open(&self.path)? })
+    }
+
+    pub fn sync(&mut self) -> io::Result<()> {
+        if !self.read_only {
+            self.file.sync_data()?;
+        }
+        self.unsynced = 0;
+        Ok(())
+    }
+
+    /// Compaction: atomically replaces the log with `payloads` (written to a temporary file,
+    /// fsynced, then renamed over the log). Returns the new offset of each payload.
+    pub fn rewrite<I: IntoIterator<Item = Vec<u8>>>(&mut self, payloads: I) -> io::Result<Vec<u64>> {
+        if self.read_only {
+            return Err(io::Error::new(io::ErrorKind::PermissionDenied, "Log is opened read-only"));
+        }
+        let mut tmp_path = self.path.clone().into_os_string();
+        tmp_path.push(".compact");
+        let mut tmp = File::create(&tmp_path)?;
+        let mut offsets = Vec::new();
+        let mut len = 0;
+        for payload in payloads {
+            offsets.push(len);
+            let record = frame(&payload);
+            tmp.write_all(&record)?;
+            len += record.len() as u64;
+        }
+        tmp.sync_all()?;
+        drop(tmp);
+        fs::rename(&tmp_path, &self.path)?;
+        if let Some(dir) = self.path.parent().filter(|d| !d.as_os_str().is_empty()) {
+            // 让 rename 本身落盘
+            File::open(dir)?.sync_all()?;
+        }
+        self.file = OpenOptions::new().read(true).write(true).open(&self.path)?;
+        self.len = len;
+        self.unsynced = 0;
+        Ok(offsets)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::fs::{self, OpenOptions};
+    use std::io::{ErrorKind, Seek, SeekFrom, Write};
+    use super::*;
+
+    #[test]
+    fn test_record_log() {
+        let path = std::env::temp_dir().join(format!("rvdp_record_log_{}.log", std::process::id()));
+        let _ = fs::remove_file(&path);
+        let (mut log, _) = RecordLog::open(&path, FsyncPolicy::Always).unwrap();
+        let offsets: Vec<u64> = (0..3u8).map(|i| log.append(&[i; 20]).unwrap()).collect();
+        assert!(log.append(&vec![0; MAX_RECORD_LEN + 1]).is_err());
+        let reader = log.reader().unwrap();
+        assert_eq!(reader.read_at(offsets[2]).unwrap(), vec![2; 20]);
+        drop(log);
+
+        // 写到一半的最后一条记录被截断
+        let full = fs::metadata(&path).unwrap().len();
+        OpenOptions::new().append(true).open(&path).unwrap().write_all(&[9, 0, 0, 0, 1, 2]).unwrap();
+        let (log, records) = RecordLog::open(&path, FsyncPolicy::Always).unwrap();
+        assert_eq!(records.len(), 3);
+        assert_eq!(fs::metadata(&path).unwrap().len(), full);
+        drop(log);
+
+        // 中间的记录损坏时不能丢掉后面已确认的记录
+        let mut file = OpenOptions::new().write(true).open(&path).unwrap();
+        file.seek(SeekFrom::Start(offsets[1] + HEADER_LEN)).unwrap();
+        file.write_all(&[7]).unwrap();
+        drop(file);
+        assert_eq!(RecordLog::open(&path, FsyncPolicy::Always).err().unwrap().kind(), ErrorKind::InvalidData);
+        assert_eq!(fs::metadata(&path).unwrap().len(), full);
+
+        // 长度超限的头部在分配之前就被拒绝
+        let mut file = OpenOptions::new().write(true).open(&path).unwrap();
+        file.seek(SeekFrom::Start(offsets[2])).unwrap();
+        file.write_all(&u32::MAX.to_le_bytes()).unwrap();
+        drop(file);
+        assert_eq!(reader.read_at(offsets[2]).unwrap_err().kind(), ErrorKind::InvalidData);
+        assert_eq!(RecordLog::open_read_only(&path).err().unwrap().kind(), ErrorKind::InvalidData);
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -1,7 +1,6 @@
 use blstrs::{G1Projective, Scalar};
 use ff::Field;
 use group::Group;
-use crate::constants::{SPLIT_LEN,SHARE_LEN,IND_ARR};
 use crate::util::random_scalars;
 use rand::Rng; // Import the Rng trait
 use rand::thread_rng;
@@ -9,80 +8,148 @@ use crate::commitment::{Commit,CommitBase};
 use serde::{Serialize, Deserialize};
 
 
+/// Parameters of the replicated secret sharing: `prover_num` provers, any `threshold` of which
+/// learn nothing about the secret.
+///
+/// The secret is split into one additive split per `threshold`-subset of provers, and each
+/// split is held by every prover outside its subset. Splits are ordered lexicographically by
+/// their holder sets, e.g. `(3, 1)` gives `[[0, 1], [0, 2], [1, 2]]`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SharingScheme {
+    prover_num: usize,
+    threshold: usize,
+    ind_arr: Vec<Vec<usize>>, // splits held by each prover
+    holders: Vec<Vec<usize>>, // provers holding each split
+}
+
+impl SharingScheme {
+    pub fn new(prover_num: usize, threshold: usize) -> Self {
+        if threshold == 0 || threshold >= prover_num {
+            panic!("Invalid threshold {} for {} provers", threshold, prover_num);
+        }
+        let holders = combinations(prover_num, prover_num - threshold);
+        let mut ind_arr = vec![Vec::new(); prover_num];
+        for (split, set) in holders.iter().enumerate() {
+            for &prover in set {
+                ind_arr[prover].push(split);
+            }
+        }
+        Self {
+            prover_num,
+            threshold,
+            ind_arr,
+            holders,
+        }
+    }
+
+    pub fn prover_num(&self) -> usize {
+        self.prover_num
+    }
+
+    pub fn threshold(&self) -> usize {
+        self.threshold
+    }
+
+    /// Number of additive splits of a secret.
+    pub fn split_len(&self) -> usize {
+        self.holders.len()
+    }
+
+    /// Number of splits held by a single prover.
+    pub fn share_len(&self) -> usize {
+        self.ind_arr[0].len()
+    }
+
+    /// Indices of the splits held by prover `ind`.
+    pub fn indices(&self, ind: usize) -> &[usize] {
+        &self.ind_arr[ind]
+    }
+
+    /// Indices of the provers holding split `split`.
+    pub fn holders(&self, split: usize) -> &[usize] {
+        &self.holders[split]
+    }
+
+    pub fn ind_arr(&self) -> &[Vec<usize>] {
+        &self.ind_arr
+    }
+}
+
+// 按字典序枚举 {0..n} 的所有 k 元子集
+fn combinations(n: usize, k: usize) -> Vec<Vec<usize>> {
+    let mut res = Vec::new();
+    let mut comb: Vec<usize> = (0..k).collect();
+    loop {
+        res.push(comb.clone());
+        let mut i = k;
+        while i > 0 && comb[i - 1] == n - k + i - 1 {
+            i -= 1;
+        }
+        if i == 0 {
+            return res;
+        }
+        comb[i - 1] += 1;
+        for j in i..k {
+            comb[j] = comb[j - 1] + 1;
+        }
+    }
+}
+
+
 pub struct ReplicaSecret {
     s: Scalar,
-    splits: [Scalar; SPLIT_LEN],
-    blindings: [Scalar; SPLIT_LEN],
+    splits: Vec<Scalar>,
+    blindings: Vec<Scalar>,
     r_sum: Scalar,
+    scheme: SharingScheme,
 }
 
 
-#[derive(Clone, Serialize, Deserialize)]
+/// The splits of one prover. For a histogram the splits of every bucket are concatenated,
+/// bucket `b` occupying `share[b * share_len..(b + 1) * share_len]`.
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize)]
 
 pub struct ReplicaShare{
     ind:usize,
-    share:[Scalar; SHARE_LEN],
-    blindings:[Scalar; SHARE_LEN],
+    share:Vec<Scalar>,
+    blindings:Vec<Scalar>,
 }
 
 
 
 impl ReplicaSecret{
-    pub fn new(s:Scalar) -> Self {
-        let splits_len = SPLIT_LEN;
+    pub fn new(s:Scalar, scheme:&SharingScheme) -> Self {
+        let split_len = scheme.split_len();
         let mut rng = thread_rng();
-        let splits_vec = random_scalars(SPLIT_LEN - 1, &mut rng);
-        let blindings_vec = random_scalars(SPLIT_LEN, &mut rng);
-
-        let mut splits: [Scalar; SPLIT_LEN] = [Scalar::zero(); SPLIT_LEN];
-        let mut blindings: [Scalar; SPLIT_LEN] = [Scalar::zero(); SPLIT_LEN];
-
-        // 将 Vec 转换为数组
-        for i in 0..(SPLIT_LEN - 1) {
-            splits[i] = splits_vec[i];
-        }
-        for i in 0..SPLIT_LEN {
-            blindings[i] = blindings_vec[i];
-        }
-
-        let mut sum = Scalar::zero();
-        for i in 0..(SPLIT_LEN - 1) {
-            sum += splits[i];
-        }
-        let last = s - sum;
-        splits[SPLIT_LEN - 1] = last;
+        let mut splits = random_scalars(split_len - 1, &mut rng);
+        let blindings = random_scalars(split_len, &mut rng);
 
+        let sum: Scalar = splits.iter().sum();
+        splits.push(s - sum);
 
-        let mut blindings_sum = Scalar::zero();
-        for i in 0..splits_len {
-            blindings_sum += blindings[i];
-        }
+        let blindings_sum: Scalar = blindings.iter().sum();
 
         Self {
             s,
             splits,
             blindings,
             r_sum: blindings_sum,
+            scheme: scheme.clone(),
         }
     }
 
     pub fn get_s(&self) -> Scalar {
-        self.s.clone()
+        self.s
     }
 
-    pub fn get_splits(&self) -> [Scalar; SPLIT_LEN] {
+    pub fn get_splits(&self) -> Vec<Scalar> {
         self.splits.clone()
     }
 
     pub fn get_share(&self, ind:usize) -> ReplicaShare {
-        let mut share: [Scalar; SHARE_LEN] = [Scalar::zero(); SHARE_LEN];
-        let mut blindings: [Scalar; SHARE_LEN] = [Scalar::zero(); SHARE_LEN];
-
-        for j in 0..SHARE_LEN {
-            let i=IND_ARR[ind][j];//注意这里的索引顺序
-            share[j]=self.splits[i];
-            blindings[j]=self.blindings[i];
-        }
+        let indices = self.scheme.indices(ind);//注意这里的索引顺序
+        let share = indices.iter().map(|&i| self.splits[i]).collect();
+        let blindings = indices.iter().map(|&i| self.blindings[i]).collect();
 
         ReplicaShare {
             ind,
@@ -92,22 +159,18 @@ impl ReplicaSecret{
     }
     
     pub fn commit(&self,base:CommitBase ) -> Vec<G1Projective> {
-        let mut coms = Vec::new();
-        for i in 0..SPLIT_LEN {
-            coms.push(base.commit(self.splits[i], self.blindings[i]));
-        }
-        coms
+        self.splits.iter().zip(self.blindings.iter()).map(|(&s, &r)| base.commit(s, r)).collect()
     }
 
     pub fn get_sum_r(&self) -> Scalar {
-        self.r_sum.clone()
+        self.r_sum
     }
 }
 
 impl ReplicaShare{
-    pub fn new_zero(ind:usize) -> Self {
-        let share = [Scalar::zero(); SHARE_LEN];
-        let blindings = [Scalar::zero(); SHARE_LEN];
+    pub fn new_zero(scheme:&SharingScheme, ind:usize, buckets:usize) -> Self {
+        let share = vec![Scalar::zero(); scheme.share_len() * buckets];
+        let blindings = vec![Scalar::zero(); scheme.share_len() * buckets];
         Self {
             ind,
             share,
@@ -115,38 +178,67 @@ impl ReplicaShare{
         }
         
     }
+
+    /// Concatenates the shares of one prover for several buckets.
+    pub fn concat(shares: Vec<ReplicaShare>) -> Self {
+        let ind = shares.first().map(|s| s.ind).unwrap_or_default();
+        let share = shares.iter().flat_map(|s| s.share.iter().cloned()).collect();
+        let blindings = shares.iter().flat_map(|s| s.blindings.iter().cloned()).collect();
+        Self {
+            ind,
+            share,
+            blindings,
+        }
+    }
     
     pub fn get_ind(&self) -> usize {
         self.ind
     }
 
-    pub fn get_share(&self) -> [Scalar; SHARE_LEN]{
+    pub fn get_share(&self) -> Vec<Scalar>{
         self.share.clone()
     }
 
-    pub fn check_com(&self,base:&CommitBase,com:ReplicaCommitment) -> bool {
-        for i in 0..SHARE_LEN {
-            let ind=IND_ARR[self.ind][i];
-            if !base.vrfy(self.share[i], self.blindings[i], com.ind_value(ind)) {
+    /// Number of buckets this share covers.
+    pub fn buckets(&self, scheme:&SharingScheme) -> usize {
+        self.share.len() / scheme.share_len()
+    }
+
+    // 份额长度或编号与方案不符时视为无效
+    fn matches(&self, scheme:&SharingScheme) -> bool {
+        self.ind < scheme.prover_num() && !self.share.is_empty() && self.share.len().is_multiple_of(scheme.share_len()) && self.blindings.len() == self.share.len()
+    }
+
+    // 第 k 个份额元素对应的承诺下标
+    fn com_index(&self, scheme:&SharingScheme, k:usize) -> usize {
+        let (bucket, i) = (k / scheme.share_len(), k % scheme.share_len());
+        bucket * scheme.split_len() + scheme.indices(self.ind)[i]
+    }
+
+    pub fn check_com(&self,scheme:&SharingScheme,base:&CommitBase,com:ReplicaCommitment) -> bool {
+        if !self.matches(scheme) || com.len() != self.buckets(scheme) * scheme.split_len() {
+            return false;
+        }
+        for k in 0..self.share.len() {
+            if !base.vrfy(self.share[k], self.blindings[k], com.ind_value(self.com_index(scheme, k))) {
                 return false;
             }
         }
         true
     }
 
-    pub fn check_com_with_noise(&self, base: &CommitBase, com:ReplicaCommitment, noise_commitment: Vec<G1Projective>) -> bool {
-        for i in 0..SHARE_LEN {
-            let ind = IND_ARR[self.ind][i];
-            if !base.vrfy(self.share[i], self.blindings[i], com.ind_value(ind) + noise_commitment[i]) {
-                return false;
-            }
+    pub fn check_com_with_noise(&self, scheme:&SharingScheme, base: &CommitBase, com:ReplicaCommitment, noise_commitment: Vec<G1Projective>) -> bool {
+        if !self.matches(scheme) || com.len() != self.buckets(scheme) * scheme.split_len() || noise_commitment.len() != self.share.len() {
+            return false;
         }
-        true
+        noise_commitment.iter().enumerate().all(|(k, noise)| {
+            base.vrfy(self.share[k], self.blindings[k], com.ind_value(self.com_index(scheme, k)) + noise)
+        })
     }
 
     pub fn add_noise(&self, noise: Vec<Scalar>, noise_proof: Vec<Scalar>)-> ReplicaShare{
-        let share_with_noise: [Scalar; SHARE_LEN] = self.share.iter().zip(noise.iter()).map(|(&s, &n)| s + n).collect::<Vec<Scalar>>().try_into().unwrap();
-        let blindings_with_noise: [Scalar; SHARE_LEN] = self.blindings.iter().zip(noise_proof.iter()).map(|(&b, &n)| b + n).collect::<Vec<Scalar>>().try_into().unwrap();
+        let share_with_noise = self.share.iter().zip(noise.iter()).map(|(&s, &n)| s + n).collect();
+        let blindings_with_noise = self.blindings.iter().zip(noise_proof.iter()).map(|(&b, &n)| b + n).collect();
         ReplicaShare {
             ind: self.ind,
             share: share_with_noise,
@@ -155,18 +247,6 @@ impl ReplicaShare{
     }
 }
 
-impl Default for ReplicaShare {
-    fn default() -> Self {
-        let share = [Scalar::zero(); SHARE_LEN];
-        let blindings = [Scalar::zero(); SHARE_LEN];
-        Self {
-            ind: 0,
-            share,
-            blindings,
-        }
-    }
-}
-
 use std::ops::Add;
 
 
@@ -177,8 +257,8 @@ impl Add for ReplicaShare {
     fn add(self, other: Self) -> Self {
 
         // 对应元素相加
-        let share: [Scalar; SHARE_LEN] = self.share.iter().zip(other.share.iter()).map(|(a, b)| *a + *b).collect::<Vec<Scalar>>().try_into().unwrap();
-        let blindings = self.blindings.iter().zip(other.blindings.iter()).map(|(a, b)| *a + *b).collect::<Vec<Scalar>>().try_into().unwrap();
+        let share = self.share.iter().zip(other.share.iter()).map(|(a, b)| *a + *b).collect();
+        let blindings = self.blindings.iter().zip(other.blindings.iter()).map(|(a, b)| *a + *b).collect();
 
         Self {
             ind: self.ind,
@@ -190,15 +270,17 @@ impl Add for ReplicaShare {
 
 
 
-#[derive(Clone, Serialize, Deserialize)]
+/// Commitments to every split; for a histogram the commitments of all buckets are
+/// concatenated, bucket `b` occupying `com[b * split_len..(b + 1) * split_len]`.
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize)]
 
 pub struct ReplicaCommitment{
     com:Vec<G1Projective>,
 }
 
 impl ReplicaCommitment{
-    pub fn new(com:Vec<G1Projective>) -> Self {
-        if com.len() != SPLIT_LEN {
+    pub fn new(scheme:&SharingScheme, com:Vec<G1Projective>) -> Self {
+        if com.len() != scheme.split_len() {
             panic!("Invalid length of commitment");
         }
         Self {
@@ -206,20 +288,36 @@ impl ReplicaCommitment{
         }
     }
 
+    /// Concatenates the commitments of several buckets.
+    pub fn concat(coms: Vec<ReplicaCommitment>) -> Self {
+        Self {
+            com: coms.into_iter().flat_map(|c| c.com).collect(),
+        }
+    }
+
     pub fn ind_value(&self,ind:usize) -> G1Projective {
         self.com[ind]
     }
 
+    pub fn len(&self) -> usize {
+        self.com.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.com.is_empty()
+    }
+
     pub fn get_sum(&self) -> G1Projective {
-        let mut sum = G1Projective::identity();
-        for i in 0..SPLIT_LEN {
-            sum += self.com[i];
-        }
-        sum
+        self.com.iter().sum()
     }
 
-    pub fn new_zero() -> Self {
-        let com = vec![G1Projective::identity(); SPLIT_LEN];
+    /// The commitment to each bucket's value, i.e. the sum of that bucket's split commitments.
+    pub fn bucket_sums(&self, scheme:&SharingScheme) -> Vec<G1Projective> {
+        self.com.chunks(scheme.split_len()).map(|c| c.iter().sum()).collect()
+    }
+
+    pub fn new_zero(scheme:&SharingScheme, buckets:usize) -> Self {
+        let com = vec![G1Projective::identity(); scheme.split_len() * buckets];
         Self {
             com,
         }
@@ -240,56 +338,204 @@ impl Add for ReplicaCommitment {
     }
 }
 
-pub fn recon_shares(shares:Vec<ReplicaShare>)->Option<Scalar>{
-    let mut splits: [Vec<_>; SPLIT_LEN] = Default::default();
+pub fn recon_shares(scheme:&SharingScheme, shares:Vec<ReplicaShare>)->Option<Scalar>{
+    match recon_buckets(scheme, shares)?.as_slice() {
+        [value] => Some(*value),
+        _ => None,
+    }
+}
+
+/// Reconstructs every bucket of a histogram; `None` if some split of some bucket is held by
+/// none of `shares` or the shares disagree on the number of buckets.
+pub fn recon_buckets(scheme:&SharingScheme, shares:Vec<ReplicaShare>)->Option<Vec<Scalar>>{
+    let buckets = shares.first()?.buckets(scheme);
+    let mut splits: Vec<Vec<Scalar>> = vec![Vec::new(); scheme.split_len() * buckets];
     for share in shares {
-        for i in 0..SHARE_LEN {
-            let ind = IND_ARR[share.ind][i];
-            splits[ind].push(share.share[i]);
+        if !share.matches(scheme) || share.buckets(scheme) != buckets {
+            continue;
+        }
+        for k in 0..share.share.len() {
+            splits[share.com_index(scheme, k)].push(share.share[k]);
         }
     }
 
-    let mut sum = Scalar::zero();
-    for i in 0..SPLIT_LEN {
-        let len = splits[i].len();
+    let mut sums = vec![Scalar::zero(); buckets];
+    for (j, split) in splits.iter().enumerate() {
+        let len = split.len();
         if len == 0 {
             return None;
         }
         let random_pick = thread_rng().gen_range(0..len);
-        sum += splits[i][random_pick];
+        sums[j / scheme.split_len()] += split[random_pick];
     }
-    Some(sum)
+    Some(sums)
 
 }
 
+/// Why a checked reconstruction failed.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BlameReport {
+    /// Provers whose copy of some split disagrees with the commitments, or whose share is malformed.
+    pub cheaters: Vec<usize>,
+    /// Splits (over all buckets) without any copy that opens its commitment.
+    pub unresolved_splits: Vec<usize>,
+}
+
+impl std::fmt::Display for BlameReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "cheating provers {:?}, unresolved splits {:?}", self.cheaters, self.unresolved_splits)
+    }
+}
+
+impl std::error::Error for BlameReport {}
+
+/// Reconstructs every bucket after comparing all copies of each split. If every holder sent a
+/// copy of a split and they agree it is taken as is; otherwise each copy is checked against `com` (plus the copy's noise
+/// commitment from `noise_coms[prover]` for noisy shares, whose copies always differ) and the
+/// provers whose copies do not open are blamed. Fails if anyone was blamed or some split has no
+/// valid copy.
+pub fn recon_checked(scheme:&SharingScheme, base:&CommitBase, shares:&[ReplicaShare], com:&ReplicaCommitment, noise_coms:Option<&[Vec<G1Projective>]>) -> Result<Vec<Scalar>, BlameReport> {
+    let mut report = BlameReport::default();
+    let buckets = com.len() / scheme.split_len();
+    // 每个 split 的所有拷贝：(prover, 份额下标 k)
+    let mut copies: Vec<Vec<(&ReplicaShare, usize)>> = vec![Vec::new(); com.len()];
+    for share in shares {
+        let noise_ok = noise_coms.is_none_or(|n| n.get(share.ind).is_some_and(|n| n.len() == share.share.len()));
+        if !share.matches(scheme) || share.buckets(scheme) != buckets || !noise_ok {
+            if share.ind < scheme.prover_num() {
+                report.cheaters.push(share.ind);
+            }
+            continue;
+        }
+        for k in 0..share.share.len() {
+            copies[share.com_index(scheme, k)].push((share, k));
+        }
+    }
+
+    let mut sums = vec![Scalar::zero(); buckets];
+    for (j, split) in copies.iter().enumerate() {
+        // 所有持有者都给出拷贝且持有者多于 threshold 时至少有一份诚实拷贝，一致即可直接采用；
+        // 按不同的 prover 计数，同一 prover 的重复拷贝不能冒充其他持有者
+        let holders = scheme.holders(j % scheme.split_len());
+        let distinct = holders.iter().all(|h| split.iter().any(|(share, _)| share.ind == *h));
+        let agreed = noise_coms.is_none() && split.len() == holders.len() && distinct && holders.len() > scheme.threshold() && split.windows(2).all(|w| {
+            let ((a, ka), (b, kb)) = (w[0], w[1]);
+            a.share[ka] == b.share[kb] && a.blindings[ka] == b.blindings[kb]
+        });
+        let mut valid = None;
+        for &(share, k) in split {
+            if agreed {
+                valid = Some(share.share[k]);
+                break;
+            }
+            let noise = noise_coms.map_or(G1Projective::identity(), |n| n[share.ind][k]);
+            if base.vrfy(share.share[k], share.blindings[k], com.ind_value(j) + noise) {
+                valid.get_or_insert(share.share[k]);
+            } else {
+                report.cheaters.push(share.ind);
+            }
+        }
+        match valid {
+            Some(value) => sums[j / scheme.split_len()] += value,
+            None => report.unresolved_splits.push(j),
+        }
+    }
+
+    report.cheaters.sort_unstable();
+    report.cheaters.dedup();
+    if report == BlameReport::default() {
+        Ok(sums)
+    } else {
+        Err(report)
+    }
+}
+
 
 #[cfg(test)]
 mod tests{
-    use blstrs::Scalar;
+    use blstrs::{G1Projective, Scalar};
     use ff::Field;
 
+    use crate::commitment::{Commit, CommitBase};
     use crate::constants;
 
-    use super::{recon_shares, ReplicaSecret};
+    use super::{recon_checked, recon_shares, BlameReport, ReplicaCommitment, ReplicaSecret, ReplicaShare, SharingScheme};
 
     #[test]
     fn test_recon(){
-        let secret = ReplicaSecret::new(Scalar::from(1 as u64));
+        let scheme = SharingScheme::new(constants::PROVER_NUM, constants::THRESHOLD);
+        let secret = ReplicaSecret::new(Scalar::from(1 as u64), &scheme);
         let splits=secret.get_splits();
         let mut sum=Scalar::zero();
-        for i in 0..constants::SPLIT_LEN{
+        for i in 0..scheme.split_len(){
             sum+=splits[i];
         }
         println!("sum:{}",sum.to_string());
         let mut shares =Vec::new();
-        for i in 1..constants::PROVER_NUM{
+        for i in 1..scheme.prover_num(){
             shares.push(secret.get_share(i))
         }
 
-        let res=recon_shares(shares);
+        let res=recon_shares(&scheme, shares);
         println!("{}",res.unwrap().to_string());
         assert_eq!(res.unwrap(),Scalar::from(1 as u64))
     }
 
-    
-}
\ No newline at end of file
+    #[test]
+    fn test_scheme_table(){
+        let scheme = SharingScheme::new(3, 1);
+        assert_eq!(scheme.split_len(), 3);
+        assert_eq!(scheme.share_len(), 2);
+        assert_eq!(scheme.ind_arr(), &[vec![0, 1], vec![0, 2], vec![1, 2]]);
+
+        let scheme = SharingScheme::new(5, 2);
+        assert_eq!(scheme.split_len(), 10);
+        assert_eq!(scheme.share_len(), 6);
+        assert_eq!(scheme.ind_arr(), &[
+            vec![0, 1, 2, 3, 4, 5],
+            vec![0, 1, 2, 6, 7, 8],
+            vec![0, 3, 4, 6, 7, 9],
+            vec![1, 3, 5, 6, 8, 9],
+            vec![2, 4, 5, 7, 8, 9],
+        ]);
+        assert_eq!(scheme.holders(9), &[2, 3, 4]);
+
+        // 任意 threshold 个 prover 都缺少至少一个 split
+        let secret = ReplicaSecret::new(Scalar::from(7u64), &scheme);
+        let shares = vec![secret.get_share(0), secret.get_share(4)];
+        assert!(recon_shares(&scheme, shares).is_none());
+        let shares = vec![secret.get_share(0), secret.get_share(3), secret.get_share(4)];
+        assert_eq!(recon_shares(&scheme, shares).unwrap(), Scalar::from(7u64));
+    }
+
+    #[test]
+    fn test_recon_checked(){
+        let scheme = SharingScheme::new(3, 1);
+        let base = CommitBase::new(b"seed");
+        let secret = ReplicaSecret::new(Scalar::from(5u64), &scheme);
+        let com = ReplicaCommitment::new(&scheme, secret.commit(base.clone()));
+        let mut shares: Vec<ReplicaShare> = (0..3).map(|i| secret.get_share(i)).collect();
+        assert_eq!(recon_checked(&scheme, &base, &shares, &com, None), Ok(vec![Scalar::from(5u64)]));
+
+        // prover 1 篡改了第二个 split 的拷贝，承诺指认出它
+        shares[1].share[1] += Scalar::one();
+        let report = recon_checked(&scheme, &base, &shares, &com, None).unwrap_err();
+        assert_eq!(report, BlameReport { cheaters: vec![1], unresolved_splits: vec![] });
+
+        // 去掉 prover 2 后 split 2 只剩错误拷贝
+        let report = recon_checked(&scheme, &base, &shares[..2], &com, None).unwrap_err();
+        assert_eq!(report, BlameReport { cheaters: vec![1], unresolved_splits: vec![2] });
+
+        // prover 1 的同一份错误份额交两次，不能当作两个持有者一致
+        let report = recon_checked(&scheme, &base, &[shares[1].clone(), shares[1].clone()], &com, None).unwrap_err();
+        assert_eq!(report, BlameReport { cheaters: vec![1], unresolved_splits: vec![1, 2] });
+
+        // 带噪声的拷贝各不相同，逐个对照噪声承诺
+        let shares: Vec<ReplicaShare> = (0..3).map(|i| secret.get_share(i).add_noise(vec![Scalar::from(i as u64 + 1); 2], vec![Scalar::one(); 2])).collect();
+        let noise_coms: Vec<Vec<G1Projective>> = (0..3).map(|i| vec![base.commit(Scalar::from(i as u64 + 1), Scalar::one()); 2]).collect();
+        // 各 split 取第一个有效拷贝：split 0、1 来自 prover 0，split 2 来自 prover 1
+        let sum = recon_checked(&scheme, &base, &shares, &com, Some(&noise_coms)).unwrap();
+        assert_eq!(sum, vec![Scalar::from(5u64 + 1 + 1 + 2)]);
+        assert_eq!(recon_checked(&scheme, &base, &shares, &com, None).unwrap_err().unresolved_splits, vec![0, 1, 2]);
+    }
+}
@@ -1,15 +1,44 @@
+use serde::{Serialize, Deserialize};
 use std::collections::HashMap;
+use std::path::Path;
 use std::sync::RwLock;
 use crate::replicated::ReplicaShare;
+use crate::record_log::{FsyncPolicy, RecordLog};
+use crate::error::{Error, Result};
+
+/// The seed a prover's noise of one session is drawn from, and whether its noisy share of the
+/// session was released.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SessionNoise {
+    pub seed: [u8; 32],
+    pub released: bool,
+}
+
+// 会话的噪声种子只能写一次，之后只能把 released 置位
+fn check_noise(old: Option<&SessionNoise>, new: &SessionNoise, session_id: u64) -> Result<()> {
+    match old {
+        Some(old) if old.seed != new.seed => Err(Error::NoiseExists(session_id)),
+        Some(old) if old.released && !new.released => Err(Error::NoisyShareReleased),
+        _ => Ok(()),
+    }
+}
 
 pub trait ShareStore {
-    fn put(&mut self, uid: u64, share: ReplicaShare);
+    fn put(&mut self, uid: u64, share: ReplicaShare) -> Result<()>;
     fn get(&self, uid: u64) -> Option<ReplicaShare>;
     fn get_all(&self) -> Vec<(u64, ReplicaShare)>;
+
+    /// The prover's noise of `session_id`, if it was drawn.
+    fn get_noise(&self, session_id: u64) -> Option<SessionNoise>;
+
+    /// Records the noise of `session_id`. Fails if the session has a different seed or its
+    /// release would be undone.
+    fn put_noise(&mut self, session_id: u64, noise: SessionNoise) -> Result<()>;
 }
 
 pub struct MemoryShareStore {
     shares: RwLock<HashMap<u64, ReplicaShare>>,
+    noise: RwLock<HashMap<u64, SessionNoise>>,
 }
 
 impl Default for MemoryShareStore {
@@ -22,14 +51,16 @@ impl MemoryShareStore {
     pub fn new() -> Self {
         MemoryShareStore {
             shares: RwLock::new(HashMap::new()),
+            noise: RwLock::new(HashMap::new()),
         }
     }
 }
 
 impl ShareStore for MemoryShareStore {
-    fn put(&mut self, uid: u64, share: ReplicaShare) {
-        let mut shares = self.shares.write().unwrap();
+    fn put(&mut self, uid: u64, share: ReplicaShare) -> Result<()> {
+        let mut shares = self.shares.write()?;
         shares.insert(uid, share);
+        Ok(())
     }
 
     fn get(&self, uid: u64) -> Option<ReplicaShare> {
@@ -41,4 +72,205 @@ impl ShareStore for MemoryShareStore {
         let shares = self.shares.read().unwrap();
         shares.iter().map(|(k, v)| (*k, v.clone())).collect()
     }
+
+    fn get_noise(&self, session_id: u64) -> Option<SessionNoise> {
+        self.noise.read().ok()?.get(&session_id).copied()
+    }
+
+    fn put_noise(&mut self, session_id: u64, noise: SessionNoise) -> Result<()> {
+        let mut sessions = self.noise.write()?;
+        check_noise(sessions.get(&session_id), &noise, session_id)?;
+        sessions.insert(session_id, noise);
+        Ok(())
+    }
+}
+
+// 记录格式版本号，写在每条记录的第一个字节
+// 1: (uid, share)
+// 2: ShareRecord，增加了会话的噪声记录；仍可读取版本 1 的记录
+const SHARE_RECORD_VERSION: u8 = 2;
+
+#[derive(Serialize, Deserialize)]
+enum ShareRecord {
+    Share(u64, ReplicaShare),
+    Noise(u64, SessionNoise),
+}
+
+fn encode_record(record: &ShareRecord) -> Result<Vec<u8>> {
+    let mut payload = vec![SHARE_RECORD_VERSION];
+    payload.extend(bcs::to_bytes(record).map_err(|e| Error::Store(e.to_string()))?);
+    Ok(payload)
+}
+
+fn decode_record(payload: &[u8]) -> Result<ShareRecord> {
+    let decoded = match payload.split_first() {
+        Some((&1, rest)) => bcs::from_bytes(rest).map(|(uid, share)| ShareRecord::Share(uid, share)),
+        Some((&SHARE_RECORD_VERSION, rest)) => bcs::from_bytes(rest),
+        _ => return Err(Error::Store("unknown share record version".to_string())),
+    };
+    decoded.map_err(|e| Error::Store(e.to_string()))
+}
+
+/// A `ShareStore` backed by an append-only log file, so a restarted prover keeps every share
+/// it accepted and the noise of every session. Only the offset of each client's latest record
+/// is kept in memory, plus the (small) noise records.
+pub struct FileShareStore {
+    log: RecordLog,
+    index: HashMap<u64, u64>,
+    noise: HashMap<u64, SessionNoise>,
+    dead_records: usize,
+}
+
+impl FileShareStore {
+    /// Opens the store at `path`, recovering from a crash during the last write.
+    pub fn open<P: AsRef<Path>>(path: P, policy: FsyncPolicy) -> Result<Self> {
+        let (log, records) = RecordLog::open(path, policy)?;
+        let mut index = HashMap::new();
+        let mut noise = HashMap::new();
+        let mut dead_records = 0;
+        for (offset, payload) in records {
+            let replaced = match decode_record(&payload)? {
+                ShareRecord::Share(uid, _) => index.insert(uid, offset).is_some(),
+                ShareRecord::Noise(session_id, record) => noise.insert(session_id, record).is_some(),
+            };
+            if replaced {
+                dead_records += 1;
+            }
+        }
+        Ok(Self { log, index, noise, dead_records })
+    }
+
+    /// Number of records overwritten by a later `put` and still taking up space.
+    pub fn dead_records(&self) -> usize {
+        self.dead_records
+    }
+
+    pub fn sync(&mut self) -> Result<()> {
+        Ok(self.log.sync()?)
+    }
+
+    /// Rewrites the log with only the latest share of each client and the latest noise
+    /// record of each session.
+    pub fn compact(&mut self) -> Result<()> {
+        let mut live: Vec<(u64, u64)> = self.index.iter().map(|(&uid, &offset)| (uid, offset)).collect();
+        live.sort_unstable();
+        let mut payloads = live.iter().map(|&(_, offset)| self.log.read_at(offset)).collect::<std::io::Result<Vec<_>>>()?;
+        for (&session_id, &noise) in &self.noise {
+            payloads.push(encode_record(&ShareRecord::Noise(session_id, noise))?);
+        }
+        let offsets = self.log.rewrite(payloads)?;
+        self.index = live.into_iter().map(|(uid, _)| uid).zip(offsets).collect();
+        self.dead_records = 0;
+        Ok(())
+    }
+}
+
+impl ShareStore for FileShareStore {
+    fn put(&mut self, uid: u64, share: ReplicaShare) -> Result<()> {
+        let offset = self.log.append(&encode_record(&ShareRecord::Share(uid, share))?)?;
+        if self.index.insert(uid, offset).is_some() {
+            self.dead_records += 1;
+        }
+        Ok(())
+    }
+
+    fn get(&self, uid: u64) -> Option<ReplicaShare> {
+        let payload = self.log.read_at(*self.index.get(&uid)?).ok()?;
+        match decode_record(&payload).ok()? {
+            ShareRecord::Share(_, share) => Some(share),
+            ShareRecord::Noise(..) => None,
+        }
+    }
+
+    fn get_all(&self) -> Vec<(u64, ReplicaShare)> {
+        let mut uids: Vec<u64> = self.index.keys().cloned().collect();
+        uids.sort_unstable();
+        uids.into_iter().filter_map(|uid| Some((uid, self.get(uid)?))).collect()
+    }
+
+    fn get_noise(&self, session_id: u64) -> Option<SessionNoise> {
+        self.noise.get(&session_id).copied()
+    }
+
+    // 先写日志再更新内存
+    fn put_noise(&mut self, session_id: u64, noise: SessionNoise) -> Result<()> {
+        check_noise(self.noise.get(&session_id), &noise, session_id)?;
+        if self.noise.get(&session_id) == Some(&noise) {
+            return Ok(());
+        }
+        self.log.append(&encode_record(&ShareRecord::Noise(session_id, noise))?)?;
+        if self.noise.insert(session_id, noise).is_some() {
+            self.dead_records += 1;
+        }
+        Ok(())
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::fs::OpenOptions;
+    use std::io::Write;
+    use blstrs::Scalar;
+    use crate::record_log::FsyncPolicy;
+    use crate::replicated::{ReplicaSecret, SharingScheme};
+    use crate::error::Error;
+    use super::{FileShareStore, MemoryShareStore, SessionNoise, ShareStore};
+
+    // 两种实现共用的检查
+    fn check_store<S: ShareStore>(store: &mut S) {
+        let scheme = SharingScheme::new(3, 1);
+        assert!(store.get(1).is_none());
+        for uid in 0..4u64 {
+            store.put(uid, ReplicaSecret::new(Scalar::from(uid), &scheme).get_share(0)).unwrap();
+        }
+        let share = ReplicaSecret::new(Scalar::from(9u64), &scheme).get_share(0);
+        store.put(2, share.clone()).unwrap();
+        assert_eq!(store.get(2).unwrap().get_share(), share.get_share());
+        let mut all = store.get_all();
+        all.sort_by_key(|(uid, _)| *uid);
+        assert_eq!(all.iter().map(|(uid, _)| *uid).collect::<Vec<_>>(), vec![0, 1, 2, 3]);
+
+        // 会话的噪声种子只写一次，公开之后不能撤销
+        let noise = SessionNoise { seed: [1; 32], released: false };
+        store.put_noise(5, noise).unwrap();
+        assert_eq!(store.put_noise(5, SessionNoise { seed: [2; 32], released: false }), Err(Error::NoiseExists(5)));
+        store.put_noise(5, SessionNoise { released: true, ..noise }).unwrap();
+        assert_eq!(store.put_noise(5, noise), Err(Error::NoisyShareReleased));
+        assert!(store.get_noise(5).unwrap().released && store.get_noise(6).is_none());
+    }
+
+    #[test]
+    fn test_share_stores() {
+        check_store(&mut MemoryShareStore::new());
+
+        let path = std::env::temp_dir().join(format!("rvdp_share_store_{}.log", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let mut store = FileShareStore::open(&path, FsyncPolicy::Every(2)).unwrap();
+        check_store(&mut store);
+        assert_eq!(store.dead_records(), 2);
+        let expected = store.get_all();
+        drop(store);
+
+        // 模拟写到一半崩溃：尾部残缺的记录在重新打开时被丢弃
+        let len = std::fs::metadata(&path).unwrap().len();
+        OpenOptions::new().append(true).open(&path).unwrap().write_all(&[200, 0, 0, 0, 1, 2, 3]).unwrap();
+        let mut store = FileShareStore::open(&path, FsyncPolicy::Always).unwrap();
+        assert_eq!(std::fs::metadata(&path).unwrap().len(), len);
+        assert_eq!(store.get_all().len(), 4);
+        assert!(store.get_noise(5).unwrap().released);
+
+        store.compact().unwrap();
+        assert_eq!(store.dead_records(), 0);
+        assert!(std::fs::metadata(&path).unwrap().len() < len);
+        drop(store);
+        let store = FileShareStore::open(&path, FsyncPolicy::Never).unwrap();
+        assert_eq!(store.get_noise(5).unwrap(), SessionNoise { seed: [1; 32], released: true });
+        let all = store.get_all();
+        assert_eq!(all.len(), expected.len());
+        for ((uid, share), (e_uid, e_share)) in all.iter().zip(expected.iter()) {
+            assert_eq!((uid, share.get_share()), (e_uid, e_share.get_share()));
+        }
+        std::fs::remove_file(&path).unwrap();
+    }
 }
@@ -9,6 +9,8 @@ use blstrs::{G1Projective, Scalar};
 
 
 use crate::commitment::{CommitBase, Commit};
+use crate::public_parameters::PublicParameters;
+use crate::replicated::ReplicaCommitment;
 use crate::util::*;use serde::{Serialize, Deserialize};
 
 #[derive(Clone, Serialize, Deserialize)]
@@ -31,11 +33,58 @@ pub struct  ProofStruct{
 //     biguint_to_scalar(&remainder) // 返回值
 // }
 
+/// Public data the Fiat–Shamir challenge of a proof is bound to, so that a proof cannot be
+/// replayed under another client id, session or set of public parameters.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ProofContext {
+    transcript: Vec<u8>,
+}
+
+impl ProofContext {
+    /// Context of a client's bit proof: session id, client id, public parameters digest and
+    /// every split commitment of the client.
+    pub fn new(session_id: u64, client_id: u64, pp: &PublicParameters, coms: &ReplicaCommitment) -> Self {
+        let mut transcript = b"client".to_vec();
+        transcript.extend(session_id.to_le_bytes());
+        transcript.extend(client_id.to_le_bytes());
+        transcript.extend(pp.digest());
+        transcript.extend((coms.len() as u64).to_le_bytes());
+        for i in 0..coms.len() {
+            transcript.extend(coms.ind_value(i).to_compressed());
+        }
+        Self { transcript }
+    }
+
+    /// Context of the bit proofs a prover publishes for its noise commitments `coms_v_k`.
+    pub fn for_noise(session_id: u64, prover_index: usize, pp: &PublicParameters) -> Self {
+        let mut transcript = b"noise".to_vec();
+        transcript.extend(session_id.to_le_bytes());
+        transcript.extend((prover_index as u64).to_le_bytes());
+        transcript.extend(pp.digest());
+        Self { transcript }
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.transcript
+    }
+}
+
+// e = H(context, com, d0, d1)
+fn challenge(ctx: &ProofContext, com: &G1Projective, d0: &G1Projective, d1: &G1Projective) -> Scalar {
+    let mut input_to_rom: Vec<u8> = Vec::new();
+    input_to_rom.extend(ctx.as_bytes());
+    // 椭圆曲线点的压缩
+    input_to_rom.extend(com.to_compressed());
+    input_to_rom.extend(d0.to_compressed());
+    input_to_rom.extend(d1.to_compressed());
+    hash_to_scalar(&input_to_rom, crate::DST_ROBUST_DP_SIGMA_OR_GENERATION)
+}
+
 // create_or_proof
 // self替换为commitbase中的g和h, 全都换到G1Projective上
 // ct_rand 表示Client的私有randomness
 // 应该传x_scalar，因为可能会有不属于{0,1}的值，
-pub fn create_proof_0(commit_base: &CommitBase, x_scalar: Scalar, ct_rand: Scalar)->ProofStruct{
+pub fn create_proof_0(commit_base: &CommitBase, x_scalar: Scalar, ct_rand: Scalar, ctx: &ProofContext)->ProofStruct{
 
     // create FIAT shamir proof for when the secret is 0
     let mut rng = thread_rng();  // 随机数生成器
@@ -46,41 +95,26 @@ pub fn create_proof_0(commit_base: &CommitBase, x_scalar: Scalar, ct_rand: Scala
     let b = random_scalar(&mut rng);
 
     // d1 : Cheat
-    let ce1 = -(&com * &e1); // 1/c^{e1}
-    // g 和 h 弄成pub的
-    let ge1 = &commit_base.bases[0] * &e1; // g^{e1}
-    let hv1 = &commit_base.bases[1] * &v1;      
+    let ce1 = -(com * e1); // 1/c^{e1}
+    let ge1 = commit_base.get_g() * e1; // g^{e1}
+    let hv1 = commit_base.get_h() * v1;
     let d1 = hv1 + ce1 + ge1; //h^{v1} x 1/c^{e1} x g^{e1}
 
     // d0 : Honest
-    let d0 = &commit_base.bases[1] * &b  ; // h^{b}  
+    let d0 = commit_base.get_h() * b; // h^{b}
 
-    //let mut hasher = Sha3_256::new();
-    let mut input_to_rom: Vec<u8> = Vec::new();
-    // 椭圆曲线点的压缩
-    input_to_rom.extend(com.to_compressed());  // 这里能修改vss里的常数COMPRESSED_SIZE: usize = 48改为32做统一吗 
-    input_to_rom.extend(d0.to_compressed());
-    input_to_rom.extend(d1.to_compressed());
-    //hasher.update(input_to_rom); // this will take d0, d1, commitment as a byte array
-    //let result: [u8;32] =  hasher.finalize().into();        
-    // In the interactive version this would come in round 2
-    // 这一块重复使用，直接替换为from_bytes_to_modscalar()函数
-    // let bignum = BigUint::from_bytes_le(&result);
-    // let remainder = bignum.mod_floor(&SCALAR_FIELD_ORDER);
-
-    // let e = biguint_to_scalar(&remainder);
-    let e: Scalar = hash_to_scalar(&input_to_rom,crate::DST_ROBUST_DP_SIGMA_OR_GENERATION);
-    let e0 = e - e1;         
+    let e = challenge(ctx, &com, &d0, &d1);
+    let e0 = e - e1;
     let v0 = b + e0*ct_rand;
 
-    return ProofStruct{e0, e1, e, v0, v1, d0, d1};
+    ProofStruct{e0, e1, e, v0, v1, d0, d1}
 
 }
 
 
-pub fn create_proof_1(commit_base: &CommitBase, x_scalar: Scalar, ct_rand: Scalar)->ProofStruct{
+pub fn create_proof_1(commit_base: &CommitBase, x_scalar: Scalar, ct_rand: Scalar, ctx: &ProofContext)->ProofStruct{
 
-    // create FIAT shamir proof for when the secret is 0
+    // create FIAT shamir proof for when the secret is 1
     let mut rng = thread_rng();  // 随机数生成器
 
     let com = commit_base.commit(x_scalar, ct_rand);
@@ -89,73 +123,177 @@ pub fn create_proof_1(commit_base: &CommitBase, x_scalar: Scalar, ct_rand: Scala
     let b = random_scalar(&mut rng);
 
     // d0 : Cheat
-    let ce0 = -(&com * &e0); // 1/c^{e0}
-    let hv0 = &commit_base.bases[1] * &v0;  // h^{0}   
+    let ce0 = -(com * e0); // 1/c^{e0}
+    let hv0 = commit_base.get_h() * v0;  // h^{v0}
     let d0 = hv0 + ce0; //h^{v0} x 1/c^{e0} 
 
     // d1 : Honest
-    let d1 = &commit_base.bases[1] * &b  ; // h^{b}  
+    let d1 = commit_base.get_h() * b; // h^{b}
 
-    //let mut hasher = Sha3_256::new();
-    let mut input_to_rom: Vec<u8> = Vec::new();
-    // 椭圆曲线点的压缩
-    input_to_rom.extend(com.to_compressed());  // 这里能修改vss里的常数COMPRESSED_SIZE: usize = 48改为32做统一吗 
-    input_to_rom.extend(d0.to_compressed());
-    input_to_rom.extend(d1.to_compressed());
-    //hasher.update(input_to_rom); // this will take d0, d1, commitment as a byte array
-    //let result: [u8;32] =  hasher.finalize().into();        
-    // In the interactive version this would come in round 2
+    let e = challenge(ctx, &com, &d0, &d1);
+    let e1 = e - e0;
+    let v1 = b + e1*ct_rand;
 
-    // let bignum = BigUint::from_bytes_le(&result);
-    // let remainder = bignum.mod_floor(&SCALAR_FIELD_ORDER);
+    ProofStruct{e0, e1, e, v0, v1, d0, d1}
 
-    // let e = biguint_to_scalar(&remainder);
-    let e: Scalar = hash_to_scalar(&input_to_rom,crate::DST_ROBUST_DP_SIGMA_OR_GENERATION);
-    let e1 = e - e0;         
-    let v1 = b + e1*ct_rand;
+}
+
+/// The equation of the OR proof that failed to verify.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SigmaOrError {
+    /// e != H(com, d0, d1)
+    ChallengeHash,
+    /// e != e0 + e1
+    ChallengeSplit,
+    /// d0 c^{e0} != h^{v0}
+    Branch0,
+    /// d1 c^{e1} != g^{e1} h^{v1}
+    Branch1,
+}
+
+impl std::fmt::Display for SigmaOrError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SigmaOrError::ChallengeHash => write!(f, "challenge does not match the transcript hash"),
+            SigmaOrError::ChallengeSplit => write!(f, "challenge is not the sum of e0 and e1"),
+            SigmaOrError::Branch0 => write!(f, "d0 branch equation does not hold"),
+            SigmaOrError::Branch1 => write!(f, "d1 branch equation does not hold"),
+        }
+    }
+}
 
-    return ProofStruct{e0, e1, e, v0, v1, d0, d1};
+impl std::error::Error for SigmaOrError {}
 
+/// Proves that `commit_base.commit(bit, ct_rand)` opens to `bit`, picking the honest branch.
+pub fn create_bit_proof(commit_base: &CommitBase, bit: bool, ct_rand: Scalar, ctx: &ProofContext) -> ProofStruct {
+    let x_scalar = Scalar::from(bit as u64);
+    if bit {
+        create_proof_1(commit_base, x_scalar, ct_rand, ctx)
+    } else {
+        create_proof_0(commit_base, x_scalar, ct_rand, ctx)
+    }
 }
 
 // verify_or_proof
-pub fn sigma_or_verify(commit_base: &CommitBase, pf_scalar: &ProofStruct, reconcom:G1Projective) -> bool {
+// 不会 panic，返回第一个不成立的等式
+pub fn sigma_or_check(commit_base: &CommitBase, pf_scalar: &ProofStruct, reconcom:G1Projective, ctx: &ProofContext) -> Result<(), SigmaOrError> {
 
     // CHECK the hash of the initial pf_scalar is equal to e and then 
-    //let mut hasher = Sha3_256::new();
-    let mut input_to_rom: Vec<u8> = Vec::new();
-    input_to_rom.extend(reconcom.to_compressed());
-    input_to_rom.extend(pf_scalar.d0.to_compressed());
-    input_to_rom.extend(pf_scalar.d1.to_compressed());
-    //hasher.update(input_to_rom); // this will take d0, d1, commitment as a byte array
-    //let result: [u8;32] =  hasher.finalize().into();
-
-    // let bignum = BigUint::from_bytes_le(&result);
-    // let remainder = bignum.mod_floor(&SCALAR_FIELD_ORDER);
-        
-    // let e = biguint_to_scalar(&remainder);
-    let e: Scalar = hash_to_scalar(&input_to_rom,crate::DST_ROBUST_DP_SIGMA_OR_GENERATION);
-    assert_eq!(e, pf_scalar.e);
+    let e = challenge(ctx, &reconcom, &pf_scalar.d0, &pf_scalar.d1);
+    if e != pf_scalar.e {
+        return Err(SigmaOrError::ChallengeHash);
+    }
 
     // pf_scalar.e = hash(d0,d1, com. )
-    assert_eq!(pf_scalar.e, pf_scalar.e1 + pf_scalar.e0); // CHECK e = e0 + e1
+    if pf_scalar.e != pf_scalar.e1 + pf_scalar.e0 { // CHECK e = e0 + e1
+        return Err(SigmaOrError::ChallengeSplit);
+    }
 
-    let ce0 = &reconcom * &pf_scalar.e0; //c^{e0}
-    let hv0 = commit_base.bases[1] * &pf_scalar.v0; //h^{v0}
-    assert_eq!(&pf_scalar.d0 + &ce0, hv0); //d0 c^{e0} = h^{v0}
+    let ce0 = reconcom * pf_scalar.e0; //c^{e0}
+    let hv0 = commit_base.bases[1] * pf_scalar.v0; //h^{v0}
+    if pf_scalar.d0 + ce0 != hv0 { //d0 c^{e0} = h^{v0}
+        return Err(SigmaOrError::Branch0);
+    }
+
+    let ce1 = reconcom * pf_scalar.e1; // c^{e1}
 
-    let ce1 = &reconcom * &pf_scalar.e1; // c^{e1}
+    let ge1 = commit_base.bases[0] * pf_scalar.e1; // g^{e1}
+    let hv1 = commit_base.bases[1] * pf_scalar.v1;// h^{v1}
 
-    let ge1 = commit_base.bases[0] * &pf_scalar.e1; // g^{e1}
-    let hv1 = commit_base.bases[1] * &pf_scalar.v1;// h^{v1}
+    if pf_scalar.d1 + ce1 != ge1 + hv1 { //d1 c^{e1} = g^{e1}h^{v1}
+        return Err(SigmaOrError::Branch1);
+    }
 
-    assert_eq!(&pf_scalar.d1 + &ce1, &ge1 + &hv1); //d1 c^{e1} = g^{e1}h^{v1}
+    Ok(())
+}
 
-    return true;
+pub fn sigma_or_verify(commit_base: &CommitBase, pf_scalar: &ProofStruct, reconcom:G1Projective, ctx: &ProofContext) -> bool {
+    sigma_or_check(commit_base, pf_scalar, reconcom, ctx).is_ok()
 }
 
 impl ProofStruct{
-    pub fn verify(&self, commit_base: &CommitBase, reconcom:G1Projective) -> bool {
-        sigma_or_verify(commit_base, self, reconcom)
+    pub fn check(&self, commit_base: &CommitBase, reconcom:G1Projective, ctx: &ProofContext) -> Result<(), SigmaOrError> {
+        sigma_or_check(commit_base, self, reconcom, ctx)
+    }
+
+    pub fn verify(&self, commit_base: &CommitBase, reconcom:G1Projective, ctx: &ProofContext) -> bool {
+        sigma_or_verify(commit_base, self, reconcom, ctx)
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use blstrs::Scalar;
+    use ff::Field;
+    use group::Group;
+
+    use crate::commitment::Commit;
+    use crate::public_parameters::PublicParameters;
+    use crate::replicated::{ReplicaCommitment, ReplicaSecret, SharingScheme};
+    use super::{create_proof_0, create_proof_1, ProofContext, SigmaOrError};
+
+    #[test]
+    fn test_sigma_or_rejects_without_panic() {
+        let pp = PublicParameters::new(b"seed");
+        let base = pp.get_commit_base();
+        let ctx = ProofContext::default();
+        let r = Scalar::from(5u64);
+        let com0 = base.commit(Scalar::zero(), r);
+        let com1 = base.commit(Scalar::one(), r);
+
+        let proof0 = create_proof_0(base, Scalar::zero(), r, &ctx);
+        let proof1 = create_proof_1(base, Scalar::one(), r, &ctx);
+        assert_eq!(proof0.check(base, com0, &ctx), Ok(()));
+        assert_eq!(proof1.check(base, com1, &ctx), Ok(()));
+        assert_eq!(proof0.check(base, com1, &ctx), Err(SigmaOrError::ChallengeHash));
+
+        let mut bad = proof0.clone();
+        bad.e1 += Scalar::one();
+        assert_eq!(bad.check(base, com0, &ctx), Err(SigmaOrError::ChallengeSplit));
+
+        let mut bad = proof0.clone();
+        bad.v0 += Scalar::one();
+        assert_eq!(bad.check(base, com0, &ctx), Err(SigmaOrError::Branch0));
+
+        let mut bad = proof1.clone();
+        bad.v1 += Scalar::one();
+        assert_eq!(bad.check(base, com1, &ctx), Err(SigmaOrError::Branch1));
+
+        // 非比特值的承诺无法通过验证
+        let com2 = base.commit(Scalar::from(2u64), r);
+        let proof2 = create_proof_1(base, Scalar::from(2u64), r, &ctx);
+        assert!(!proof2.verify(base, com2, &ctx));
+        assert!(!proof0.verify(base, blstrs::G1Projective::identity(), &ctx));
+    }
+
+    #[test]
+    fn test_proof_bound_to_context() {
+        let pp = PublicParameters::new(b"seed");
+        let scheme = SharingScheme::new(3, 1);
+        let secret = ReplicaSecret::new(Scalar::one(), &scheme);
+        let coms = ReplicaCommitment::new(&scheme, secret.commit(pp.get_commit_base().clone()));
+        let reconcom = coms.get_sum();
+
+        let ctx = ProofContext::new(7, 42, &pp, &coms);
+        let proof = create_proof_1(pp.get_commit_base(), Scalar::one(), secret.get_sum_r(), &ctx);
+        assert!(proof.verify(pp.get_commit_base(), reconcom, &ctx));
+
+        // 换会话、换用户、换公共参数都无法重放
+        let other_session = ProofContext::new(8, 42, &pp, &coms);
+        let other_client = ProofContext::new(7, 43, &pp, &coms);
+        let other_pp = ProofContext::new(7, 42, &PublicParameters::new(b"other"), &coms);
+        for other in [other_session, other_client, other_pp] {
+            assert_eq!(proof.check(pp.get_commit_base(), reconcom, &other), Err(SigmaOrError::ChallengeHash));
+        }
+
+        // 和不变但单个承诺不同也无法重放
+        let mut shifted = secret.commit(pp.get_commit_base().clone());
+        let delta = pp.get_g();
+        shifted[0] += delta;
+        shifted[1] -= delta;
+        let shifted = ReplicaCommitment::new(&scheme, shifted);
+        assert_eq!(shifted.get_sum(), reconcom);
+        let other_coms = ProofContext::new(7, 42, &pp, &shifted);
+        assert!(!proof.verify(pp.get_commit_base(), reconcom, &other_coms));
+    }
+}
@@ -0,0 +1,34 @@
+// 测试共用的部署：n 个 prover、门限 t 的方案，会话 0，每个 prover 有自己的内存份额库
+use ed25519_dalek::{SigningKey, VerifyingKey};
+use crate::prover::Prover;
+use crate::public_parameters::PublicParameters;
+use crate::replicated::SharingScheme;
+use crate::share_store::MemoryShareStore;
+use crate::sign;
+
+pub struct Setup {
+    pub scheme: SharingScheme,
+    pub pp: PublicParameters,
+    pub sig_keys: Vec<SigningKey>,
+    pub pks: Vec<VerifyingKey>,
+    pub provers: Vec<Prover<'static, MemoryShareStore>>,
+}
+
+/// `n` provers with threshold `t` and 4 noise bits.
+pub fn setup(n: usize, t: usize) -> Setup {
+    setup_with(n, t, PublicParameters::new(b"seed").with_noise_bits(4))
+}
+
+/// `n` provers with threshold `t` under `pp`.
+pub fn setup_with(n: usize, t: usize, pp: PublicParameters) -> Setup {
+    let scheme = SharingScheme::new(n, t);
+    let (sig_keys, pks): (Vec<_>, Vec<_>) = (0..n).map(|_| sign::gen_keys()).unzip();
+    // 份额库要活得比 prover 久，测试里直接泄漏
+    let provers = (0..n)
+        .map(|i| {
+            let store = Box::leak(Box::new(MemoryShareStore::new()));
+            Prover::new(i, 0, &pp, &scheme, sig_keys[i].clone(), &pks, store).unwrap()
+        })
+        .collect();
+    Setup { scheme, pp, sig_keys, pks, provers }
+}
@@ -0,0 +1,306 @@
+// prover、公告板与其它参与方之间的 TLS 1.3 信道（rustls）
+// 服务端用自己的 ed25519 签名密钥生成自签名证书，连接方不依赖 CA，而是要求证书中的公钥
+// 就是 pks 文件里该 prover（或公告板）的 VerifyingKey；连接方用自己的 ed25519 密钥做客户端认证，
+// 服务端只接受 PeerDirectory 里登记过的密钥：各 prover、verifier 和注册表中的客户端
+use ed25519_dalek::{SigningKey, VerifyingKey};
+use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+use rustls::crypto::{verify_tls13_signature, CryptoProvider, WebPkiSupportedAlgorithms};
+use rustls::pki_types::{CertificateDer, PrivateKeyDer, PrivatePkcs8KeyDer, ServerName, UnixTime};
+use rustls::server::danger::{ClientCertVerified, ClientCertVerifier};
+use rustls::{CertificateError, ClientConfig, ClientConnection, DigitallySignedStruct, DistinguishedName, ServerConfig, ServerConnection, SignatureScheme, StreamOwned};
+use std::io;
+use std::net::{self, SocketAddr};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::net::{TcpStream, ToSocketAddrs};
+use tokio_rustls::{client, server, TlsAcceptor, TlsConnector};
+use crate::async_communicator::AsyncStreamCommunicator;
+use crate::client_auth::ClientRegistry;
+use crate::communicator::SyncStreamCommunicator;
+use crate::protocol::Role;
+
+pub type TlsClientCommunicator = AsyncStreamCommunicator<client::TlsStream<TcpStream>>;
+pub type TlsServerCommunicator = AsyncStreamCommunicator<server::TlsStream<TcpStream>>;
+pub type SyncTlsClientCommunicator = SyncStreamCommunicator<StreamOwned<ClientConnection, net::TcpStream>>;
+pub type SyncTlsServerCommunicator = SyncStreamCommunicator<StreamOwned<ServerConnection, net::TcpStream>>;
+
+// RFC 8410 中 ed25519 的 PKCS#8 私钥和 SPKI 公钥前缀
+const PKCS8_PREFIX: [u8; 16] = [0x30, 0x2e, 0x02, 0x01, 0x00, 0x30, 0x05, 0x06, 0x03, 0x2b, 0x65, 0x70, 0x04, 0x22, 0x04, 0x20];
+const SPKI_PREFIX: [u8; 12] = [0x30, 0x2a, 0x30, 0x05, 0x06, 0x03, 0x2b, 0x65, 0x70, 0x03, 0x21, 0x00];
+
+fn provider() -> Arc<CryptoProvider> {
+    Arc::new(rustls::crypto::ring::default_provider())
+}
+
+fn algorithms() -> WebPkiSupportedAlgorithms {
+    rustls::crypto::ring::default_provider().signature_verification_algorithms
+}
+
+fn tls_error(e: impl std::error::Error + Send + Sync + 'static) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, e)
+}
+
+fn private_key(sk: &SigningKey) -> PrivateKeyDer<'static> {
+    let mut der = PKCS8_PREFIX.to_vec();
+    der.extend(sk.to_bytes());
+    PrivateKeyDer::Pkcs8(PrivatePkcs8KeyDer::from(der))
+}
+
+/// A self-signed certificate for the ed25519 key `sk`.
+pub fn self_signed_certificate(sk: &SigningKey, name: &str) -> io::Result<CertificateDer<'static>> {
+    let PrivateKeyDer::Pkcs8(der) = private_key(sk) else { unreachable!() };
+    let key_pair = rcgen::KeyPair::from_pkcs8_der_and_sign_algo(&der, &rcgen::PKCS_ED25519)
+        .map_err(tls_error)?;
+    let params = rcgen::CertificateParams::new(vec![name.to_string()]).map_err(tls_error)?;
+    Ok(params.self_signed(&key_pair).map_err(tls_error)?.der().clone())
+}
+
+/// The ed25519 key a certificate is issued for; `None` for malformed or non-ed25519 certificates.
+pub fn certificate_key(cert: &CertificateDer<'_>) -> Option<VerifyingKey> {
+    let cert = webpki::EndEntityCert::try_from(cert).ok()?;
+    let spki = cert.subject_public_key_info();
+    let key = spki.as_ref().strip_prefix(&SPKI_PREFIX[..])?;
+    VerifyingKey::from_bytes(key.try_into().ok()?).ok()
+}
+
+/// The keys peers may authenticate with, and the role each one stands for.
+#[derive(Clone, Debug)]
+pub struct PeerDirectory {
+    provers: Vec<VerifyingKey>,
+    verifier: VerifyingKey,
+    clients: ClientRegistry,
+}
+
+impl PeerDirectory {
+    pub fn new(provers: &[VerifyingKey], verifier: VerifyingKey, clients: ClientRegistry) -> Self {
+        Self { provers: provers.to_vec(), verifier, clients }
+    }
+
+    pub fn get_prover_keys(&self) -> &[VerifyingKey] {
+        &self.provers
+    }
+
+    pub fn role_of(&self, pk: &VerifyingKey) -> Option<Role> {
+        if let Some(ind) = self.provers.iter().position(|p| p == pk) {
+            return Some(Role::Prover(ind));
+        }
+        if *pk == self.verifier {
+            return Some(Role::Verifier);
+        }
+        self.clients.get_id(pk).map(Role::Client)
+    }
+}
+
+/// Accepts exactly the certificate of one server's key.
+#[derive(Debug)]
+struct ServerKeyVerifier {
+    expected: VerifyingKey,
+}
+
+impl ServerCertVerifier for ServerKeyVerifier {
+    fn verify_server_cert(&self, end_entity: &CertificateDer<'_>, _: &[CertificateDer<'_>], _: &ServerName<'_>, _: &[u8], _: UnixTime) -> Result<ServerCertVerified, rustls::Error> {
+        match certificate_key(end_entity) {
+            Some(key) if key == self.expected => Ok(ServerCertVerified::assertion()),
+            Some(_) => Err(rustls::Error::InvalidCertificate(CertificateError::ApplicationVerificationFailure)),
+            None => Err(rustls::Error::InvalidCertificate(CertificateError::BadEncoding)),
+        }
+    }
+
+    fn verify_tls12_signature(&self, _: &[u8], _: &CertificateDer<'_>, _: &DigitallySignedStruct) -> Result<HandshakeSignatureValid, rustls::Error> {
+        Err(rustls::Error::General("TLS 1.2 is not supported".to_string()))
+    }
+
+    fn verify_tls13_signature(&self, message: &[u8], cert: &CertificateDer<'_>, dss: &DigitallySignedStruct) -> Result<HandshakeSignatureValid, rustls::Error> {
+        verify_tls13_signature(message, cert, dss, &algorithms())
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        vec![SignatureScheme::ED25519]
+    }
+}
+
+/// Accepts clients presenting a certificate of a key in `peers`, or none unless `mandatory`.
+#[derive(Debug)]
+struct ClientKeyVerifier {
+    peers: Arc<PeerDirectory>,
+    mandatory: bool,
+}
+
+impl ClientCertVerifier for ClientKeyVerifier {
+    fn client_auth_mandatory(&self) -> bool {
+        self.mandatory
+    }
+
+    fn root_hint_subjects(&self) -> &[DistinguishedName] {
+        &[]
+    }
+
+    fn verify_client_cert(&self, end_entity: &CertificateDer<'_>, _: &[CertificateDer<'_>], _: UnixTime) -> Result<ClientCertVerified, rustls::Error> {
+        match certificate_key(end_entity) {
+            Some(key) if self.peers.role_of(&key).is_some() => Ok(ClientCertVerified::assertion()),
+            Some(_) => Err(rustls::Error::InvalidCertificate(CertificateError::ApplicationVerificationFailure)),
+            None => Err(rustls::Error::InvalidCertificate(CertificateError::BadEncoding)),
+        }
+    }
+
+    fn verify_tls12_signature(&self, _: &[u8], _: &CertificateDer<'_>, _: &DigitallySignedStruct) -> Result<HandshakeSignatureValid, rustls::Error> {
+        Err(rustls::Error::General("TLS 1.2 is not supported".to_string()))
+    }
+
+    fn verify_tls13_signature(&self, message: &[u8], cert: &CertificateDer<'_>, dss: &DigitallySignedStruct) -> Result<HandshakeSignatureValid, rustls::Error> {
+        verify_tls13_signature(message, cert, dss, &algorithms())
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        vec![SignatureScheme::ED25519]
+    }
+}
+
+/// Client side: trusts only the prover or board holding the key `server_pk`, and authenticates
+/// with `identity` if given.
+pub fn client_config(server_pk: VerifyingKey, identity: Option<&SigningKey>) -> io::Result<Arc<ClientConfig>> {
+    let builder = ClientConfig::builder_with_provider(provider())
+        .with_protocol_versions(&[&rustls::version::TLS13]).map_err(tls_error)?
+        .dangerous()
+        .with_custom_certificate_verifier(Arc::new(ServerKeyVerifier { expected: server_pk }));
+    let config = match identity {
+        Some(sk) => builder.with_client_auth_cert(vec![self_signed_certificate(sk, "client")?], private_key(sk)).map_err(tls_error)?,
+        None => builder.with_no_client_auth(),
+    };
+    Ok(Arc::new(config))
+}
+
+/// Server side: presents a certificate for `sig_key` and accepts only peers in `peers`; peers
+/// must authenticate if `require_client_auth`.
+pub fn server_config(sig_key: &SigningKey, peers: Arc<PeerDirectory>, require_client_auth: bool) -> io::Result<Arc<ServerConfig>> {
+    let config = ServerConfig::builder_with_provider(provider())
+        .with_protocol_versions(&[&rustls::version::TLS13]).map_err(tls_error)?
+        .with_client_cert_verifier(Arc::new(ClientKeyVerifier { peers, mandatory: require_client_auth }))
+        .with_single_cert(vec![self_signed_certificate(sig_key, "server")?], private_key(sig_key))
+        .map_err(tls_error)?;
+    Ok(Arc::new(config))
+}
+
+/// Connects to a prover and completes the handshake within `timeout`.
+pub async fn connect<A: ToSocketAddrs>(addr: A, config: Arc<ClientConfig>, timeout: Option<Duration>) -> io::Result<TlsClientCommunicator> {
+    let handshake = async {
+        let stream = TcpStream::connect(addr).await?;
+        // 证书不绑定域名，名字只用于 SNI
+        let name = ServerName::try_from("server").map_err(tls_error)?;
+        TlsConnector::from(config).connect(name, stream).await
+    };
+    let stream = match timeout {
+        Some(t) => tokio::time::timeout(t, handshake).await.map_err(|_| io::Error::new(io::ErrorKind::TimedOut, "Handshake timed out"))??,
+        None => handshake.await?,
+    };
+    Ok(AsyncStreamCommunicator::new(stream).with_timeout(timeout))
+}
+
+/// Completes the server side of the handshake on an accepted connection.
+pub async fn accept(stream: TcpStream, config: Arc<ServerConfig>, timeout: Option<Duration>) -> io::Result<TlsServerCommunicator> {
+    let handshake = TlsAcceptor::from(config).accept(stream);
+    let stream = match timeout {
+        Some(t) => tokio::time::timeout(t, handshake).await.map_err(|_| io::Error::new(io::ErrorKind::TimedOut, "Handshake timed out"))??,
+        None => handshake.await?,
+    };
+    Ok(AsyncStreamCommunicator::new(stream).with_timeout(timeout))
+}
+
+/// The key a client authenticated with, if any.
+pub fn client_key(comm: &TlsServerCommunicator) -> Option<VerifyingKey> {
+    comm.get_ref().get_ref().1.peer_certificates()?.first().and_then(certificate_key)
+}
+
+/// Blocking counterpart of `connect`; `timeout` bounds the connection, the handshake and every
+/// later read and write.
+pub fn connect_sync(addr: &SocketAddr, config: Arc<ClientConfig>, timeout: Duration) -> io::Result<SyncTlsClientCommunicator> {
+    let sock = net::TcpStream::connect_timeout(addr, timeout)?;
+    sock.set_read_timeout(Some(timeout))?;
+    sock.set_write_timeout(Some(timeout))?;
+    let name = ServerName::try_from("server").map_err(tls_error)?;
+    let conn = ClientConnection::new(config, name).map_err(tls_error)?;
+    let mut stream = StreamOwned::new(conn, sock);
+    while stream.conn.is_handshaking() {
+        stream.conn.complete_io(&mut stream.sock)?;
+    }
+    Ok(SyncStreamCommunicator::new(stream))
+}
+
+/// Blocking counterpart of `accept`.
+pub fn accept_sync(sock: net::TcpStream, config: Arc<ServerConfig>, timeout: Option<Duration>) -> io::Result<SyncTlsServerCommunicator> {
+    sock.set_read_timeout(timeout)?;
+    sock.set_write_timeout(timeout)?;
+    let conn = ServerConnection::new(config).map_err(tls_error)?;
+    let mut stream = StreamOwned::new(conn, sock);
+    while stream.conn.is_handshaking() {
+        stream.conn.complete_io(&mut stream.sock)?;
+    }
+    Ok(SyncStreamCommunicator::new(stream))
+}
+
+/// Blocking counterpart of `client_key`.
+pub fn sync_client_key(comm: &SyncTlsServerCommunicator) -> Option<VerifyingKey> {
+    comm.get_ref().conn.peer_certificates()?.first().and_then(certificate_key)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use tokio::net::TcpListener;
+    use crate::async_communicator::AsyncCommunicator;
+    use crate::communicator::Communicator;
+    use crate::sign;
+    use super::*;
+
+    #[tokio::test]
+    async fn test_mutual_tls() {
+        let (prover_sk, prover_pk) = sign::gen_keys();
+        let (client_sk, client_pk) = sign::gen_keys();
+        let (other_sk, other_pk) = sign::gen_keys();
+        assert_eq!(certificate_key(&self_signed_certificate(&prover_sk, "prover").unwrap()), Some(prover_pk));
+        let mut registry = ClientRegistry::new();
+        registry.register(3, client_pk).unwrap();
+        let peers = PeerDirectory::new(&[prover_pk], sign::gen_keys().1, registry);
+        assert_eq!(peers.role_of(&client_pk), Some(Role::Client(3)));
+        assert_eq!(peers.role_of(&prover_pk), Some(Role::Prover(0)));
+        assert_eq!(peers.role_of(&other_pk), None);
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let config = server_config(&prover_sk, Arc::new(peers), true).unwrap();
+        tokio::spawn(async move {
+            loop {
+                let (stream, _) = listener.accept().await.unwrap();
+                if let Ok(mut comm) = accept(stream, config.clone(), Some(Duration::from_secs(5))).await {
+                    let x: u64 = comm.receive().await.unwrap();
+                    let key = client_key(&comm).unwrap();
+                    comm.send(&(x + 1, key.to_bytes())).await.unwrap();
+                }
+            }
+        });
+
+        let timeout = Some(Duration::from_secs(5));
+        let mut comm = connect(addr, client_config(prover_pk, Some(&client_sk)).unwrap(), timeout).await.unwrap();
+        comm.send(&41u64).await.unwrap();
+        assert_eq!(comm.receive::<(u64, [u8; 32])>().await.unwrap(), (42, client_pk.to_bytes()));
+
+        // 同步客户端与异步服务端使用同样的信道
+        let config = client_config(prover_pk, Some(&client_sk)).unwrap();
+        let reply = tokio::task::spawn_blocking(move || {
+            let mut comm = connect_sync(&addr, config, Duration::from_secs(5)).unwrap();
+            Communicator::send(&mut comm, &6u64).unwrap();
+            Communicator::receive::<(u64, [u8; 32])>(&mut comm).unwrap()
+        }).await.unwrap();
+        assert_eq!(reply, (7, client_pk.to_bytes()));
+
+        // 服务端的密钥不是期望的 prover 公钥
+        assert!(connect(addr, client_config(other_pk, Some(&client_sk)).unwrap(), timeout).await.is_err());
+        // 服务端要求客户端认证，且只接受登记过的密钥
+        for identity in [None, Some(&other_sk)] {
+            let mut comm = connect(addr, client_config(prover_pk, identity).unwrap(), timeout).await.unwrap();
+            comm.send(&1u64).await.ok();
+            assert!(comm.receive::<(u64, [u8; 32])>().await.is_err());
+        }
+    }
+}
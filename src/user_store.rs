@@ -1,31 +1,61 @@
-use crate::constants;
-use crate::replicated::{ReplicaShare, ReplicaCommitment};
-use crate::sigma_or::ProofStruct;
+use crate::replicated::{ReplicaShare, ReplicaCommitment, SharingScheme};
+use crate::sigma_or::ProofContext;
+use crate::range_proof::InputProof;
 use crate::sign::{MySignature,verify_sig};
 use crate::public_parameters::PublicParameters;
+use crate::record_log::{FsyncPolicy, RecordLog, Records};
+use crate::error::{Error, Result};
 use ed25519_dalek::VerifyingKey;
-use serde::de;
+use serde::{Serialize, Deserialize};
 use std::collections::HashMap;
+use std::path::Path;
 use std::sync::RwLock;
 use std::collections::HashSet;
 
 
-#[derive(Clone)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct User{
     pub id: u64,
     pub commitment: ReplicaCommitment,
-    pub sigma_proof: ProofStruct,
-    pub signatures: [Option<MySignature>; constants::PROVER_NUM],
-    pub share: [Option<ReplicaShare>; constants::PROVER_NUM]
+    pub sigma_proof: InputProof,
+    pub submission_sig: Option<MySignature>, // 客户端用身份密钥对提交的签名，公告板验证客户端身份时才有
+    pub signatures: Vec<Option<MySignature>>,
+    pub share: Vec<Option<ReplicaShare>>
 }
 
 
 impl User {
-    pub fn check_signature(&self, pks: &Vec<VerifyingKey>) -> HashSet<usize> {
+    pub fn new(scheme: &SharingScheme, id: u64, commitment: ReplicaCommitment, sigma_proof: InputProof) -> Self {
+        User {
+            id,
+            commitment,
+            sigma_proof,
+            submission_sig: None,
+            signatures: vec![None; scheme.prover_num()],
+            share: vec![None; scheme.prover_num()],
+        }
+    }
+
+    /// Checks that the record has a signature and a share slot for every prover, as a record
+    /// decoded from the network or disk may not.
+    pub fn check_slots(&self, scheme: &SharingScheme) -> Result<()> {
+        if self.signatures.len() != scheme.prover_num() || self.share.len() != scheme.prover_num() {
+            return Err(Error::BadRecord(self.id));
+        }
+        Ok(())
+    }
+
+    /// Verifies the input proof against this user's id and commitments in session `session_id`.
+    pub fn check_proof(&self, scheme: &SharingScheme, session_id: u64, pp: &PublicParameters) -> Result<()> {
+        let ctx = ProofContext::new(session_id, self.id, pp, &self.commitment);
+        self.sigma_proof.check(pp, &self.commitment.bucket_sums(scheme), &ctx)
+    }
+
+    pub fn check_signature(&self, pks: &[VerifyingKey]) -> HashSet<usize> {
         let mut res = HashSet::new();
-        for i in 0..constants::PROVER_NUM {
-            if let Some(sig) = &self.signatures[i] {
-                if verify_sig(&self.commitment, &pks[i], &sig.clone().into()) {
+        for (i, (sig, pk)) in self.signatures.iter().zip(pks.iter()).enumerate() {
+            if let Some(sig) = sig {
+                if verify_sig(&self.commitment, pk, &sig.clone().into()) {
                     res.insert(i);
                 }
             }
@@ -34,11 +64,11 @@ impl User {
     }
 
 
-    pub fn check_share(&self, pp: &PublicParameters) -> HashSet<usize> {
+    pub fn check_share(&self, scheme: &SharingScheme, pp: &PublicParameters) -> HashSet<usize> {
         let mut res = HashSet::new();
-        for i in 0..constants::PROVER_NUM {
-            if let Some(share) = &self.share[i] {
-                if share.check_com(pp.get_commit_base(), self.commitment.clone()) {
+        for (i, share) in self.share.iter().enumerate() {
+            if let Some(share) = share {
+                if share.get_ind() == i && share.check_com(scheme, pp.get_commit_base(), self.commitment.clone()) {
                     res.insert(i);
                 }
             }
@@ -46,24 +76,35 @@ impl User {
         res
     }
 
-    pub fn check_whole(&self, pks: &Vec<VerifyingKey>,pp: &PublicParameters) -> bool {
-        let reconcom = self.commitment.get_sum();
-        if !self.sigma_proof.verify(pp.get_commit_base(), reconcom){
-            return false;
+    /// Checks the proof and that every prover either signed or has a valid revealed share.
+    pub fn validate(&self, scheme: &SharingScheme, session_id: u64, pks: &[VerifyingKey], pp: &PublicParameters) -> Result<()> {
+        if self.commitment.len() != scheme.split_len() * pp.get_buckets() {
+            return Err(Error::MalformedCommitment);
         }
-        let shares=self.check_share(pp);
+        self.check_proof(scheme, session_id, pp)?;
+        let shares=self.check_share(scheme, pp);
         let sigs=self.check_signature(pks);
-        //shares add sigs should be equal to 0..PROVER_NUM
-        let union=shares.union(&sigs).cloned().collect::<HashSet<_>>();
-        union.len()==constants::PROVER_NUM
+        //shares add sigs should be equal to 0..prover_num
+        let missing: Vec<usize> = (0..scheme.prover_num()).filter(|i| !shares.contains(i) && !sigs.contains(i)).collect();
+        if !missing.is_empty() {
+            return Err(Error::MissingProvers(missing));
+        }
+        Ok(())
+    }
+
+    pub fn check_whole(&self, scheme: &SharingScheme, session_id: u64, pks: &[VerifyingKey], pp: &PublicParameters) -> bool {
+        self.validate(scheme, session_id, pks, pp).is_ok()
     }
 
-    pub fn check_whole_lazy(&self, pks: &Vec<VerifyingKey>, pp: &PublicParameters, proverid: usize) -> (bool,Option<ReplicaShare>) {
-        let shares = self.check_share(pp);
+    pub fn check_whole_lazy(&self, scheme: &SharingScheme, session_id: u64, pks: &[VerifyingKey], pp: &PublicParameters, proverid: usize) -> (bool,Option<ReplicaShare>) {
+        if self.commitment.len() != scheme.split_len() * pp.get_buckets() {
+            return (false, None);
+        }
+        let shares = self.check_share(scheme, pp);
         let sigs = self.check_signature(pks);
         // shares and sigs should cover all provers
         let union = shares.union(&sigs).cloned().collect::<HashSet<_>>();
-        if union.len() < constants::PROVER_NUM {
+        if union.len() < scheme.prover_num() {
             return (false, None);
         }
 
@@ -71,123 +112,329 @@ impl User {
             return (true, None);
         }
 
-        let reconcom = self.commitment.get_sum();
-        if !self.sigma_proof.verify(pp.get_commit_base(), reconcom) {
+        if self.check_proof(scheme, session_id, pp).is_err() {
             return (false, None);
         }
 
-        (true, self.share[proverid].clone())
+        (true, self.share.get(proverid).cloned().flatten())
     }
 }
 
 
+// 每个 prover 的签名和公开份额只能写一次；再次提交同样的值视为成功。返回是否写入了新值
+fn post_once<T: PartialEq>(slot: &mut Option<T>, value: T, id: u64, prover: usize) -> Result<bool> {
+    match slot {
+        Some(old) if *old == value => Ok(false),
+        Some(_) => Err(Error::AlreadyPosted { id, prover }),
+        None => {
+            *slot = Some(value);
+            Ok(true)
+        }
+    }
+}
+
 pub trait UserStore {
-    fn new_user(&mut self, id: u64, commitment: ReplicaCommitment, sigma_proof: ProofStruct) -> bool;
+    /// Adds a user; fails if the id already exists.
+    fn new_user(&mut self, id: u64, commitment: ReplicaCommitment, sigma_proof: InputProof) -> Result<()>;
+
+    /// Adds a user whose submission is signed with the client's identity key. The signature is
+    /// kept in the record so that anyone can re-check it.
+    fn new_signed_user(&mut self, id: u64, commitment: ReplicaCommitment, sigma_proof: InputProof, sig: &MySignature) -> Result<()>;
 
     fn get_user(&self, id: u64) -> Option<User>;
 
-    fn get_user_commitment_proof(&self, id: u64) -> Option<(ReplicaCommitment, ProofStruct)>;
+    fn get_user_commitment_proof(&self, id: u64) -> Option<(ReplicaCommitment, InputProof)>;
 
-    fn sig_to_user(&mut self, id: u64, sig: MySignature, proverid: usize) -> bool;
+    fn sig_to_user(&mut self, id: u64, sig: MySignature, proverid: usize) -> Result<()>;
 
-    fn upload_share(&mut self, id: u64, share: ReplicaShare, proverid: usize) -> bool;
+    fn upload_share(&mut self, id: u64, share: ReplicaShare, proverid: usize) -> Result<()>;
 
-    fn iter_all_users(&self) -> Option<Box<dyn Iterator<Item = User>>>;
+    fn iter_all_users(&self) -> Result<Box<dyn Iterator<Item = User>>>;
 
-    fn check_all_users(&self, pks: &Vec<VerifyingKey>, pp: &PublicParameters) -> Vec<u64>;
+    fn check_all_users(&self, session_id: u64, pks: &[VerifyingKey], pp: &PublicParameters) -> Vec<u64>;
 }
 
 pub struct MemoryUserStore {
+    scheme: SharingScheme,
     users: RwLock<HashMap<u64, User>>
 }
 
 impl MemoryUserStore {
-    pub fn new() -> Self {
+    pub fn new(scheme: &SharingScheme) -> Self {
         MemoryUserStore {
+            scheme: scheme.clone(),
             users: RwLock::new(HashMap::new()),
         }
     }
 }
 
-impl UserStore for MemoryUserStore {
-    fn new_user(&mut self, id: u64, commitment: ReplicaCommitment, sigma_proof: ProofStruct) -> bool {
-        let user = User {
-            id,
-            commitment,
-            sigma_proof,
-            signatures: core::array::from_fn(|_| None),
-            share: core::array::from_fn(|_| None),
-        };
-        match self.users.write() {
-            Ok(mut users) => {
-                users.insert(id, user);
-                true
-            },
-            Err(_) => false,
+impl MemoryUserStore {
+    fn insert(&mut self, user: User) -> Result<()> {
+        let mut users = self.users.write()?;
+        if users.contains_key(&user.id) {
+            return Err(Error::DuplicateUser(user.id));
         }
+        users.insert(user.id, user);
+        Ok(())
+    }
+
+    // 把用户 id 恢复成 before（None 即删除），供 MerkleBoard 写日志失败时回滚
+    pub(crate) fn restore(&mut self, id: u64, before: Option<User>) -> Result<()> {
+        let mut users = self.users.write()?;
+        match before {
+            Some(user) => users.insert(id, user),
+            None => users.remove(&id),
+        };
+        Ok(())
+    }
+}
+
+impl UserStore for MemoryUserStore {
+    fn new_user(&mut self, id: u64, commitment: ReplicaCommitment, sigma_proof: InputProof) -> Result<()> {
+        self.insert(User::new(&self.scheme, id, commitment, sigma_proof))
+    }
+
+    fn new_signed_user(&mut self, id: u64, commitment: ReplicaCommitment, sigma_proof: InputProof, sig: &MySignature) -> Result<()> {
+        let mut user = User::new(&self.scheme, id, commitment, sigma_proof);
+        user.submission_sig = Some(sig.clone());
+        self.insert(user)
     }
 
     fn get_user(&self, id: u64) -> Option<User> {
         self.users.read().ok()?.get(&id).cloned()
     }
 
-    fn get_user_commitment_proof(&self, id: u64) -> Option<(ReplicaCommitment, ProofStruct)> {
+    fn get_user_commitment_proof(&self, id: u64) -> Option<(ReplicaCommitment, InputProof)> {
         self.users.read().ok()?.get(&id).map(|user| (user.commitment.clone(), user.sigma_proof.clone()))
     }
 
-    fn sig_to_user(&mut self, id: u64, sig: MySignature, proverid: usize) -> bool {
-        if proverid >= constants::PROVER_NUM {
-            return false;
+    fn sig_to_user(&mut self, id: u64, sig: MySignature, proverid: usize) -> Result<()> {
+        if proverid >= self.scheme.prover_num() {
+            return Err(Error::UnknownProver(proverid));
+        }
+        let mut users = self.users.write()?;
+        let user = users.get_mut(&id).ok_or(Error::UnknownUser(id))?;
+        post_once(user.signatures.get_mut(proverid).ok_or(Error::BadRecord(id))?, sig, id, proverid)?;
+        Ok(())
+    }
+
+    fn upload_share(&mut self, id: u64, share: ReplicaShare, proverid: usize) -> Result<()> {
+        if proverid >= self.scheme.prover_num() {
+            return Err(Error::UnknownProver(proverid));
         }
-        match self.users.write() {
-            Ok(mut users) => {
-                if let Some(user) = users.get_mut(&id) {
-                    user.signatures[proverid] = Some(sig);
-                    true
-                } else {
-                    false
+        let mut users = self.users.write()?;
+        let user = users.get_mut(&id).ok_or(Error::UnknownUser(id))?;
+        post_once(user.share.get_mut(proverid).ok_or(Error::BadRecord(id))?, share, id, proverid)?;
+        Ok(())
+    }
+
+    fn iter_all_users(&self) -> Result<Box<dyn Iterator<Item = User>>> {
+        let users = self.users.read()?;
+        Ok(Box::new(users.values().cloned().collect::<Vec<_>>().into_iter()))
+    }
+
+    fn check_all_users(&self, session_id: u64, pks: &[VerifyingKey], pp: &PublicParameters) -> Vec<u64> {
+        let mut valid_user_ids = Vec::new();
+
+        if let Ok(users_iter) = self.iter_all_users() {
+            for user in users_iter {
+                if user.check_whole(&self.scheme, session_id, pks, pp) {
+                    valid_user_ids.push(user.id);
                 }
-            },
-            Err(_) => false,
+            }
         }
+
+        valid_user_ids.sort();
+        valid_user_ids
     }
+}
+
+// 用户记录格式版本号，写在每条记录的第一个字节
+// 2: User 增加了 submission_sig
+const USER_RECORD_VERSION: u8 = 2;
+
+fn encode_user(user: &User) -> Result<Vec<u8>> {
+    let mut payload = vec![USER_RECORD_VERSION];
+    payload.extend(bcs::to_bytes(user).map_err(|e| Error::Store(e.to_string()))?);
+    Ok(payload)
+}
 
-    fn upload_share(&mut self, id: u64, share: ReplicaShare, proverid: usize) -> bool {
-        if proverid >= constants::PROVER_NUM {
-            return false;
+fn decode_user(payload: &[u8], scheme: &SharingScheme) -> Result<User> {
+    let user: User = match payload.split_first() {
+        Some((&USER_RECORD_VERSION, rest)) => bcs::from_bytes(rest).map_err(|e| Error::Store(e.to_string()))?,
+        _ => return Err(Error::Store("unknown user record version".to_string())),
+    };
+    user.check_slots(scheme)?;
+    Ok(user)
+}
+
+/// A bulletin board persisted in an append-only log: every change to a user appends the whole
+/// updated `User` record, and only the offset of each user's latest record is kept in memory.
+pub struct FileUserStore {
+    scheme: SharingScheme,
+    log: RecordLog,
+    index: HashMap<u64, u64>,
+}
+
+impl FileUserStore {
+    pub fn open<P: AsRef<Path>>(path: P, scheme: &SharingScheme, policy: FsyncPolicy) -> Result<Self> {
+        let (log, records) = RecordLog::open(path, policy)?;
+        Self::from_log(log, records, scheme)
+    }
+
+    /// Opens the board for auditing, e.g. by the verifier; every write fails.
+    pub fn open_read_only<P: AsRef<Path>>(path: P, scheme: &SharingScheme) -> Result<Self> {
+        let (log, records) = RecordLog::open_read_only(path)?;
+        Self::from_log(log, records, scheme)
+    }
+
+    fn from_log(log: RecordLog, records: Records, scheme: &SharingScheme) -> Result<Self> {
+        let mut index = HashMap::new();
+        for (offset, payload) in records {
+            index.insert(decode_user(&payload, scheme)?.id, offset);
         }
-        match self.users.write() {
-            Ok(mut users) => {
-                if let Some(user) = users.get_mut(&id) {
-                    user.share[proverid] = Some(share);
-                    true
-                } else {
-                    false
-                }
-            },
-            Err(_) => false,
+        Ok(Self { scheme: scheme.clone(), log, index })
+    }
+
+    pub fn sync(&mut self) -> Result<()> {
+        Ok(self.log.sync()?)
+    }
+
+    /// Rewrites the log with only the latest record of each user.
+    pub fn compact(&mut self) -> Result<()> {
+        let mut live: Vec<(u64, u64)> = self.index.iter().map(|(&id, &offset)| (id, offset)).collect();
+        live.sort_unstable();
+        let payloads = live.iter().map(|&(_, offset)| self.log.read_at(offset)).collect::<std::io::Result<Vec<_>>>()?;
+        let offsets = self.log.rewrite(payloads)?;
+        self.index = live.into_iter().map(|(id, _)| id).zip(offsets).collect();
+        Ok(())
+    }
+
+    fn put(&mut self, user: &User) -> Result<()> {
+        let offset = self.log.append(&encode_user(user)?)?;
+        self.index.insert(user.id, offset);
+        Ok(())
+    }
+
+    fn insert(&mut self, user: User) -> Result<()> {
+        if self.index.contains_key(&user.id) {
+            return Err(Error::DuplicateUser(user.id));
         }
+        self.put(&user)
     }
 
-    fn iter_all_users(&self) -> Option<Box<dyn Iterator<Item = User>>> {
-        match self.users.read() {
-            Ok(users) => Some(Box::new(users.values().cloned().collect::<Vec<_>>().into_iter())),
-            Err(_) => None,
+    fn update(&mut self, id: u64, proverid: usize, f: impl FnOnce(&mut User) -> Result<bool>) -> Result<()> {
+        if proverid >= self.scheme.prover_num() {
+            return Err(Error::UnknownProver(proverid));
+        }
+        let mut user = self.get_user(id).ok_or(Error::UnknownUser(id))?;
+        if f(&mut user)? {
+            self.put(&user)?;
         }
+        Ok(())
+    }
+}
+
+impl UserStore for FileUserStore {
+    fn new_user(&mut self, id: u64, commitment: ReplicaCommitment, sigma_proof: InputProof) -> Result<()> {
+        self.insert(User::new(&self.scheme, id, commitment, sigma_proof))
     }
 
-    fn check_all_users(&self, pks: &Vec<VerifyingKey>, pp: &PublicParameters) -> Vec<u64> {
+    fn new_signed_user(&mut self, id: u64, commitment: ReplicaCommitment, sigma_proof: InputProof, sig: &MySignature) -> Result<()> {
+        let mut user = User::new(&self.scheme, id, commitment, sigma_proof);
+        user.submission_sig = Some(sig.clone());
+        self.insert(user)
+    }
+
+    fn get_user(&self, id: u64) -> Option<User> {
+        decode_user(&self.log.read_at(*self.index.get(&id)?).ok()?, &self.scheme).ok()
+    }
+
+    fn get_user_commitment_proof(&self, id: u64) -> Option<(ReplicaCommitment, InputProof)> {
+        self.get_user(id).map(|user| (user.commitment, user.sigma_proof))
+    }
+
+    fn sig_to_user(&mut self, id: u64, sig: MySignature, proverid: usize) -> Result<()> {
+        self.update(id, proverid, |user| post_once(user.signatures.get_mut(proverid).ok_or(Error::BadRecord(id))?, sig, id, proverid))
+    }
+
+    fn upload_share(&mut self, id: u64, share: ReplicaShare, proverid: usize) -> Result<()> {
+        self.update(id, proverid, |user| post_once(user.share.get_mut(proverid).ok_or(Error::BadRecord(id))?, share, id, proverid))
+    }
+
+    // 按 id 顺序从独立的文件句柄逐个读取，不在内存中复制所有用户
+    fn iter_all_users(&self) -> Result<Box<dyn Iterator<Item = User>>> {
+        let reader = self.log.reader()?;
+        let mut offsets: Vec<(u64, u64)> = self.index.iter().map(|(&id, &offset)| (id, offset)).collect();
+        offsets.sort_unstable();
+        let scheme = self.scheme.clone();
+        Ok(Box::new(offsets.into_iter().filter_map(move |(_, offset)| decode_user(&reader.read_at(offset).ok()?, &scheme).ok())))
+    }
+
+    fn check_all_users(&self, session_id: u64, pks: &[VerifyingKey], pp: &PublicParameters) -> Vec<u64> {
         let mut valid_user_ids = Vec::new();
 
-        if let Some(users_iter) = self.iter_all_users() {
+        if let Ok(users_iter) = self.iter_all_users() {
             for user in users_iter {
-                if user.check_whole(pks, pp) {
+                if user.check_whole(&self.scheme, session_id, pks, pp) {
                     valid_user_ids.push(user.id);
                 }
             }
         }
 
-        valid_user_ids.sort();
         valid_user_ids
     }
-}
\ No newline at end of file
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::client::Client;
+    use crate::public_parameters::PublicParameters;
+    use crate::record_log::FsyncPolicy;
+    use crate::replicated::SharingScheme;
+    use crate::sign::{self, sign_verified_deal};
+    use crate::error::Error;
+    use super::{FileUserStore, UserStore};
+
+    #[test]
+    fn test_file_user_store() {
+        let scheme = SharingScheme::new(3, 1);
+        let pp = PublicParameters::new(b"seed");
+        let (sig_keys, pks): (Vec<_>, Vec<_>) = (0..3).map(|_| sign::gen_keys()).unzip();
+        let path = std::env::temp_dir().join(format!("rvdp_user_store_{}.log", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+
+        let mut broad = FileUserStore::open(&path, &scheme, FsyncPolicy::Always).unwrap();
+        for id in 0..3u64 {
+            let client = Client::new(0, id, true, &pp, &scheme, pks.clone());
+            client.send_proof_coms(&mut broad).unwrap();
+            for (i, sig_key) in sig_keys.iter().enumerate().take(2) {
+                broad.sig_to_user(id, sign_verified_deal(sig_key, &client.get_coms()).into(), i).unwrap();
+            }
+            // prover 2 没有签名，客户端公开它的份额
+            client.reveal_share(&mut broad).unwrap();
+        }
+        assert!(broad.sig_to_user(9, Default::default(), 0).is_err());
+        assert_eq!(Client::new(0, 1, false, &pp, &scheme, pks.clone()).send_proof_coms(&mut broad), Err(Error::DuplicateUser(1)));
+        assert_eq!(broad.check_all_users(0, &pks, &pp), vec![0, 1, 2]);
+        drop(broad);
+
+        let mut audit = FileUserStore::open_read_only(&path, &scheme).unwrap();
+        assert_eq!(audit.check_all_users(0, &pks, &pp), vec![0, 1, 2]);
+        // 重复公开同一份额不写入；只读的日志拒绝任何新内容，已有的份额也不能被覆盖
+        assert!(audit.upload_share(0, audit.get_user(0).unwrap().share[2].clone().unwrap(), 2).is_ok());
+        assert!(audit.sig_to_user(0, Default::default(), 2).is_err());
+        assert_eq!(audit.upload_share(0, audit.get_user(1).unwrap().share[2].clone().unwrap(), 2), Err(Error::AlreadyPosted { id: 0, prover: 2 }));
+
+        // 记录的签名和份额槽数与 prover 数不符时拒绝打开，而不是之后按下标越界
+        assert!(matches!(FileUserStore::open_read_only(&path, &SharingScheme::new(5, 2)), Err(Error::BadRecord(0))));
+
+        let mut broad = FileUserStore::open(&path, &scheme, FsyncPolicy::Never).unwrap();
+        let len = std::fs::metadata(&path).unwrap().len();
+        broad.compact().unwrap();
+        assert!(std::fs::metadata(&path).unwrap().len() < len);
+        assert_eq!(broad.iter_all_users().unwrap().map(|u| u.id).collect::<Vec<_>>(), vec![0, 1, 2]);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
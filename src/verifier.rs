@@ -1,54 +1,135 @@
-use blstrs::G1Projective;
+use blstrs::{G1Projective, Scalar};
 use group::Group;
-use ed25519_dalek::VerifyingKey;
-use crate::{constants, util};
+use ed25519_dalek::{SigningKey, VerifyingKey};
+use crate::bulletin_board::{BoardLog, BoardMonitor};
 use crate::public_parameters::PublicParameters;
-use crate::replicated::{ReplicaShare, ReplicaCommitment};
+use crate::public_randomness::check_shape;
+use crate::receipt::ValidUserSet;
+use crate::replicated::{recon_checked, ReplicaShare, ReplicaCommitment, SharingScheme};
 use crate::user_store::UserStore;
-use blstrs::Scalar;
+use crate::sigma_or::{ProofContext, ProofStruct};
+use crate::error::{Error, Result};
 
 pub struct Verifier {
+    scheme: SharingScheme,
+    session_id: u64,
     coms_v_ks: Vec<Vec<Vec<G1Projective>>>,
     pks: Vec<VerifyingKey>,
+    rejected_provers: Vec<usize>, // 噪声承诺未被证明为比特的 prover
 }
 
 
 impl Verifier {
-    pub fn new(coms_v_ks: Vec<Vec<Vec<G1Projective>>>, pks: Vec<VerifyingKey>) -> Self {
+    /// Checks the bit proofs each prover published for its noise commitments; provers whose
+    /// commitments are malformed or not proven to be bits are rejected for the whole session.
+    pub fn new(scheme: &SharingScheme, session_id: u64, pp: &PublicParameters, coms_v_ks: Vec<Vec<Vec<G1Projective>>>, noise_proofs: Vec<Vec<Vec<ProofStruct>>>, pks: Vec<VerifyingKey>) -> Self {
+        let mut rejected_provers = Vec::new();
+        for ind in 0..scheme.prover_num() {
+            let valid = match (coms_v_ks.get(ind), noise_proofs.get(ind)) {
+                (Some(coms), Some(proofs)) => check_noise_proofs(scheme, session_id, pp, ind, coms, proofs),
+                _ => false,
+            };
+            if !valid {
+                rejected_provers.push(ind);
+            }
+        }
         Self {
+            scheme: scheme.clone(),
+            session_id,
             coms_v_ks,
             pks,
+            rejected_provers,
         }
     }
 
-    pub fn check_all_users_and_sum_coms<B:UserStore>(&self, broad: &B, pp: &PublicParameters) -> ReplicaCommitment {
-        let mut valid_user_ids = Vec::new();
-        let mut sum_com = ReplicaCommitment::new_zero();
-        let mut all_users = broad.iter_all_users().unwrap();
-        while let Some(user) = all_users.next() {
-            if user.check_whole(&self.pks, pp) {
-                valid_user_ids.push(user.id);
+    pub fn get_rejected_provers(&self) -> Vec<usize> {
+        self.rejected_provers.clone()
+    }
+
+    pub fn check_all_users_and_sum_coms<B:UserStore>(&self, broad: &B, pp: &PublicParameters) -> Result<ReplicaCommitment> {
+        let mut sum_com = ReplicaCommitment::new_zero(&self.scheme, pp.get_buckets());
+        for user in broad.iter_all_users()? {
+            if user.check_whole(&self.scheme, self.session_id, &self.pks, pp) {
                 sum_com = sum_com + user.commitment.clone();
             }
         }
-        sum_com
+        Ok(sum_com)
+    }
+
+    /// The ids of the users counted in the result, signed for publication together with the
+    /// tree head `monitor` last accepted; the users are counted from the log replayed under it.
+    pub fn publish_valid_users<L:BoardLog>(&self, monitor: &BoardMonitor, log: &L, pp: &PublicParameters, sig_key: &SigningKey) -> Result<ValidUserSet> {
+        let head = monitor.get_head().ok_or(Error::BadTreeHead)?;
+        let broad = monitor.audit(&self.scheme, log)?;
+        Ok(ValidUserSet::sign(sig_key, self.session_id, head, broad.check_all_users(self.session_id, &self.pks, pp)))
     }
 
 
 
     /// this function verifies the share of prover
-    pub fn handle_prover_share(&self,ind:usize,share:ReplicaShare, aggregated_com:ReplicaCommitment, public_rand_bits:&Vec<Vec<bool>>, pp:&PublicParameters)->bool{
+    pub fn handle_prover_share(&self,ind:usize,share:ReplicaShare, aggregated_com:ReplicaCommitment, public_rand_bits:&[Vec<bool>], pp:&PublicParameters)->Result<()>{
         let prover_id=share.get_ind();
         
-        if ind!=prover_id{
-            return false;
+        if ind!=prover_id {
+            return Err(Error::WrongProverIndex { expected: ind, got: prover_id });
+        }
+        let noise_commitments = self.noise_commitments(ind, public_rand_bits, pp)?;
+        if !share.check_com_with_noise(&self.scheme, pp.get_commit_base(),aggregated_com, noise_commitments) {
+            return Err(Error::BadShareOpening);
+        }
+        Ok(())
+    }
+
+    /// Verifies whichever noisy shares arrived, drops the invalid ones and reconstructs every
+    /// bucket from the rest. Fails with `Error::Unrecoverable` if the valid shares do not cover
+    /// every split.
+    pub fn aggregate(&self, shares: Vec<ReplicaShare>, aggregated_com: &ReplicaCommitment, public_rand_bits: &[Vec<bool>], pp: &PublicParameters) -> Result<Vec<Scalar>> {
+        // 随机比特的形状不对是调用方的问题，不能归咎于 prover
+        check_shape(public_rand_bits, self.scheme.share_len() * pp.get_buckets(), pp.get_noise_bits())?;
+        let mut valid: Vec<ReplicaShare> = Vec::new();
+        let mut dropped = Vec::new();
+        for share in shares {
+            let ind = share.get_ind();
+            if valid.iter().any(|s| s.get_ind() == ind) {
+                dropped.push((ind, Error::DuplicateShare(ind)));
+                continue;
+            }
+            match self.handle_prover_share(ind, share.clone(), aggregated_com.clone(), public_rand_bits, pp) {
+                Ok(()) => valid.push(share),
+                Err(e) => dropped.push((ind, e)),
+            }
+        }
+
+        let covered: Vec<usize> = valid.iter().flat_map(|s| self.scheme.indices(s.get_ind()).iter().cloned()).collect();
+        let missing_splits: Vec<usize> = (0..self.scheme.split_len()).filter(|j| !covered.contains(j)).collect();
+        if !missing_splits.is_empty() {
+            return Err(Error::Unrecoverable { missing_splits, dropped });
+        }
+
+        let mut noise_coms = vec![Vec::new(); self.scheme.prover_num()];
+        for share in &valid {
+            noise_coms[share.get_ind()] = self.noise_commitments(share.get_ind(), public_rand_bits, pp)?;
+        }
+        recon_checked(&self.scheme, pp.get_commit_base(), &valid, aggregated_com, Some(&noise_coms)).map_err(Error::Blame)
+    }
+
+    // prover ind 每一行噪声（按公共随机比特翻转后求和）的承诺
+    fn noise_commitments(&self, ind: usize, public_rand_bits: &[Vec<bool>], pp: &PublicParameters) -> Result<Vec<G1Projective>> {
+        if ind>=self.scheme.prover_num() {
+            return Err(Error::UnknownProver(ind));
+        }
+        if self.rejected_provers.contains(&ind) {
+            return Err(Error::RejectedProver(ind));
         }
 
         let mut coms_x_or = self.coms_v_ks[ind].clone();
         let g = pp.get_g();
         let h = pp.get_h();
-        for i in 0..constants::SHARE_LEN {
-            for j in 0..constants::BITS_NUM {
+        let rows = coms_x_or.len();
+        let noise_bits = pp.get_noise_bits();
+        check_shape(public_rand_bits, self.scheme.share_len() * pp.get_buckets(), noise_bits)?;
+        for i in 0..rows {
+            for j in 0..noise_bits {
                 if public_rand_bits[i][j] {
                     coms_x_or[i][j] = g + h - coms_x_or[i][j];
                 }
@@ -56,16 +137,86 @@ impl Verifier {
         }
 
         let mut noise_commitments = Vec::new();
-        for i in 0..constants::SHARE_LEN {
+        for row in &coms_x_or {
             let mut noise_commitment = G1Projective::identity();
-            for j in 0..constants::BITS_NUM {
-                noise_commitment += coms_x_or[i][j];
+            for com in &row[..noise_bits] {
+                noise_commitment += com;
             }
             noise_commitments.push(noise_commitment);
         }
+        Ok(noise_commitments)
+    }
 
-        share.check_com_with_noise(pp.get_commit_base(),aggregated_com, noise_commitments)
+
+}
+
+// 检查 prover ind 的噪声承诺形状正确（每个桶的每个份额位置一行）且每个都被证明是 0 或 1
+fn check_noise_proofs(scheme: &SharingScheme, session_id: u64, pp: &PublicParameters, ind: usize, coms: &[Vec<G1Projective>], proofs: &[Vec<ProofStruct>]) -> bool {
+    let rows = scheme.share_len() * pp.get_buckets();
+    if coms.len() != rows || proofs.len() != rows {
+        return false;
     }
+    let ctx = ProofContext::for_noise(session_id, ind, pp);
+    coms.iter().zip(proofs.iter()).all(|(coms, proofs)| {
+        coms.len() == pp.get_noise_bits() && proofs.len() == pp.get_noise_bits()
+            && coms.iter().zip(proofs.iter()).all(|(com, proof)| proof.verify(pp.get_commit_base(), *com, &ctx))
+    })
+}
+
+
+#[cfg(test)]
+mod tests {
+    use blstrs::Scalar;
+    use ff::Field;
+    use crate::client::Client;
+    use crate::error::Error;
+    use crate::public_randomness::expand_seed;
+    use crate::replicated::ReplicaShare;
+    use crate::test_utils::{setup, Setup};
+    use crate::user_store::MemoryUserStore;
+    use super::Verifier;
 
+    #[test]
+    fn test_aggregate_drops_failing_provers() {
+        let Setup { scheme, pp, pks, mut provers, .. } = setup(3, 1);
+        let verifier = Verifier::new(&scheme, 0, &pp,
+            provers.iter().map(|p| p.get_coms_v_k()).collect(),
+            provers.iter().map(|p| p.get_noise_proofs()).collect(), pks.clone());
 
-}
\ No newline at end of file
+        let mut broad = MemoryUserStore::new(&scheme);
+        for id in 0..4u64 {
+            let client = Client::new(0, id, id % 2 == 0, &pp, &scheme, pks.clone());
+            client.send_proof_coms(&mut broad).unwrap();
+            for (i, prover) in provers.iter_mut().enumerate() {
+                prover.handle_client(client.send_share(i), &mut broad).unwrap();
+            }
+        }
+
+        let rand_bits = expand_seed(&[7u8; 32], scheme.share_len() * pp.get_buckets(), pp.get_noise_bits());
+        let user_ids = provers[0].check_all_users(&broad).unwrap();
+        let shares: Vec<ReplicaShare> = provers.iter()
+            .map(|p| p.add_noise_from_rand_bits(&rand_bits, p.sum_share(&broad, &user_ids).unwrap()).unwrap())
+            .collect();
+        let com = verifier.check_all_users_and_sum_coms(&broad, &pp).unwrap();
+
+        // 噪声在 [0, 3 · 4] 内
+        let res = verifier.aggregate(shares.clone(), &com, &rand_bits, &pp).unwrap();
+        assert!((0..=12u64).any(|noise| res[0] == Scalar::from(2 + noise)));
+
+        // 随机比特形状错误时只报告一次，不丢弃任何 prover 的份额
+        let short = expand_seed(&[7u8; 32], scheme.split_len(), pp.get_noise_bits());
+        assert_eq!(verifier.aggregate(shares.clone(), &com, &short, &pp).unwrap_err(),
+            Error::BadPublicRandomness { rows: 2, cols: 4 });
+
+        // prover 2 离线时 prover 0、1 仍覆盖所有 split
+        assert_eq!(verifier.aggregate(shares[..2].to_vec(), &com, &rand_bits, &pp).unwrap().len(), 1);
+
+        // prover 1 的份额被篡改后被丢弃，剩下的 prover 0 覆盖不了 split 2
+        let bad = shares[1].add_noise(vec![Scalar::one(), Scalar::zero()], vec![Scalar::zero(); 2]);
+        let err = verifier.aggregate(vec![shares[0].clone(), bad, shares[0].clone()], &com, &rand_bits, &pp).unwrap_err();
+        assert_eq!(err, Error::Unrecoverable {
+            missing_splits: vec![2],
+            dropped: vec![(1, Error::BadShareOpening), (0, Error::DuplicateShare(0))],
+        });
+    }
+}